@@ -0,0 +1,126 @@
+//! Native results for the cases the wasm bindings are tested on
+//!
+//! Reads a JSON array of `{ "fn": <wasm export>, "args": [...] }` from stdin
+//! and prints the native results in the same order. `u64` arguments are
+//! decimal strings, in nested objects too, integers in results are printed as strings so JS can
+//! compare them without losing precision, and errors as `{ "error": <name> }`.
+//!
+//! ```text
+//! echo '[{"fn": "getTradeFee", "args": ["1000", "3000"]}]' | cargo run --example native_cases --features cli
+//! ```
+
+use std::io::Read;
+
+use dex_math::{
+    deposit_lp_with_protocol_fee, effective_fee_rate, get_protocol_fee, get_trade_fee, quote_pool_balances,
+    quote_pool_balances_at, rebalance_pool_ratio, split_host_fee, try_deposit_lp, try_withdraw_lp,
+    withdraw_lp_with_protocol_fee, AmmConfig, FeeSchedule, HostFeeSource, MathError, PoolBalances, Ppm,
+};
+use serde_json::{json, Value};
+
+#[derive(serde::Deserialize)]
+struct Case {
+    #[serde(rename = "fn")]
+    function: String,
+    args: Vec<Value>,
+}
+
+fn main() {
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input).expect("stdin");
+    let cases: Vec<Case> = serde_json::from_str(&input).expect("a JSON array of cases");
+
+    let results: Vec<Value> = cases.iter().map(|case| stringify_integers(run(case))).collect();
+    println!("{}", Value::Array(results));
+}
+
+fn run(case: &Case) -> Value {
+    let args = &case.args;
+    let int = |index: usize| -> u64 {
+        match &args[index] {
+            Value::String(digits) => digits.parse().expect("u64 argument"),
+            value => value.as_u64().expect("u64 argument"),
+        }
+    };
+    let int128 = |index: usize| -> u128 { args[index].as_str().expect("u128 argument").parse().expect("u128 argument") };
+    let rate = |index: usize| Ppm::new(int(index)).expect("valid rate");
+    let parse = |index: usize| parse_integers(args[index].clone());
+
+    match case.function.as_str() {
+        "quote" | "quoteAt" => {
+            let offset = usize::from(case.function == "quoteAt");
+            let amm_config: AmmConfig = serde_json::from_value(parse(offset + 2)).expect("ammConfig");
+            let balances: PoolBalances = serde_json::from_value(parse(offset + 3)).expect("poolBalances");
+            let is_swap_x_to_y = args[offset + 1].as_bool().expect("isSwapXToY");
+            let result = match amm_config.validate() {
+                Err(error) => Err(error),
+                Ok(()) if offset == 1 => quote_pool_balances_at(int(0), int(1), is_swap_x_to_y, &amm_config, &balances),
+                Ok(()) => quote_pool_balances(int(0), is_swap_x_to_y, &amm_config, &balances),
+            };
+            match result {
+                Ok(output) => serde_json::to_value(output).unwrap(),
+                Err(error) => error_value(error),
+            }
+        }
+        "depositLp" => match try_deposit_lp(int(0), int(1), int(2), int(3), int(4)) {
+            Ok(lp_tokens) => json!(lp_tokens),
+            Err(error) => error_value(error),
+        },
+        "withdrawLp" => match try_withdraw_lp(int(0), int(1), int(2), int(3)) {
+            Ok((amount_x, amount_y)) => json!({ "amount_x": amount_x, "amount_y": amount_y }),
+            Err(error) => error_value(error),
+        },
+        "depositLpWithProtocolFee" => {
+            let balances: PoolBalances = serde_json::from_value(parse(3)).expect("poolBalances");
+            let deposit = deposit_lp_with_protocol_fee(int(0), int(1), int(2), &balances, int128(4), rate(5));
+            deposit.map_or_else(error_value, |deposit| serde_json::to_value(deposit).unwrap())
+        }
+        "withdrawLpWithProtocolFee" => {
+            let balances: PoolBalances = serde_json::from_value(parse(2)).expect("poolBalances");
+            let withdrawal = withdraw_lp_with_protocol_fee(int(0), int(1), &balances, int128(3), rate(4));
+            withdrawal.map_or_else(error_value, |withdrawal| serde_json::to_value(withdrawal).unwrap())
+        }
+        "rebalancePoolRatio" => {
+            let result = rebalance_pool_ratio(int(0), int(1), int(2), int(3), int(4), rate(5));
+            serde_json::to_value(result.expect("no overflow")).unwrap()
+        }
+        "getTradeFee" => json!(get_trade_fee(int(0) as u128, int(1)).expect("valid rate") as u64),
+        "getProtocolFee" => json!(get_protocol_fee(int(0) as u128, int(1)).expect("valid rate") as u64),
+        "splitHostFee" => {
+            let source: HostFeeSource = serde_json::from_value(parse(3)).expect("hostFeeSource");
+            let (protocol_fee, host_fee) =
+                split_host_fee(int(0) as u128, int(1) as u128, rate(2), source).expect("valid split");
+            json!({ "protocol_fee": protocol_fee as u64, "host_fee": host_fee as u64 })
+        }
+        "effectiveFeeRate" => {
+            let schedule: FeeSchedule = serde_json::from_value(parse(0)).expect("feeSchedule");
+            json!(effective_fee_rate(&schedule, rate(1), int(2)).get())
+        }
+        function => panic!("unknown function {function}"),
+    }
+}
+
+fn error_value(error: MathError) -> Value {
+    json!({ "error": error.name() })
+}
+
+/// Digit strings to numbers, as the bindings read them
+fn parse_integers(value: Value) -> Value {
+    match value {
+        Value::String(digits) if !digits.is_empty() && digits.bytes().all(|byte| byte.is_ascii_digit()) => {
+            json!(digits.parse::<u64>().expect("u64 argument"))
+        }
+        Value::Array(values) => Value::Array(values.into_iter().map(parse_integers).collect()),
+        Value::Object(map) => Value::Object(map.into_iter().map(|(key, value)| (key, parse_integers(value))).collect()),
+        value => value,
+    }
+}
+
+fn stringify_integers(value: Value) -> Value {
+    match value {
+        Value::Number(number) if number.is_u64() || number.is_i64() => Value::String(number.to_string()),
+        Value::Array(values) => Value::Array(values.into_iter().map(stringify_integers).collect()),
+        Value::Object(map) => Value::Object(map.into_iter().map(|(key, value)| (key, stringify_integers(value))).collect()),
+        value => value,
+    }
+}
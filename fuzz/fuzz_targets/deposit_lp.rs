@@ -0,0 +1,52 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use dex_math::{try_deposit_lp, MathError};
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    token_a_amount: u64,
+    token_b_amount: u64,
+    total_lp_supply: u64,
+    token_a_reserve: u64,
+    token_b_reserve: u64,
+}
+
+fuzz_target!(|input: Input| {
+    let Input { token_a_amount, token_b_amount, total_lp_supply, token_a_reserve, token_b_reserve } = input;
+    let result = try_deposit_lp(token_a_amount, token_b_amount, total_lp_supply, token_a_reserve, token_b_reserve);
+
+    if total_lp_supply == 0 {
+        // the initial mint is the integer sqrt of the product
+        let lp_minted = u128::from(result.expect("the initial deposit never fails"));
+        let product = u128::from(token_a_amount) * u128::from(token_b_amount);
+        assert!(lp_minted * lp_minted <= product);
+        assert!((lp_minted + 1).checked_mul(lp_minted + 1).map_or(true, |square| square > product));
+        return;
+    }
+
+    // a zero reserve or a u64 overflow is an error, never a panic
+    let fails = token_a_reserve == 0
+        || token_b_reserve == 0
+        || token_a_amount.checked_mul(total_lp_supply).is_none()
+        || token_b_amount.checked_mul(total_lp_supply).is_none();
+    let lp_minted = match result {
+        Ok(lp_minted) => {
+            assert!(!fails);
+            lp_minted
+        }
+        Err(error) => {
+            assert!(fails);
+            assert_eq!(error, MathError::MathOverflow);
+            return;
+        }
+    };
+
+    // existing LP tokens never lose value: the new share is at most the share
+    // of each reserve deposited
+    let lp_minted = u128::from(lp_minted);
+    let total_lp_supply = u128::from(total_lp_supply);
+    assert!(lp_minted * u128::from(token_a_reserve) <= u128::from(token_a_amount) * total_lp_supply);
+    assert!(lp_minted * u128::from(token_b_reserve) <= u128::from(token_b_amount) * total_lp_supply);
+});
@@ -0,0 +1,67 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use dex_math::{quote, FeeSide, MathError};
+use dex_math_fuzz::{Config, Pool};
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    exchange_in: u64,
+    is_swap_x_to_y: bool,
+    config: Config,
+    pool: Pool,
+}
+
+fuzz_target!(|input: Input| {
+    let Input { exchange_in, is_swap_x_to_y, config: Config(amm_config), pool: Pool(balances) } = input;
+    let result = quote(
+        exchange_in,
+        is_swap_x_to_y,
+        &amm_config,
+        balances.protocol_fee_x,
+        balances.protocol_fee_y,
+        balances.user_locked_x,
+        balances.user_locked_y,
+        balances.locked_x,
+        balances.locked_y,
+        balances.reserve_x_balance,
+        balances.reserve_y_balance,
+        balances.lp_fee_x,
+        balances.lp_fee_y,
+    );
+
+    // fee buckets or locks above the vault balances are rejected up front
+    let Some((available_x, available_y)) = balances.available() else {
+        assert_eq!(result, Err(MathError::MathOverflow));
+        return;
+    };
+    let Ok(output) = result else {
+        return;
+    };
+    let (source, destination) =
+        if is_swap_x_to_y { (available_x, available_y) } else { (available_y, available_x) };
+    assert!(output.from_to_lock < source, "from_to_lock {} of {source}", output.from_to_lock);
+
+    // what the curve takes in and pays out, fees on output leave it too
+    let to_amount_swapped = match amm_config.fee_side {
+        FeeSide::Input => {
+            assert_eq!(u128::from(output.from_amount) + u128::from(output.trade_fee), u128::from(exchange_in));
+            output.to_amount
+        }
+        FeeSide::Output => {
+            assert_eq!(output.from_amount, exchange_in);
+            output.to_amount.checked_add(output.trade_fee).unwrap()
+        }
+    };
+    assert!(output.protocol_fee + output.host_fee <= output.trade_fee);
+    assert!(to_amount_swapped < destination, "{to_amount_swapped} out of {destination}");
+
+    // k never decreases, a product over u128 is above any k before
+    let k = u128::from(source) * u128::from(destination);
+    let next_source = u128::from(source) + u128::from(output.from_amount);
+    let next_destination = u128::from(destination - to_amount_swapped);
+    if let Some(next_k) = next_source.checked_mul(next_destination) {
+        assert!(next_k >= k, "k decreased from {k} to {next_k}");
+    }
+});
@@ -0,0 +1,35 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use dex_math::{rebalance_pool_ratio, Ppm, MAX_PERCENTAGE};
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    to_amount_swapped: u64,
+    current_source_amount: u64,
+    current_destination_amount: u64,
+    original_source_amount: u64,
+    original_destination_amount: u64,
+    ratio_change_tolerance_rate: u64,
+}
+
+fuzz_target!(|input: Input| {
+    let ratio_change_tolerance_rate = Ppm::new(input.ratio_change_tolerance_rate % (MAX_PERCENTAGE + 1)).unwrap();
+    let Some(result) = rebalance_pool_ratio(
+        input.to_amount_swapped,
+        input.current_source_amount,
+        input.current_destination_amount,
+        input.original_source_amount,
+        input.original_destination_amount,
+        ratio_change_tolerance_rate,
+    ) else {
+        return;
+    };
+    assert!(
+        result.from_to_lock <= input.current_source_amount,
+        "from_to_lock {} of {}",
+        result.from_to_lock,
+        input.current_source_amount
+    );
+});
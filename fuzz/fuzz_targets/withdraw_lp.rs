@@ -0,0 +1,46 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use dex_math::{try_withdraw_lp, MathError};
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    lp_tokens: u64,
+    total_lp_supply: u64,
+    token_a_reserve: u64,
+    token_b_reserve: u64,
+}
+
+fuzz_target!(|input: Input| {
+    let Input { lp_tokens, total_lp_supply, token_a_reserve, token_b_reserve } = input;
+    let result = try_withdraw_lp(lp_tokens, total_lp_supply, token_a_reserve, token_b_reserve);
+
+    // a u64 overflow is an error, never a panic
+    let fails = total_lp_supply != 0
+        && (lp_tokens.checked_mul(token_a_reserve).is_none() || lp_tokens.checked_mul(token_b_reserve).is_none());
+    let (token_a_amount, token_b_amount) = match result {
+        Ok(amounts) => {
+            assert!(!fails);
+            amounts
+        }
+        Err(error) => {
+            assert!(fails);
+            assert_eq!(error, MathError::MathOverflow);
+            return;
+        }
+    };
+    if total_lp_supply == 0 {
+        assert_eq!((token_a_amount, token_b_amount), (0, 0));
+        return;
+    }
+
+    // the remaining LP tokens never lose value: each amount is at most the
+    // burnt share of its reserve
+    let (lp, supply) = (u128::from(lp_tokens), u128::from(total_lp_supply));
+    assert!(u128::from(token_a_amount) * supply <= lp * u128::from(token_a_reserve));
+    assert!(u128::from(token_b_amount) * supply <= lp * u128::from(token_b_reserve));
+    if lp_tokens <= total_lp_supply {
+        assert!(token_a_amount <= token_a_reserve && token_b_amount <= token_b_reserve);
+    }
+});
@@ -0,0 +1,77 @@
+//! Structured inputs for the fuzz targets
+//!
+//! Run a target with the checked in seeds as a second corpus, new inputs go to
+//! the first one:
+//!
+//! ```text
+//! cargo +nightly fuzz run quote fuzz/corpus/quote fuzz/seeds/quote
+//! ```
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+use dex_math::{
+    AmmConfig, DirectionalFeeRates, FeeRates, FeeSide, HostFeeSource, PoolBalances, Ppm, MAX_PERCENTAGE,
+};
+
+/// Pool balances drawn field by field, so the fee buckets and locks may not fit
+/// in the vault balances
+#[derive(Clone, Copy, Debug)]
+pub struct Pool(pub PoolBalances);
+
+impl<'a> Arbitrary<'a> for Pool {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Pool(PoolBalances {
+            reserve_x_balance: u64::arbitrary(u)?,
+            reserve_y_balance: u64::arbitrary(u)?,
+            protocol_fee_x: u64::arbitrary(u)?,
+            protocol_fee_y: u64::arbitrary(u)?,
+            user_locked_x: u64::arbitrary(u)?,
+            user_locked_y: u64::arbitrary(u)?,
+            locked_x: u64::arbitrary(u)?,
+            locked_y: u64::arbitrary(u)?,
+            lp_fee_x: u64::arbitrary(u)?,
+            lp_fee_y: u64::arbitrary(u)?,
+        }))
+    }
+}
+
+/// Config that passes [`AmmConfig::validate`], without a launch fee schedule
+#[derive(Clone, Debug)]
+pub struct Config(pub AmmConfig);
+
+impl<'a> Arbitrary<'a> for Config {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let base_rates = fee_rates(u)?;
+        let directional_fee_rates = if bool::arbitrary(u)? {
+            Some(DirectionalFeeRates {
+                x_to_y: fee_rates(u)?,
+                y_to_x: fee_rates(u)?,
+            })
+        } else {
+            None
+        };
+        let amm_config = AmmConfig {
+            trade_fee_rate: base_rates.trade_fee_rate,
+            protocol_fee_rate: base_rates.protocol_fee_rate,
+            ratio_change_tolerance_rate: ppm(u.int_in_range(1..=MAX_PERCENTAGE)?),
+            host_fee_rate: ppm(u.int_in_range(0..=MAX_PERCENTAGE)?),
+            host_fee_source: if bool::arbitrary(u)? { HostFeeSource::ProtocolFee } else { HostFeeSource::TradeFee },
+            directional_fee_rates,
+            launch_fee_schedule: None,
+            fee_side: if bool::arbitrary(u)? { FeeSide::Output } else { FeeSide::Input },
+        };
+        // e.g. a host fee that does not fit next to the protocol fee
+        amm_config.validate().map_err(|_| arbitrary::Error::IncorrectFormat)?;
+        Ok(Config(amm_config))
+    }
+}
+
+fn fee_rates(u: &mut Unstructured) -> Result<FeeRates> {
+    Ok(FeeRates {
+        trade_fee_rate: ppm(u.int_in_range(0..=MAX_PERCENTAGE - 1)?),
+        protocol_fee_rate: ppm(u.int_in_range(0..=MAX_PERCENTAGE)?),
+    })
+}
+
+fn ppm(value: u64) -> Ppm {
+    Ppm::new(value).expect("in range")
+}
@@ -0,0 +1,242 @@
+#ifndef DEX_MATH_H
+#define DEX_MATH_H
+
+/* Generated by cbindgen from src/ffi.rs, do not edit. Regenerate with
+ * `DEX_MATH_UPDATE_HEADER=1 cargo test --features ffi --test ffi`. */
+
+#include <stdint.h>
+
+// `0` on success, otherwise a `MathError` code or one of the FFI specific
+// codes below 6000
+enum DexMathStatus
+#if defined(__cplusplus) || __STDC_VERSION__ >= 202311L
+  : uint32_t
+#endif // defined(__cplusplus) || __STDC_VERSION__ >= 202311L
+ {
+  DEX_MATH_STATUS_OK = 0,
+  // A required pointer argument was null
+  DEX_MATH_STATUS_NULL_POINTER = 1,
+  // The library panicked, caught instead of unwinding into C
+  DEX_MATH_STATUS_PANIC = 2,
+  // An enum or boolean argument is out of range
+  DEX_MATH_STATUS_INVALID_ARGUMENT = 3,
+  DEX_MATH_STATUS_MATH_OVERFLOW = 6000,
+  DEX_MATH_STATUS_INSUFFICIENT_POOL_TOKEN_X_BALANCE = 6001,
+  DEX_MATH_STATUS_INSUFFICIENT_POOL_TOKEN_Y_BALANCE = 6002,
+  DEX_MATH_STATUS_TRADE_TOO_BIG = 6003,
+  DEX_MATH_STATUS_INPUT_AMOUNT_TOO_SMALL = 6004,
+  DEX_MATH_STATUS_INVALID_RATE = 6005,
+  DEX_MATH_STATUS_INVALID_TRADE_FEE_RATE = 6006,
+  DEX_MATH_STATUS_INVALID_PROTOCOL_FEE_RATE = 6007,
+  DEX_MATH_STATUS_INVALID_RATIO_CHANGE_TOLERANCE_RATE = 6008,
+  DEX_MATH_STATUS_INVALID_HOST_FEE_RATE = 6009,
+  DEX_MATH_STATUS_FLASH_SWAP_INVARIANT_VIOLATED = 6010,
+  DEX_MATH_STATUS_ZERO_LP_SUPPLY = 6011,
+  DEX_MATH_STATUS_INSUFFICIENT_LIQUIDITY_FOR_ORDERS = 6012,
+  DEX_MATH_STATUS_INSUFFICIENT_LP_SUPPLY = 6013,
+};
+#ifndef __cplusplus
+#if __STDC_VERSION__ >= 202311L
+typedef enum DexMathStatus DexMathStatus;
+#else
+typedef uint32_t DexMathStatus;
+#endif // __STDC_VERSION__ >= 202311L
+#endif // __cplusplus
+
+// Values of the `uint32_t` fee side arguments and fields
+enum DexMathFeeSide
+#if defined(__cplusplus) || __STDC_VERSION__ >= 202311L
+  : uint32_t
+#endif // defined(__cplusplus) || __STDC_VERSION__ >= 202311L
+ {
+  DEX_MATH_FEE_SIDE_INPUT = 0,
+  DEX_MATH_FEE_SIDE_OUTPUT = 1,
+};
+#ifndef __cplusplus
+#if __STDC_VERSION__ >= 202311L
+typedef enum DexMathFeeSide DexMathFeeSide;
+#else
+typedef uint32_t DexMathFeeSide;
+#endif // __STDC_VERSION__ >= 202311L
+#endif // __cplusplus
+
+// Values of the `uint32_t` host fee source field
+enum DexMathHostFeeSource
+#if defined(__cplusplus) || __STDC_VERSION__ >= 202311L
+  : uint32_t
+#endif // defined(__cplusplus) || __STDC_VERSION__ >= 202311L
+ {
+  DEX_MATH_HOST_FEE_SOURCE_TRADE_FEE = 0,
+  DEX_MATH_HOST_FEE_SOURCE_PROTOCOL_FEE = 1,
+};
+#ifndef __cplusplus
+#if __STDC_VERSION__ >= 202311L
+typedef enum DexMathHostFeeSource DexMathHostFeeSource;
+#else
+typedef uint32_t DexMathHostFeeSource;
+#endif // __STDC_VERSION__ >= 202311L
+#endif // __cplusplus
+
+// Rates in ppm, see `AmmConfig`
+typedef struct DexMathAmmConfig {
+  uint64_t trade_fee_rate;
+  uint64_t protocol_fee_rate;
+  uint64_t ratio_change_tolerance_rate;
+  uint64_t host_fee_rate;
+  // A `DexMathHostFeeSource`
+  uint32_t host_fee_source;
+  // A `DexMathFeeSide`
+  uint32_t fee_side;
+  // `1` to use the `x_to_y_*` / `y_to_x_*` rates instead of the base rates,
+  // `0` otherwise
+  uint8_t has_directional_fee_rates;
+  uint64_t x_to_y_trade_fee_rate;
+  uint64_t x_to_y_protocol_fee_rate;
+  uint64_t y_to_x_trade_fee_rate;
+  uint64_t y_to_x_protocol_fee_rate;
+} DexMathAmmConfig;
+
+// See `PoolBalances`
+typedef struct DexMathPoolBalances {
+  uint64_t reserve_x_balance;
+  uint64_t reserve_y_balance;
+  uint64_t protocol_fee_x;
+  uint64_t protocol_fee_y;
+  uint64_t user_locked_x;
+  uint64_t user_locked_y;
+  uint64_t locked_x;
+  uint64_t locked_y;
+  uint64_t lp_fee_x;
+  uint64_t lp_fee_y;
+} DexMathPoolBalances;
+
+// See `QuoteOutput`, `trade_fee_rate` in ppm
+typedef struct DexMathQuoteOutput {
+  uint64_t from_amount;
+  uint64_t to_amount;
+  uint64_t trade_fee_rate;
+  uint64_t trade_fee;
+  uint64_t protocol_fee;
+  uint64_t host_fee;
+  uint64_t from_to_lock;
+} DexMathQuoteOutput;
+
+// See `SwapResult`, `protocol_fee` is before the host fee split; use
+// `dex_math_quote` for the host fee
+typedef struct DexMathSwapResult {
+  uint64_t from_amount;
+  uint64_t to_amount;
+  uint64_t trade_fee;
+  uint64_t protocol_fee;
+} DexMathSwapResult;
+
+// See `ProtocolFeeDeposit`, `k_last` split into its high and low 64 bits
+typedef struct DexMathProtocolFeeDeposit {
+  uint64_t lp_minted;
+  uint64_t unused_x;
+  uint64_t unused_y;
+  uint64_t protocol_fee_lp;
+  uint64_t k_last_high;
+  uint64_t k_last_low;
+} DexMathProtocolFeeDeposit;
+
+// See `ProtocolFeeWithdrawal`, `k_last` split into its high and low 64 bits
+typedef struct DexMathProtocolFeeWithdrawal {
+  uint64_t amount_x;
+  uint64_t amount_y;
+  uint64_t lp_fee_x;
+  uint64_t lp_fee_y;
+  uint64_t protocol_fee_lp;
+  uint64_t k_last_high;
+  uint64_t k_last_low;
+} DexMathProtocolFeeWithdrawal;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Quote the output for an exact input, see `quote`
+//
+// # Safety
+// `amm_config` and `pool_balances` must point to valid structs and `out` to
+// writable memory for a `DexMathQuoteOutput`.
+DexMathStatus dex_math_quote(uint64_t exchange_in,
+                             uint8_t is_swap_x_to_y,
+                             const struct DexMathAmmConfig *amm_config,
+                             const struct DexMathPoolBalances *pool_balances,
+                             struct DexMathQuoteOutput *out);
+
+// Constant product swap against raw reserves, see `swap_with_fee_side`
+//
+// # Safety
+// `out` must point to writable memory for a `DexMathSwapResult`.
+DexMathStatus dex_math_swap(uint64_t source_amount,
+                            uint64_t pool_source_amount,
+                            uint64_t pool_destination_amount,
+                            uint64_t trade_fee_rate,
+                            uint64_t protocol_fee_rate,
+                            uint32_t fee_side,
+                            struct DexMathSwapResult *out);
+
+// LP tokens minted for a deposit, see `try_deposit_lp`
+//
+// # Safety
+// `out_lp_tokens` must point to a writable `uint64_t`.
+DexMathStatus dex_math_deposit_lp(uint64_t amount_x,
+                                  uint64_t amount_y,
+                                  uint64_t total_lp_supply,
+                                  uint64_t reserve_x,
+                                  uint64_t reserve_y,
+                                  uint64_t *out_lp_tokens);
+
+// Tokens returned for burning LP tokens, see `try_withdraw_lp`
+//
+// # Safety
+// `out_amount_x` and `out_amount_y` must point to writable `uint64_t`s.
+DexMathStatus dex_math_withdraw_lp(uint64_t lp_tokens,
+                                   uint64_t total_lp_supply,
+                                   uint64_t reserve_x,
+                                   uint64_t reserve_y,
+                                   uint64_t *out_amount_x,
+                                   uint64_t *out_amount_y);
+
+// LP tokens minted for a deposit after minting the protocol fee, see
+// `deposit_lp_with_protocol_fee`; `k_last` is passed and returned as its high
+// and low 64 bits, `protocol_share` in ppm
+//
+// # Safety
+// `pool_balances` must point to a valid struct and `out` to writable memory
+// for a `DexMathProtocolFeeDeposit`.
+DexMathStatus dex_math_deposit_lp_with_protocol_fee(uint64_t amount_x,
+                                                    uint64_t amount_y,
+                                                    uint64_t total_lp_supply,
+                                                    const struct DexMathPoolBalances *pool_balances,
+                                                    uint64_t k_last_high,
+                                                    uint64_t k_last_low,
+                                                    uint64_t protocol_share,
+                                                    struct DexMathProtocolFeeDeposit *out);
+
+// Tokens returned for burning LP tokens after minting the protocol fee, see
+// `withdraw_lp_with_protocol_fee`; `k_last` is passed and returned as its
+// high and low 64 bits, `protocol_share` in ppm
+//
+// # Safety
+// `pool_balances` must point to a valid struct and `out` to writable memory
+// for a `DexMathProtocolFeeWithdrawal`.
+DexMathStatus dex_math_withdraw_lp_with_protocol_fee(uint64_t lp_tokens,
+                                                     uint64_t total_lp_supply,
+                                                     const struct DexMathPoolBalances *pool_balances,
+                                                     uint64_t k_last_high,
+                                                     uint64_t k_last_low,
+                                                     uint64_t protocol_share,
+                                                     struct DexMathProtocolFeeWithdrawal *out);
+
+// Name of a status, e.g. `"TradeTooBig"`, as a static NUL terminated string,
+// `"Unknown"` for values that are not a `DexMathStatus`
+const char *dex_math_status_name(uint32_t status);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* DEX_MATH_H */
@@ -0,0 +1,172 @@
+//! Side-typed token amounts
+//!
+//! `Amount<X>` and `Amount<Y>` are plain `u64`s at runtime but cannot be
+//! mixed up at compile time. The typed variants of `swap`, `rebalance_pool_ratio`
+//! and `quote` use `S::Other` for the destination side, so a swapped argument
+//! order is a type error rather than a wrong quote.
+
+use core::marker::PhantomData;
+
+use crate::{state::FeeSide, MathError};
+
+mod sealed {
+    pub trait Sealed {}
+    impl Sealed for super::X {}
+    impl Sealed for super::Y {}
+}
+
+/// Token X of the pool
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct X;
+
+/// Token Y of the pool
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Y;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SwapDirection {
+    XToY,
+    YToX,
+}
+
+impl SwapDirection {
+    pub fn is_x_to_y(self) -> bool {
+        self == SwapDirection::XToY
+    }
+
+    pub fn reverse(self) -> SwapDirection {
+        match self {
+            SwapDirection::XToY => SwapDirection::YToX,
+            SwapDirection::YToX => SwapDirection::XToY,
+        }
+    }
+}
+
+impl From<bool> for SwapDirection {
+    fn from(is_swap_x_to_y: bool) -> Self {
+        if is_swap_x_to_y {
+            SwapDirection::XToY
+        } else {
+            SwapDirection::YToX
+        }
+    }
+}
+
+/// A side of the pool, used as the source side of a swap
+pub trait Side: sealed::Sealed + Copy + Default + Eq + Ord + core::hash::Hash + 'static {
+    type Other: Side<Other = Self>;
+
+    /// Direction of a swap with this side as the source
+    const DIRECTION: SwapDirection;
+
+    /// Returned when the pool does not hold enough of this side
+    const INSUFFICIENT_BALANCE: MathError;
+
+    /// Order an X/Y pair as (this side, other side)
+    fn split(x: Amount<X>, y: Amount<Y>) -> (Amount<Self>, Amount<Self::Other>);
+}
+
+impl Side for X {
+    type Other = Y;
+    const DIRECTION: SwapDirection = SwapDirection::XToY;
+    const INSUFFICIENT_BALANCE: MathError = MathError::InsufficientPoolTokenXBalance;
+
+    fn split(x: Amount<X>, y: Amount<Y>) -> (Amount<X>, Amount<Y>) {
+        (x, y)
+    }
+}
+
+impl Side for Y {
+    type Other = X;
+    const DIRECTION: SwapDirection = SwapDirection::YToX;
+    const INSUFFICIENT_BALANCE: MathError = MathError::InsufficientPoolTokenYBalance;
+
+    fn split(x: Amount<X>, y: Amount<Y>) -> (Amount<Y>, Amount<X>) {
+        (y, x)
+    }
+}
+
+/// Token amount of one side of the pool
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct Amount<S: Side> {
+    value: u64,
+    side: PhantomData<S>,
+}
+
+impl<S: Side> Amount<S> {
+    pub const ZERO: Amount<S> = Amount::new(0);
+
+    pub const fn new(value: u64) -> Self {
+        Amount {
+            value,
+            side: PhantomData,
+        }
+    }
+
+    pub const fn get(self) -> u64 {
+        self.value
+    }
+
+    pub fn checked_add(self, other: Amount<S>) -> Option<Amount<S>> {
+        self.value.checked_add(other.value).map(Amount::new)
+    }
+
+    pub fn checked_sub(self, other: Amount<S>) -> Option<Amount<S>> {
+        self.value.checked_sub(other.value).map(Amount::new)
+    }
+}
+
+/// Fee charged in either token of a swap with source side `S`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeeAmount<S: Side> {
+    Source(Amount<S>),
+    Destination(Amount<S::Other>),
+}
+
+impl<S: Side> FeeAmount<S> {
+    pub fn new(value: u64, fee_side: FeeSide) -> Self {
+        match fee_side {
+            FeeSide::Input => FeeAmount::Source(Amount::new(value)),
+            FeeSide::Output => FeeAmount::Destination(Amount::new(value)),
+        }
+    }
+
+    pub fn get(self) -> u64 {
+        match self {
+            FeeAmount::Source(amount) => amount.get(),
+            FeeAmount::Destination(amount) => amount.get(),
+        }
+    }
+}
+
+impl<S: Side> From<Amount<S>> for u64 {
+    fn from(amount: Amount<S>) -> u64 {
+        amount.value
+    }
+}
+
+impl<S: Side> From<Amount<S>> for u128 {
+    fn from(amount: Amount<S>) -> u128 {
+        amount.value as u128
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_orders_source_first() {
+        let (x, y) = (Amount::<X>::new(1), Amount::<Y>::new(2));
+        assert_eq!(X::split(x, y), (x, y));
+        assert_eq!(Y::split(x, y), (y, x));
+        assert_eq!(X::DIRECTION.reverse(), Y::DIRECTION);
+        assert_eq!(SwapDirection::from(true), SwapDirection::XToY);
+    }
+
+    #[test]
+    fn amounts_are_zero_cost() {
+        assert_eq!(core::mem::size_of::<Amount<X>>(), core::mem::size_of::<u64>());
+    }
+}
@@ -0,0 +1,270 @@
+//! Replay of historical trades under candidate configs
+//!
+//! Each config gets its own [`SimPool`] seeded with the same reserves, and the
+//! trades are applied in order as immediate swaps quoted at their timestamp.
+//! Trades a config does not accept are counted and skipped, so later trades
+//! see that config's own pool. Trades the library accepts but that would break
+//! a pool invariant are skipped too and listed separately, they are bugs.
+
+use core::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    sim::{InvariantViolation, Operation, Outcome, SimError, SimPool},
+    AmmConfig, FeeSide, MathError, PoolBalances,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Trade {
+    /// Slot or timestamp, in the unit of any launch fee schedule
+    pub timestamp: u64,
+    pub is_swap_x_to_y: bool,
+    /// Input amount
+    pub amount: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BacktestReport {
+    pub amm_config: AmmConfig,
+    pub executed_trades: u64,
+    /// Trades over the ratio change tolerance, `MathLibTradeTooBig`
+    pub rejected_trades: u64,
+    /// Trades failing for any other reason, e.g. more than the pool holds
+    pub failed_trades: u64,
+    /// Index in the trades of each trade that would have broken a pool
+    /// invariant, not counted above; should always be empty
+    pub invariant_violations: Vec<(usize, InvariantViolation)>,
+
+    pub lp_fee_x: u64,
+    pub lp_fee_y: u64,
+    pub protocol_fee_x: u64,
+    pub protocol_fee_y: u64,
+    pub host_fee_x: u64,
+    pub host_fee_y: u64,
+
+    pub final_pool_balances: PoolBalances,
+}
+
+/// Replay `trades` against a pool holding `reserve_x` / `reserve_y`, once per
+/// config in `amm_configs`
+pub fn backtest(
+    trades: &[Trade],
+    reserve_x: u64,
+    reserve_y: u64,
+    amm_configs: &[AmmConfig],
+) -> Vec<BacktestReport> {
+    amm_configs
+        .iter()
+        .map(|amm_config| replay(trades, reserve_x, reserve_y, amm_config))
+        .collect()
+}
+
+fn replay(trades: &[Trade], reserve_x: u64, reserve_y: u64, amm_config: &AmmConfig) -> BacktestReport {
+    let mut pool = SimPool::new(amm_config.clone(), reserve_x, reserve_y);
+    let mut report = BacktestReport {
+        amm_config: amm_config.clone(),
+        executed_trades: 0,
+        rejected_trades: 0,
+        failed_trades: 0,
+        invariant_violations: Vec::new(),
+        lp_fee_x: 0,
+        lp_fee_y: 0,
+        protocol_fee_x: 0,
+        protocol_fee_y: 0,
+        host_fee_x: 0,
+        host_fee_y: 0,
+        final_pool_balances: PoolBalances::default(),
+    };
+
+    for (index, trade) in trades.iter().enumerate() {
+        pool.set_now(trade.timestamp);
+        let result = pool.apply(Operation::Swap {
+            exchange_in: trade.amount,
+            is_swap_x_to_y: trade.is_swap_x_to_y,
+        });
+
+        match result {
+            Ok(step) => {
+                let Outcome::Swapped(quote) = &step.outcome else {
+                    unreachable!("swaps always have a swapped outcome");
+                };
+                report.executed_trades += 1;
+
+                let lp_fee = quote.trade_fee - quote.protocol_fee - quote.host_fee;
+                let fee_in_x = trade.is_swap_x_to_y == (amm_config.fee_side == FeeSide::Input);
+                let (lp_fee_total, protocol_fee_total, host_fee_total) = if fee_in_x {
+                    (&mut report.lp_fee_x, &mut report.protocol_fee_x, &mut report.host_fee_x)
+                } else {
+                    (&mut report.lp_fee_y, &mut report.protocol_fee_y, &mut report.host_fee_y)
+                };
+                *lp_fee_total = lp_fee_total.saturating_add(lp_fee);
+                *protocol_fee_total = protocol_fee_total.saturating_add(quote.protocol_fee);
+                *host_fee_total = host_fee_total.saturating_add(quote.host_fee);
+            }
+            Err(error) => count_failure(&mut report, index, error),
+        }
+    }
+
+    report.final_pool_balances = *pool.pool_balances();
+    report
+}
+
+fn count_failure(report: &mut BacktestReport, index: usize, error: SimError) {
+    match error {
+        SimError::Math(MathError::TradeTooBig) => report.rejected_trades += 1,
+        SimError::Invariant { violation, .. } => report.invariant_violations.push((index, violation)),
+        SimError::Math(_) | SimError::UnknownOrder(_) => report.failed_trades += 1,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseTradesError {
+    /// 1-based line of the CSV input
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseTradesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseTradesError {}
+
+/// Read trades from CSV with a `timestamp,direction,amount` header, columns in
+/// any order, `direction` being `x_to_y` or `y_to_x`
+pub fn parse_trades_csv(csv: &str) -> Result<Vec<Trade>, ParseTradesError> {
+    let mut lines = csv
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty());
+
+    let error = |line: usize, message: String| ParseTradesError { line, message };
+
+    let (header_line, header) = lines.next().ok_or_else(|| error(1, "missing header".to_string()))?;
+    let columns: Vec<&str> = header.split(',').map(str::trim).collect();
+    let column = |name: &str| {
+        columns
+            .iter()
+            .position(|column| *column == name)
+            .ok_or_else(|| error(header_line, format!("missing column `{name}`")))
+    };
+    let (timestamp_column, direction_column, amount_column) =
+        (column("timestamp")?, column("direction")?, column("amount")?);
+
+    lines
+        .map(|(line, row)| {
+            let fields: Vec<&str> = row.split(',').map(str::trim).collect();
+            if fields.len() != columns.len() {
+                return Err(error(line, format!("expected {} fields, got {}", columns.len(), fields.len())));
+            }
+            let number = |index: usize| {
+                fields[index]
+                    .parse::<u64>()
+                    .map_err(|e| error(line, format!("`{}`: {e}", fields[index])))
+            };
+            let is_swap_x_to_y = match fields[direction_column] {
+                "x_to_y" => true,
+                "y_to_x" => false,
+                direction => return Err(error(line, format!("unknown direction `{direction}`"))),
+            };
+
+            Ok(Trade {
+                timestamp: number(timestamp_column)?,
+                is_swap_x_to_y,
+                amount: number(amount_column)?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::Ppm};
+
+    fn amm_config(trade_fee_rate: u64, ratio_change_tolerance_rate: u64) -> AmmConfig {
+        AmmConfig::builder()
+            .trade_fee_rate(Ppm::new(trade_fee_rate).unwrap())
+            .protocol_fee_rate(Ppm::new(200_000).unwrap())
+            .ratio_change_tolerance_rate(Ppm::new(ratio_change_tolerance_rate).unwrap())
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn parses_csv_in_any_column_order() {
+        let trades = parse_trades_csv("amount,timestamp,direction\n100,1,x_to_y\n\n200,2,y_to_x\n").unwrap();
+        assert_eq!(
+            trades,
+            vec![
+                Trade { timestamp: 1, is_swap_x_to_y: true, amount: 100 },
+                Trade { timestamp: 2, is_swap_x_to_y: false, amount: 200 },
+            ]
+        );
+
+        assert_eq!(parse_trades_csv("timestamp,amount\n").unwrap_err().line, 1);
+        assert_eq!(parse_trades_csv("timestamp,direction,amount\n1,up,5").unwrap_err().line, 2);
+        assert_eq!(parse_trades_csv("timestamp,direction,amount\n1,x_to_y,-5").unwrap_err().line, 2);
+    }
+
+    #[test]
+    fn reports_income_per_config() {
+        let trades = [
+            Trade { timestamp: 0, is_swap_x_to_y: true, amount: 10_000 },
+            Trade { timestamp: 1, is_swap_x_to_y: false, amount: 20_000 },
+            Trade { timestamp: 2, is_swap_x_to_y: true, amount: 10_000_000 },
+        ];
+        let reports = backtest(
+            &trades,
+            1_000_000,
+            2_000_000,
+            &[amm_config(3_000, 1_000_000), amm_config(10_000, 1_000_000)],
+        );
+
+        let (low, high) = (&reports[0], &reports[1]);
+        assert_eq!(low.executed_trades, 3);
+        assert_eq!((low.rejected_trades, low.failed_trades), (0, 0));
+        // fees on input: X from x_to_y trades, Y from y_to_x trades
+        assert_eq!(low.protocol_fee_x, low.final_pool_balances.protocol_fee_x);
+        assert_eq!(low.lp_fee_y, low.final_pool_balances.lp_fee_y);
+        assert!(high.lp_fee_x > low.lp_fee_x && high.lp_fee_y > low.lp_fee_y);
+    }
+
+    #[test]
+    fn lists_invariant_violations_apart_from_failed_trades() {
+        let mut report = backtest(&[], 1_000, 1_000, &[amm_config(0, 1_000_000)]).remove(0);
+        let step = crate::sim::Step {
+            operation: Operation::CollectProtocolFees,
+            outcome: Outcome::ProtocolFeesCollected { amount_x: 0, amount_y: 0 },
+            pool_balances: PoolBalances::default(),
+            lp_supply: 0,
+        };
+        let violation = InvariantViolation::CurveValueDecreased;
+        count_failure(&mut report, 3, SimError::Invariant { violation, step: Box::new(step) });
+        count_failure(&mut report, 4, SimError::Math(MathError::MathOverflow));
+
+        assert_eq!(report.invariant_violations, [(3, violation)]);
+        assert_eq!((report.failed_trades, report.rejected_trades), (1, 0));
+    }
+
+    #[test]
+    fn counts_trades_over_the_tolerance() {
+        // a 10% trade moves a 1:3 pool by more than 10 ppm once from_to_lock is
+        // rounded, a config rejecting it leaves the pool untouched
+        let trades = [Trade { timestamp: 0, is_swap_x_to_y: true, amount: 1_000 }];
+        let reports = backtest(&trades, 10_000, 30_000, &[amm_config(3_000, 10), amm_config(3_000, 100)]);
+        assert_eq!((reports[0].rejected_trades, reports[0].executed_trades), (1, 0));
+        assert_eq!((reports[1].rejected_trades, reports[1].executed_trades), (0, 1));
+        assert_eq!(reports[0].final_pool_balances.reserve_x_balance, 10_000);
+
+        let trades = [Trade { timestamp: 0, is_swap_x_to_y: true, amount: 0 }];
+        let reports = backtest(&trades, 1_000_000, 2_000_000, &[amm_config(3_000, 1_000_000)]);
+        assert_eq!(reports[0].failed_trades, 1);
+    }
+}
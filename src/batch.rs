@@ -0,0 +1,407 @@
+//! Batch auction clearing
+//!
+//! Clears a set of pending orders against a single pool at one uniform price,
+//! so the order in which they were submitted has no effect on any fill.
+
+use alloc::vec::Vec;
+
+use crate::{
+    split_host_fee,
+    state::{BatchFill, BatchOrder, BatchResult, FeeSide},
+    errors::{MathError, Result},
+    AmmConfig, SwapDirection,
+};
+
+/// Clear a batch of orders at a single uniform price
+///
+/// Trade fees are taken from each order's input first (or from its output
+/// with `FeeSide::Output`) and split into the protocol, host and LP shares,
+/// see [`crate::split_host_fee`]. The host shares leave the pool and are
+/// totalled in `host_fee_x/y`. The remaining X and Y flows are netted against
+/// each other and only the residual trades against the constant product curve. Settling everything at the price
+///
+///   P = (y + sum_y_in) / (x + sum_x_in)   (Y per X)
+///
+/// leaves `x * y` unchanged in exact arithmetic, which makes P the price the
+/// curve itself quotes for the residual. Every fill is rounded down, so the
+/// invariant can only grow.
+///
+/// # Arguments
+/// * `orders` - The pending orders, each with its own direction
+/// * `amm_config` - The configuration of the AMM
+/// * `reserve_x` - The available X balance of the pool (excluding fees and locks)
+/// * `reserve_y` - The available Y balance of the pool (excluding fees and locks)
+pub fn clear_batch(
+    orders: &[BatchOrder],
+    amm_config: &AmmConfig,
+    reserve_x: u64,
+    reserve_y: u64,
+) -> Result<BatchResult> {
+    if reserve_x == 0 {
+        return Err(MathError::InsufficientPoolTokenXBalance);
+    }
+    if reserve_y == 0 {
+        return Err(MathError::InsufficientPoolTokenYBalance);
+    }
+
+    let mut fills = Vec::with_capacity(orders.len());
+    let (mut sum_x_in, mut sum_y_in) = (0u128, 0u128);
+    let mut fee_x = FeeBuckets::default();
+    let mut fee_y = FeeBuckets::default();
+
+    for order in orders {
+        if order.amount_in == 0 {
+            return Err(MathError::InputAmountTooSmall);
+        }
+
+        let amount_in = order.amount_in as u128;
+        let (sum_in, source_fees) = if order.is_swap_x_to_y {
+            (&mut sum_x_in, &mut fee_x)
+        } else {
+            (&mut sum_y_in, &mut fee_y)
+        };
+
+        let (from_amount, fees) = match amm_config.fee_side {
+            FeeSide::Input => {
+                let fees = charge_fees(amount_in, order, amm_config)?;
+                source_fees.add(&fees)?;
+                let from_amount = amount_in
+                    .checked_sub(fees.trade_fee)
+                    .ok_or(MathError::MathOverflow)?;
+                (from_amount, fees)
+            }
+            // charged once the output is known
+            FeeSide::Output => (amount_in, Fees::default()),
+        };
+
+        *sum_in = sum_in
+            .checked_add(from_amount)
+            .ok_or(MathError::MathOverflow)?;
+
+        fills.push(BatchFill {
+            from_amount: from_amount as u64,
+            to_amount: 0,
+            trade_fee: fees.trade_fee as u64,
+            protocol_fee: fees.protocol_fee as u64,
+            host_fee: fees.host_fee as u64,
+        });
+    }
+
+    // Y per X
+    let price_numerator = (reserve_y as u128)
+        .checked_add(sum_y_in)
+        .ok_or(MathError::MathOverflow)?;
+    let price_denominator = (reserve_x as u128)
+        .checked_add(sum_x_in)
+        .ok_or(MathError::MathOverflow)?;
+
+    let (mut sum_x_out, mut sum_y_out) = (0u128, 0u128);
+    for (order, fill) in orders.iter().zip(fills.iter_mut()) {
+        let from_amount = fill.from_amount as u128;
+        let (numerator, denominator, sum_out, destination_fees) = if order.is_swap_x_to_y {
+            (price_numerator, price_denominator, &mut sum_y_out, &mut fee_y)
+        } else {
+            (price_denominator, price_numerator, &mut sum_x_out, &mut fee_x)
+        };
+
+        let to_amount = from_amount
+            .checked_mul(numerator)
+            .ok_or(MathError::MathOverflow)?
+            .checked_div(denominator)
+            .ok_or(MathError::MathOverflow)?;
+        *sum_out = sum_out
+            .checked_add(to_amount)
+            .ok_or(MathError::MathOverflow)?;
+
+        let to_amount = match amm_config.fee_side {
+            FeeSide::Input => to_amount,
+            FeeSide::Output => {
+                let fees = charge_fees(to_amount, order, amm_config)?;
+                destination_fees.add(&fees)?;
+                fill.trade_fee = fees.trade_fee as u64;
+                fill.protocol_fee = fees.protocol_fee as u64;
+                fill.host_fee = fees.host_fee as u64;
+                to_amount
+                    .checked_sub(fees.trade_fee)
+                    .ok_or(MathError::MathOverflow)?
+            }
+        };
+
+        fill.to_amount = u64::try_from(to_amount).map_err(|_| MathError::MathOverflow)?;
+    }
+
+    // outflows are bounded by the inflows at the clearing price, never by more
+    // than the pool holds
+    let new_reserve_x = price_denominator
+        .checked_sub(sum_x_out)
+        .ok_or(MathError::InsufficientPoolTokenXBalance)?;
+    let new_reserve_y = price_numerator
+        .checked_sub(sum_y_out)
+        .ok_or(MathError::InsufficientPoolTokenYBalance)?;
+
+    let to_u64 = |amount: u128| u64::try_from(amount).map_err(|_| MathError::MathOverflow);
+
+    Ok(BatchResult {
+        fills,
+        price_numerator,
+        price_denominator,
+        reserve_x: to_u64(new_reserve_x)?,
+        reserve_y: to_u64(new_reserve_y)?,
+        protocol_fee_x: to_u64(fee_x.protocol_fee)?,
+        protocol_fee_y: to_u64(fee_y.protocol_fee)?,
+        lp_fee_x: to_u64(fee_x.lp_fee)?,
+        lp_fee_y: to_u64(fee_y.lp_fee)?,
+        host_fee_x: to_u64(fee_x.host_fee)?,
+        host_fee_y: to_u64(fee_y.host_fee)?,
+    })
+}
+
+#[derive(Default)]
+struct Fees {
+    trade_fee: u128,
+    protocol_fee: u128,
+    host_fee: u128,
+}
+
+#[derive(Default)]
+struct FeeBuckets {
+    protocol_fee: u128,
+    lp_fee: u128,
+    host_fee: u128,
+}
+
+impl FeeBuckets {
+    fn add(&mut self, fees: &Fees) -> Result<()> {
+        let lp_fee = fees
+            .trade_fee
+            .checked_sub(fees.protocol_fee)
+            .and_then(|fee| fee.checked_sub(fees.host_fee))
+            .ok_or(MathError::MathOverflow)?;
+
+        self.protocol_fee = self
+            .protocol_fee
+            .checked_add(fees.protocol_fee)
+            .ok_or(MathError::MathOverflow)?;
+        self.lp_fee = self
+            .lp_fee
+            .checked_add(lp_fee)
+            .ok_or(MathError::MathOverflow)?;
+        self.host_fee = self
+            .host_fee
+            .checked_add(fees.host_fee)
+            .ok_or(MathError::MathOverflow)?;
+        Ok(())
+    }
+}
+
+fn charge_fees(amount: u128, order: &BatchOrder, amm_config: &AmmConfig) -> Result<Fees> {
+    let fee_rates = amm_config.fee_rates(SwapDirection::from(order.is_swap_x_to_y));
+    let trade_fee = fee_rates
+        .trade_fee_rate
+        .apply_ceil(amount)
+        .ok_or(MathError::MathOverflow)?;
+    let protocol_fee = fee_rates
+        .protocol_fee_rate
+        .apply_floor(trade_fee)
+        .ok_or(MathError::MathOverflow)?;
+    let (protocol_fee, host_fee) = split_host_fee(
+        trade_fee,
+        protocol_fee,
+        amm_config.host_fee_rate,
+        amm_config.host_fee_source,
+    )
+    .ok_or(MathError::MathOverflow)?;
+
+    Ok(Fees {
+        trade_fee,
+        protocol_fee,
+        host_fee,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::{HostFeeSource, Ppm}, proptest::prelude::*};
+
+    fn config() -> AmmConfig {
+        AmmConfig::builder()
+            .trade_fee_rate(Ppm::new(3_000).unwrap())
+            .protocol_fee_rate(Ppm::new(200_000).unwrap())
+            .ratio_change_tolerance_rate(Ppm::new(10_000).unwrap())
+            .build()
+            .unwrap()
+    }
+
+    fn order(amount_in: u64, is_swap_x_to_y: bool) -> BatchOrder {
+        BatchOrder {
+            amount_in,
+            is_swap_x_to_y,
+        }
+    }
+
+    #[test]
+    fn opposing_flow_is_netted_before_the_curve() {
+        let amm_config = AmmConfig::builder()
+            .ratio_change_tolerance_rate(Ppm::MAX)
+            .build()
+            .unwrap();
+
+        // 100 X in and 200 Y in at a 1:2 pool cancel out exactly
+        let result = clear_batch(
+            &[order(100, true), order(200, false)],
+            &amm_config,
+            1_000_000,
+            2_000_000,
+        )
+        .unwrap();
+
+        assert_eq!(result.fills[0].to_amount, 200);
+        assert_eq!(result.fills[1].to_amount, 100);
+        assert_eq!(result.reserve_x, 1_000_000);
+        assert_eq!(result.reserve_y, 2_000_000);
+    }
+
+    #[test]
+    fn single_order_matches_the_curve() {
+        let amm_config = config();
+        let result = clear_batch(&[order(10_000, true)], &amm_config, 1_000_000, 2_000_000).unwrap();
+
+        let swap_result = crate::swap(
+            10_000,
+            1_000_000,
+            2_000_000,
+            amm_config.trade_fee_rate,
+            amm_config.protocol_fee_rate,
+        )
+        .unwrap();
+
+        assert_eq!(result.fills[0].from_amount, swap_result.from_amount);
+        assert_eq!(result.fills[0].to_amount, swap_result.to_amount);
+        assert_eq!(result.fills[0].trade_fee, swap_result.trade_fee);
+        assert_eq!(result.fills[0].protocol_fee, swap_result.protocol_fee);
+    }
+
+    #[test]
+    fn single_order_matches_the_curve_with_fees_on_output() {
+        let amm_config = AmmConfig::builder()
+            .trade_fee_rate(Ppm::new(3_000).unwrap())
+            .protocol_fee_rate(Ppm::new(200_000).unwrap())
+            .ratio_change_tolerance_rate(Ppm::new(10_000).unwrap())
+            .fee_side(FeeSide::Output)
+            .build()
+            .unwrap();
+        let result = clear_batch(&[order(10_000, false)], &amm_config, 1_000_000, 2_000_000).unwrap();
+
+        let swap_result = crate::swap_fee_on_output(
+            10_000,
+            2_000_000,
+            1_000_000,
+            amm_config.trade_fee_rate,
+            amm_config.protocol_fee_rate,
+        )
+        .unwrap();
+
+        assert_eq!(result.fills[0].from_amount, swap_result.from_amount);
+        assert_eq!(result.fills[0].to_amount, swap_result.to_amount);
+        assert_eq!(result.fills[0].trade_fee, swap_result.trade_fee);
+        assert_eq!(result.protocol_fee_x, swap_result.protocol_fee);
+        assert_eq!(result.lp_fee_x, swap_result.trade_fee - swap_result.protocol_fee);
+    }
+
+    #[test]
+    fn host_fees_are_totalled_apart_from_the_buckets() {
+        let amm_config = AmmConfig::builder()
+            .trade_fee_rate(Ppm::new(3_000).unwrap())
+            .protocol_fee_rate(Ppm::new(200_000).unwrap())
+            .ratio_change_tolerance_rate(Ppm::new(10_000).unwrap())
+            .host_fee_rate(Ppm::new(100_000).unwrap(), HostFeeSource::TradeFee)
+            .build()
+            .unwrap();
+        let orders = [order(10_000, true), order(30_000, true), order(5_000, false)];
+        let result = clear_batch(&orders, &amm_config, 1_000_000, 2_000_000).unwrap();
+
+        let fills = &result.fills;
+        assert!(fills.iter().all(|fill| fill.host_fee > 0));
+        assert_eq!(result.host_fee_x, fills[0].host_fee + fills[1].host_fee);
+        assert_eq!(result.host_fee_y, fills[2].host_fee);
+
+        // every X paid in ends up in the reserve, a bucket, the host fee or a fill
+        let x_in: u64 = orders[..2].iter().map(|order| order.amount_in).sum();
+        assert_eq!(
+            1_000_000 + x_in,
+            result.reserve_x + result.protocol_fee_x + result.lp_fee_x + result.host_fee_x + fills[2].to_amount
+        );
+    }
+
+    #[test]
+    fn rejects_empty_orders_and_pools() {
+        let amm_config = config();
+        assert!(clear_batch(&[order(0, true)], &amm_config, 1_000, 1_000).is_err());
+        assert!(clear_batch(&[order(10, true)], &amm_config, 0, 1_000).is_err());
+        assert!(clear_batch(&[order(10, true)], &amm_config, 1_000, 0).is_err());
+
+        let result = clear_batch(&[], &amm_config, 1_000, 2_000).unwrap();
+        assert!(result.fills.is_empty());
+        assert_eq!((result.reserve_x, result.reserve_y), (1_000, 2_000));
+    }
+
+    fn orders_strategy() -> impl Strategy<Value = Vec<BatchOrder>> {
+        prop::collection::vec(
+            (1..u32::MAX as u64, any::<bool>()).prop_map(|(amount_in, is_swap_x_to_y)| {
+                order(amount_in, is_swap_x_to_y)
+            }),
+            1..16,
+        )
+    }
+
+    proptest! {
+        #[test]
+        fn fills_do_not_depend_on_order_sequence(
+            (orders, shuffled) in orders_strategy()
+                .prop_flat_map(|orders| (Just(orders.clone()), Just(orders).prop_shuffle())),
+            reserve_x in 1..u32::MAX as u64,
+            reserve_y in 1..u32::MAX as u64,
+        ) {
+            let amm_config = config();
+            let result = clear_batch(&orders, &amm_config, reserve_x, reserve_y).unwrap();
+            let shuffled_result = clear_batch(&shuffled, &amm_config, reserve_x, reserve_y).unwrap();
+
+            prop_assert_eq!(result.reserve_x, shuffled_result.reserve_x);
+            prop_assert_eq!(result.reserve_y, shuffled_result.reserve_y);
+            prop_assert_eq!(result.price_numerator, shuffled_result.price_numerator);
+            prop_assert_eq!(result.price_denominator, shuffled_result.price_denominator);
+
+            // identical orders receive identical fills wherever they land
+            for (order, fill) in orders.iter().zip(result.fills.iter()) {
+                let (_, shuffled_fill) = shuffled
+                    .iter()
+                    .zip(shuffled_result.fills.iter())
+                    .find(|(o, _)| o.amount_in == order.amount_in && o.is_swap_x_to_y == order.is_swap_x_to_y)
+                    .unwrap();
+                prop_assert_eq!(fill, shuffled_fill);
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn curve_value_does_not_decrease_from_batch(
+            orders in orders_strategy(),
+            reserve_x in 1..u32::MAX as u64,
+            reserve_y in 1..u32::MAX as u64,
+        ) {
+            let amm_config = config();
+            let result = clear_batch(&orders, &amm_config, reserve_x, reserve_y).unwrap();
+
+            let previous_value = reserve_x as u128 * reserve_y as u128;
+            let new_value = result.reserve_x as u128 * result.reserve_y as u128;
+            prop_assert!(new_value >= previous_value);
+
+            // fees never truncate the submitted amounts
+            for (order, fill) in orders.iter().zip(result.fills.iter()) {
+                prop_assert_eq!(fill.from_amount + fill.trade_fee, order.amount_in);
+                prop_assert!(fill.protocol_fee + fill.host_fee <= fill.trade_fee);
+            }
+        }
+    }
+}
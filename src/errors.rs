@@ -1,15 +1,200 @@
-use anchor_lang::prelude::error_code;
-
-#[error_code]
-pub enum ErrorCode {
-    #[msg("Math lib: Math overflow")]
-    MathLibMathOverflow,
-    #[msg("Math lib: Insufficient pool token X balance")]
-    MathLibInsufficientPoolTokenXBalance,
-    #[msg("Math lib: Insufficient pool token Y balance")]
-    MathLibInsufficientPoolTokenYBalance,
-    #[msg("Math lib: Trade too big, exceeds max rate tolerance")]
-    MathLibTradeTooBig,
-    #[msg("Math lib: Input amount too small")]
-    MathLibInputAmountTooSmall,
+use core::fmt;
+
+/// Math failure
+///
+/// Numbered like the on-chain `ErrorCode`, 6000 onwards in declaration order,
+/// which the `anchor` feature maps it into.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MathError {
+    MathOverflow,
+    InsufficientPoolTokenXBalance,
+    InsufficientPoolTokenYBalance,
+    TradeTooBig,
+    InputAmountTooSmall,
+    InvalidRate,
+    InvalidTradeFeeRate,
+    InvalidProtocolFeeRate,
+    InvalidRatioChangeToleranceRate,
+    InvalidHostFeeRate,
+    FlashSwapInvariantViolated,
+    ZeroLpSupply,
+    InsufficientLiquidityForOrders,
+    InsufficientLpSupply,
+}
+
+pub type Result<T> = core::result::Result<T, MathError>;
+
+/// Anchor's offset for program error codes
+const ERROR_CODE_OFFSET: u32 = 6000;
+
+impl MathError {
+    pub const ALL: [MathError; 14] = [
+        MathError::MathOverflow,
+        MathError::InsufficientPoolTokenXBalance,
+        MathError::InsufficientPoolTokenYBalance,
+        MathError::TradeTooBig,
+        MathError::InputAmountTooSmall,
+        MathError::InvalidRate,
+        MathError::InvalidTradeFeeRate,
+        MathError::InvalidProtocolFeeRate,
+        MathError::InvalidRatioChangeToleranceRate,
+        MathError::InvalidHostFeeRate,
+        MathError::FlashSwapInvariantViolated,
+        MathError::ZeroLpSupply,
+        MathError::InsufficientLiquidityForOrders,
+        MathError::InsufficientLpSupply,
+    ];
+
+    /// Error code number of the matching `ErrorCode`
+    pub fn code(self) -> u32 {
+        ERROR_CODE_OFFSET + self as u32
+    }
+
+    /// Name of the matching `ErrorCode`, e.g. `MathLibTradeTooBig`
+    pub fn name(self) -> &'static str {
+        match self {
+            MathError::MathOverflow => "MathLibMathOverflow",
+            MathError::InsufficientPoolTokenXBalance => "MathLibInsufficientPoolTokenXBalance",
+            MathError::InsufficientPoolTokenYBalance => "MathLibInsufficientPoolTokenYBalance",
+            MathError::TradeTooBig => "MathLibTradeTooBig",
+            MathError::InputAmountTooSmall => "MathLibInputAmountTooSmall",
+            MathError::InvalidRate => "MathLibInvalidRate",
+            MathError::InvalidTradeFeeRate => "MathLibInvalidTradeFeeRate",
+            MathError::InvalidProtocolFeeRate => "MathLibInvalidProtocolFeeRate",
+            MathError::InvalidRatioChangeToleranceRate => "MathLibInvalidRatioChangeToleranceRate",
+            MathError::InvalidHostFeeRate => "MathLibInvalidHostFeeRate",
+            MathError::FlashSwapInvariantViolated => "MathLibFlashSwapInvariantViolated",
+            MathError::ZeroLpSupply => "MathLibZeroLpSupply",
+            MathError::InsufficientLiquidityForOrders => "MathLibInsufficientLiquidityForOrders",
+            MathError::InsufficientLpSupply => "MathLibInsufficientLpSupply",
+        }
+    }
+
+    pub fn message(self) -> &'static str {
+        match self {
+            MathError::MathOverflow => "Math lib: Math overflow",
+            MathError::InsufficientPoolTokenXBalance => "Math lib: Insufficient pool token X balance",
+            MathError::InsufficientPoolTokenYBalance => "Math lib: Insufficient pool token Y balance",
+            MathError::TradeTooBig => "Math lib: Trade too big, exceeds max rate tolerance",
+            MathError::InputAmountTooSmall => "Math lib: Input amount too small",
+            MathError::InvalidRate => "Math lib: Rate exceeds 100%",
+            MathError::InvalidTradeFeeRate => "Math lib: Trade fee rate must be below 100%",
+            MathError::InvalidProtocolFeeRate => "Math lib: Protocol fee rate exceeds 100%",
+            MathError::InvalidRatioChangeToleranceRate => {
+                "Math lib: Ratio change tolerance rate must be between 0% and 100%"
+            }
+            MathError::InvalidHostFeeRate => "Math lib: Host fee rate does not fit in its source fee",
+            MathError::FlashSwapInvariantViolated => "Math lib: Flash swap repayment does not cover the invariant",
+            MathError::ZeroLpSupply => "Math lib: Pool has no LP supply",
+            MathError::InsufficientLiquidityForOrders => {
+                "Math lib: Withdrawal leaves too little liquidity for pending orders"
+            }
+            MathError::InsufficientLpSupply => "Math lib: Burning more LP tokens than the supply",
+        }
+    }
+}
+
+impl fmt::Display for MathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MathError {}
+
+#[cfg(feature = "anchor")]
+pub use anchor::ErrorCode;
+
+#[cfg(feature = "anchor")]
+mod anchor {
+    use anchor_lang::prelude::error_code;
+
+    use super::MathError;
+
+    #[error_code]
+    pub enum ErrorCode {
+        #[msg("Math lib: Math overflow")]
+        MathLibMathOverflow,
+        #[msg("Math lib: Insufficient pool token X balance")]
+        MathLibInsufficientPoolTokenXBalance,
+        #[msg("Math lib: Insufficient pool token Y balance")]
+        MathLibInsufficientPoolTokenYBalance,
+        #[msg("Math lib: Trade too big, exceeds max rate tolerance")]
+        MathLibTradeTooBig,
+        #[msg("Math lib: Input amount too small")]
+        MathLibInputAmountTooSmall,
+        #[msg("Math lib: Rate exceeds 100%")]
+        MathLibInvalidRate,
+        #[msg("Math lib: Trade fee rate must be below 100%")]
+        MathLibInvalidTradeFeeRate,
+        #[msg("Math lib: Protocol fee rate exceeds 100%")]
+        MathLibInvalidProtocolFeeRate,
+        #[msg("Math lib: Ratio change tolerance rate must be between 0% and 100%")]
+        MathLibInvalidRatioChangeToleranceRate,
+        #[msg("Math lib: Host fee rate does not fit in its source fee")]
+        MathLibInvalidHostFeeRate,
+        #[msg("Math lib: Flash swap repayment does not cover the invariant")]
+        MathLibFlashSwapInvariantViolated,
+        #[msg("Math lib: Pool has no LP supply")]
+        MathLibZeroLpSupply,
+        #[msg("Math lib: Withdrawal leaves too little liquidity for pending orders")]
+        MathLibInsufficientLiquidityForOrders,
+        #[msg("Math lib: Burning more LP tokens than the supply")]
+        MathLibInsufficientLpSupply,
+    }
+
+    impl From<MathError> for ErrorCode {
+        fn from(error: MathError) -> Self {
+            match error {
+                MathError::MathOverflow => ErrorCode::MathLibMathOverflow,
+                MathError::InsufficientPoolTokenXBalance => ErrorCode::MathLibInsufficientPoolTokenXBalance,
+                MathError::InsufficientPoolTokenYBalance => ErrorCode::MathLibInsufficientPoolTokenYBalance,
+                MathError::TradeTooBig => ErrorCode::MathLibTradeTooBig,
+                MathError::InputAmountTooSmall => ErrorCode::MathLibInputAmountTooSmall,
+                MathError::InvalidRate => ErrorCode::MathLibInvalidRate,
+                MathError::InvalidTradeFeeRate => ErrorCode::MathLibInvalidTradeFeeRate,
+                MathError::InvalidProtocolFeeRate => ErrorCode::MathLibInvalidProtocolFeeRate,
+                MathError::InvalidRatioChangeToleranceRate => ErrorCode::MathLibInvalidRatioChangeToleranceRate,
+                MathError::InvalidHostFeeRate => ErrorCode::MathLibInvalidHostFeeRate,
+                MathError::FlashSwapInvariantViolated => ErrorCode::MathLibFlashSwapInvariantViolated,
+                MathError::ZeroLpSupply => ErrorCode::MathLibZeroLpSupply,
+                MathError::InsufficientLiquidityForOrders => ErrorCode::MathLibInsufficientLiquidityForOrders,
+                MathError::InsufficientLpSupply => ErrorCode::MathLibInsufficientLpSupply,
+            }
+        }
+    }
+
+    /// Lets `?` propagate math errors out of Anchor instructions
+    impl From<MathError> for anchor_lang::error::Error {
+        fn from(error: MathError) -> Self {
+            ErrorCode::from(error).into()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_follow_declaration_order() {
+        for (index, error) in MathError::ALL.into_iter().enumerate() {
+            assert_eq!(error.code(), 6000 + index as u32);
+        }
+    }
+
+    #[cfg(feature = "anchor")]
+    #[test]
+    fn maps_into_the_anchor_error_code() {
+        for error in MathError::ALL {
+            let anchor_error = match anchor_lang::error::Error::from(error) {
+                anchor_lang::error::Error::AnchorError(anchor_error) => anchor_error,
+                error => panic!("{error:?}"),
+            };
+            assert_eq!(anchor_error.error_code_number, error.code());
+            assert_eq!(anchor_error.error_name, error.name());
+            assert_eq!(anchor_error.error_msg, error.to_string());
+        }
+    }
 }
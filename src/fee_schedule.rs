@@ -0,0 +1,140 @@
+//! Launch fee schedule
+//!
+//! Lets a new pool start with a high trade fee that decays to the configured
+//! base rate, making it expensive to snipe the first blocks after launch.
+
+#[cfg(feature = "borsh")]
+use borsh::{BorshDeserialize, BorshSerialize};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::Ppm;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum FeeDecay {
+    /// Straight line from the start rate to the base rate over `duration`
+    Linear,
+    /// Excess over the base rate halves every `half_life`, snapping to the
+    /// base rate once `duration` has passed
+    Exponential { half_life: u64 },
+}
+
+/// `start`, `duration` and the `now` passed to [`effective_fee_rate`] share a
+/// unit, slots or unix timestamps, whichever the program reads
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FeeSchedule {
+    pub start_fee_rate: Ppm,
+    /// Slot or timestamp the decay starts at, usually pool creation
+    pub start: u64,
+    pub duration: u64,
+    pub decay: FeeDecay,
+}
+
+/// Trade fee rate in effect at `now`
+///
+/// Never goes below `base_fee_rate`, and is `start_fee_rate` until `start`.
+pub fn effective_fee_rate(schedule: &FeeSchedule, base_fee_rate: Ppm, now: u64) -> Ppm {
+    if schedule.start_fee_rate <= base_fee_rate {
+        return base_fee_rate;
+    }
+
+    let elapsed = now.saturating_sub(schedule.start);
+    if elapsed >= schedule.duration {
+        return base_fee_rate;
+    }
+
+    let excess = (schedule.start_fee_rate.get() - base_fee_rate.get()) as u128;
+    let remaining_excess = match schedule.decay {
+        FeeDecay::Linear => {
+            // round the decayed part down so the rate never undershoots the line
+            let duration = schedule.duration as u128;
+            excess - excess * elapsed as u128 / duration
+        }
+        FeeDecay::Exponential { half_life } => {
+            if half_life == 0 {
+                return base_fee_rate;
+            }
+            let halvings = elapsed / half_life;
+            if halvings >= u128::BITS as u64 {
+                0
+            } else {
+                // interpolate linearly between consecutive halvings
+                let halved = excess >> halvings;
+                let into_period = (elapsed % half_life) as u128;
+                let half_life = half_life as u128;
+                halved * (2 * half_life - into_period) / (2 * half_life)
+            }
+        }
+    };
+
+    // excess <= MAX_PERCENTAGE, so this stays within range
+    Ppm::new(base_fee_rate.get() + remaining_excess as u64).unwrap_or(Ppm::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, proptest::prelude::*};
+
+    fn schedule(decay: FeeDecay) -> FeeSchedule {
+        FeeSchedule {
+            start_fee_rate: Ppm::new(500_000).unwrap(),
+            start: 1_000,
+            duration: 100,
+            decay,
+        }
+    }
+
+    #[test]
+    fn linear_decay() {
+        let base = Ppm::new(3_000).unwrap();
+        let schedule = schedule(FeeDecay::Linear);
+
+        assert_eq!(effective_fee_rate(&schedule, base, 0).get(), 500_000);
+        assert_eq!(effective_fee_rate(&schedule, base, 1_000).get(), 500_000);
+        assert_eq!(effective_fee_rate(&schedule, base, 1_050).get(), 251_500);
+        assert_eq!(effective_fee_rate(&schedule, base, 1_100).get(), 3_000);
+        assert_eq!(effective_fee_rate(&schedule, base, u64::MAX).get(), 3_000);
+    }
+
+    #[test]
+    fn exponential_decay() {
+        let base = Ppm::new(3_000).unwrap();
+        let schedule = schedule(FeeDecay::Exponential { half_life: 10 });
+
+        assert_eq!(effective_fee_rate(&schedule, base, 1_000).get(), 500_000);
+        assert_eq!(effective_fee_rate(&schedule, base, 1_010).get(), 3_000 + 497_000 / 2);
+        assert_eq!(effective_fee_rate(&schedule, base, 1_020).get(), 3_000 + 497_000 / 4);
+        assert_eq!(effective_fee_rate(&schedule, base, 1_100).get(), 3_000);
+    }
+
+    proptest! {
+        #[test]
+        fn effective_fee_rate_is_monotonic(
+            start_fee_rate in 0..=1_000_000u64,
+            base_fee_rate in 0..=1_000_000u64,
+            duration in 0..u64::MAX / 2,
+            half_life in 0..u64::MAX / 2,
+            exponential in any::<bool>(),
+            now in 0..u64::MAX / 2,
+            step in 0..u64::MAX / 2,
+        ) {
+            let schedule = FeeSchedule {
+                start_fee_rate: Ppm::new(start_fee_rate).unwrap(),
+                start: 0,
+                duration,
+                decay: if exponential { FeeDecay::Exponential { half_life } } else { FeeDecay::Linear },
+            };
+            let base_fee_rate = Ppm::new(base_fee_rate).unwrap();
+
+            let rate = effective_fee_rate(&schedule, base_fee_rate, now);
+            let later_rate = effective_fee_rate(&schedule, base_fee_rate, now + step);
+            prop_assert!(later_rate <= rate);
+            prop_assert!(later_rate >= base_fee_rate);
+            prop_assert!(rate <= schedule.start_fee_rate.max(base_fee_rate));
+        }
+    }
+}
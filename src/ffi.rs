@@ -0,0 +1,572 @@
+//! C ABI
+//!
+//! `extern "C"` wrappers over the library for non-Rust backends, declared in
+//! `include/dex_math.h` (generated by cbindgen, see `cbindgen.toml`).
+//!
+//! Every function returns a [`DexMathStatus`] and writes its result through
+//! an out pointer, which is left untouched unless the status is
+//! `DEX_MATH_STATUS_OK`. Math failures return the `MathError` code, so
+//! statuses match the on-chain program's error codes. Launch fee schedules
+//! are not exposed.
+//!
+//! Enums and booleans cross the ABI as `uint32_t` / `uint8_t` and are checked
+//! on the way in, so any value C passes is defined behaviour.
+
+use std::{ffi::c_char, panic::UnwindSafe};
+
+use crate::{
+    AmmConfig, DirectionalFeeRates, FeeRates, FeeSide, HostFeeSource, MathError, PoolBalances, Ppm,
+};
+
+/// `0` on success, otherwise a `MathError` code or one of the FFI specific
+/// codes below 6000
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DexMathStatus {
+    Ok = 0,
+    /// A required pointer argument was null
+    NullPointer = 1,
+    /// The library panicked, caught instead of unwinding into C
+    Panic = 2,
+    /// An enum or boolean argument is out of range
+    InvalidArgument = 3,
+
+    MathOverflow = 6000,
+    InsufficientPoolTokenXBalance = 6001,
+    InsufficientPoolTokenYBalance = 6002,
+    TradeTooBig = 6003,
+    InputAmountTooSmall = 6004,
+    InvalidRate = 6005,
+    InvalidTradeFeeRate = 6006,
+    InvalidProtocolFeeRate = 6007,
+    InvalidRatioChangeToleranceRate = 6008,
+    InvalidHostFeeRate = 6009,
+    FlashSwapInvariantViolated = 6010,
+    ZeroLpSupply = 6011,
+    InsufficientLiquidityForOrders = 6012,
+    InsufficientLpSupply = 6013,
+}
+
+impl From<MathError> for DexMathStatus {
+    fn from(error: MathError) -> Self {
+        match error {
+            MathError::MathOverflow => DexMathStatus::MathOverflow,
+            MathError::InsufficientPoolTokenXBalance => DexMathStatus::InsufficientPoolTokenXBalance,
+            MathError::InsufficientPoolTokenYBalance => DexMathStatus::InsufficientPoolTokenYBalance,
+            MathError::TradeTooBig => DexMathStatus::TradeTooBig,
+            MathError::InputAmountTooSmall => DexMathStatus::InputAmountTooSmall,
+            MathError::InvalidRate => DexMathStatus::InvalidRate,
+            MathError::InvalidTradeFeeRate => DexMathStatus::InvalidTradeFeeRate,
+            MathError::InvalidProtocolFeeRate => DexMathStatus::InvalidProtocolFeeRate,
+            MathError::InvalidRatioChangeToleranceRate => DexMathStatus::InvalidRatioChangeToleranceRate,
+            MathError::InvalidHostFeeRate => DexMathStatus::InvalidHostFeeRate,
+            MathError::FlashSwapInvariantViolated => DexMathStatus::FlashSwapInvariantViolated,
+            MathError::ZeroLpSupply => DexMathStatus::ZeroLpSupply,
+            MathError::InsufficientLiquidityForOrders => DexMathStatus::InsufficientLiquidityForOrders,
+            MathError::InsufficientLpSupply => DexMathStatus::InsufficientLpSupply,
+        }
+    }
+}
+
+/// Values of the `uint32_t` fee side arguments and fields
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DexMathFeeSide {
+    Input = 0,
+    Output = 1,
+}
+
+/// Values of the `uint32_t` host fee source field
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DexMathHostFeeSource {
+    TradeFee = 0,
+    ProtocolFee = 1,
+}
+
+/// Rates in ppm, see `AmmConfig`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DexMathAmmConfig {
+    pub trade_fee_rate: u64,
+    pub protocol_fee_rate: u64,
+    pub ratio_change_tolerance_rate: u64,
+    pub host_fee_rate: u64,
+    /// A `DexMathHostFeeSource`
+    pub host_fee_source: u32,
+    /// A `DexMathFeeSide`
+    pub fee_side: u32,
+    /// `1` to use the `x_to_y_*` / `y_to_x_*` rates instead of the base rates,
+    /// `0` otherwise
+    pub has_directional_fee_rates: u8,
+    pub x_to_y_trade_fee_rate: u64,
+    pub x_to_y_protocol_fee_rate: u64,
+    pub y_to_x_trade_fee_rate: u64,
+    pub y_to_x_protocol_fee_rate: u64,
+}
+
+/// See `PoolBalances`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DexMathPoolBalances {
+    pub reserve_x_balance: u64,
+    pub reserve_y_balance: u64,
+    pub protocol_fee_x: u64,
+    pub protocol_fee_y: u64,
+    pub user_locked_x: u64,
+    pub user_locked_y: u64,
+    pub locked_x: u64,
+    pub locked_y: u64,
+    pub lp_fee_x: u64,
+    pub lp_fee_y: u64,
+}
+
+/// See `QuoteOutput`, `trade_fee_rate` in ppm
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DexMathQuoteOutput {
+    pub from_amount: u64,
+    pub to_amount: u64,
+    pub trade_fee_rate: u64,
+    pub trade_fee: u64,
+    pub protocol_fee: u64,
+    pub host_fee: u64,
+    pub from_to_lock: u64,
+}
+
+/// See `SwapResult`, `protocol_fee` is before the host fee split; use
+/// `dex_math_quote` for the host fee
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DexMathSwapResult {
+    pub from_amount: u64,
+    pub to_amount: u64,
+    pub trade_fee: u64,
+    pub protocol_fee: u64,
+}
+
+/// See `ProtocolFeeDeposit`, `k_last` split into its high and low 64 bits
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DexMathProtocolFeeDeposit {
+    pub lp_minted: u64,
+    pub unused_x: u64,
+    pub unused_y: u64,
+    pub protocol_fee_lp: u64,
+    pub k_last_high: u64,
+    pub k_last_low: u64,
+}
+
+/// See `ProtocolFeeWithdrawal`, `k_last` split into its high and low 64 bits
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DexMathProtocolFeeWithdrawal {
+    pub amount_x: u64,
+    pub amount_y: u64,
+    pub lp_fee_x: u64,
+    pub lp_fee_y: u64,
+    pub protocol_fee_lp: u64,
+    pub k_last_high: u64,
+    pub k_last_low: u64,
+}
+
+impl DexMathAmmConfig {
+    fn to_amm_config(self) -> Result<AmmConfig, DexMathStatus> {
+        let rate = |ppm: u64| Ppm::new(ppm).map_err(DexMathStatus::from);
+        let directional_fee_rates = if flag(self.has_directional_fee_rates)? {
+            Some(DirectionalFeeRates {
+                x_to_y: FeeRates {
+                    trade_fee_rate: rate(self.x_to_y_trade_fee_rate)?,
+                    protocol_fee_rate: rate(self.x_to_y_protocol_fee_rate)?,
+                },
+                y_to_x: FeeRates {
+                    trade_fee_rate: rate(self.y_to_x_trade_fee_rate)?,
+                    protocol_fee_rate: rate(self.y_to_x_protocol_fee_rate)?,
+                },
+            })
+        } else {
+            None
+        };
+
+        let amm_config = AmmConfig {
+            trade_fee_rate: rate(self.trade_fee_rate)?,
+            protocol_fee_rate: rate(self.protocol_fee_rate)?,
+            ratio_change_tolerance_rate: rate(self.ratio_change_tolerance_rate)?,
+            host_fee_rate: rate(self.host_fee_rate)?,
+            host_fee_source: host_fee_source(self.host_fee_source)?,
+            directional_fee_rates,
+            launch_fee_schedule: None,
+            fee_side: fee_side(self.fee_side)?,
+        };
+        amm_config.validate()?;
+        Ok(amm_config)
+    }
+}
+
+fn fee_side(fee_side: u32) -> Result<FeeSide, DexMathStatus> {
+    match fee_side {
+        value if value == DexMathFeeSide::Input as u32 => Ok(FeeSide::Input),
+        value if value == DexMathFeeSide::Output as u32 => Ok(FeeSide::Output),
+        _ => Err(DexMathStatus::InvalidArgument),
+    }
+}
+
+fn host_fee_source(source: u32) -> Result<HostFeeSource, DexMathStatus> {
+    match source {
+        value if value == DexMathHostFeeSource::TradeFee as u32 => Ok(HostFeeSource::TradeFee),
+        value if value == DexMathHostFeeSource::ProtocolFee as u32 => Ok(HostFeeSource::ProtocolFee),
+        _ => Err(DexMathStatus::InvalidArgument),
+    }
+}
+
+fn flag(value: u8) -> Result<bool, DexMathStatus> {
+    match value {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(DexMathStatus::InvalidArgument),
+    }
+}
+
+impl From<DexMathPoolBalances> for PoolBalances {
+    fn from(balances: DexMathPoolBalances) -> Self {
+        PoolBalances {
+            reserve_x_balance: balances.reserve_x_balance,
+            reserve_y_balance: balances.reserve_y_balance,
+            protocol_fee_x: balances.protocol_fee_x,
+            protocol_fee_y: balances.protocol_fee_y,
+            user_locked_x: balances.user_locked_x,
+            user_locked_y: balances.user_locked_y,
+            locked_x: balances.locked_x,
+            locked_y: balances.locked_y,
+            lp_fee_x: balances.lp_fee_x,
+            lp_fee_y: balances.lp_fee_y,
+        }
+    }
+}
+
+/// Run `body`, turning panics into [`DexMathStatus::Panic`] so they never
+/// unwind into C
+fn guard(body: impl FnOnce() -> Result<(), DexMathStatus> + UnwindSafe) -> DexMathStatus {
+    match std::panic::catch_unwind(body) {
+        Ok(Ok(())) => DexMathStatus::Ok,
+        Ok(Err(status)) => status,
+        Err(_) => DexMathStatus::Panic,
+    }
+}
+
+/// Quote the output for an exact input, see `quote`
+///
+/// # Safety
+/// `amm_config` and `pool_balances` must point to valid structs and `out` to
+/// writable memory for a `DexMathQuoteOutput`.
+#[no_mangle]
+pub unsafe extern "C" fn dex_math_quote(
+    exchange_in: u64,
+    is_swap_x_to_y: u8,
+    amm_config: *const DexMathAmmConfig,
+    pool_balances: *const DexMathPoolBalances,
+    out: *mut DexMathQuoteOutput,
+) -> DexMathStatus {
+    if amm_config.is_null() || pool_balances.is_null() || out.is_null() {
+        return DexMathStatus::NullPointer;
+    }
+    let (amm_config, balances) = (*amm_config, PoolBalances::from(*pool_balances));
+
+    guard(move || {
+        let is_swap_x_to_y = flag(is_swap_x_to_y)?;
+        let amm_config = amm_config.to_amm_config()?;
+        let output = crate::quote_pool_balances(exchange_in, is_swap_x_to_y, &amm_config, &balances)?;
+        *out = DexMathQuoteOutput {
+            from_amount: output.from_amount,
+            to_amount: output.to_amount,
+            trade_fee_rate: output.trade_fee_rate.get(),
+            trade_fee: output.trade_fee,
+            protocol_fee: output.protocol_fee,
+            host_fee: output.host_fee,
+            from_to_lock: output.from_to_lock,
+        };
+        Ok(())
+    })
+}
+
+/// Constant product swap against raw reserves, see `swap_with_fee_side`
+///
+/// # Safety
+/// `out` must point to writable memory for a `DexMathSwapResult`.
+#[no_mangle]
+pub unsafe extern "C" fn dex_math_swap(
+    source_amount: u64,
+    pool_source_amount: u64,
+    pool_destination_amount: u64,
+    trade_fee_rate: u64,
+    protocol_fee_rate: u64,
+    fee_side: u32,
+    out: *mut DexMathSwapResult,
+) -> DexMathStatus {
+    if out.is_null() {
+        return DexMathStatus::NullPointer;
+    }
+
+    guard(move || {
+        let result = crate::swap_with_fee_side(
+            source_amount as u128,
+            pool_source_amount as u128,
+            pool_destination_amount as u128,
+            Ppm::new(trade_fee_rate)?,
+            Ppm::new(protocol_fee_rate)?,
+            self::fee_side(fee_side)?,
+        )
+        .ok_or(DexMathStatus::MathOverflow)?;
+        *out = DexMathSwapResult {
+            from_amount: result.from_amount,
+            to_amount: result.to_amount,
+            trade_fee: result.trade_fee,
+            protocol_fee: result.protocol_fee,
+        };
+        Ok(())
+    })
+}
+
+/// LP tokens minted for a deposit, see `try_deposit_lp`
+///
+/// # Safety
+/// `out_lp_tokens` must point to a writable `uint64_t`.
+#[no_mangle]
+pub unsafe extern "C" fn dex_math_deposit_lp(
+    amount_x: u64,
+    amount_y: u64,
+    total_lp_supply: u64,
+    reserve_x: u64,
+    reserve_y: u64,
+    out_lp_tokens: *mut u64,
+) -> DexMathStatus {
+    if out_lp_tokens.is_null() {
+        return DexMathStatus::NullPointer;
+    }
+
+    guard(move || {
+        *out_lp_tokens = crate::try_deposit_lp(amount_x, amount_y, total_lp_supply, reserve_x, reserve_y)?;
+        Ok(())
+    })
+}
+
+/// Tokens returned for burning LP tokens, see `try_withdraw_lp`
+///
+/// # Safety
+/// `out_amount_x` and `out_amount_y` must point to writable `uint64_t`s.
+#[no_mangle]
+pub unsafe extern "C" fn dex_math_withdraw_lp(
+    lp_tokens: u64,
+    total_lp_supply: u64,
+    reserve_x: u64,
+    reserve_y: u64,
+    out_amount_x: *mut u64,
+    out_amount_y: *mut u64,
+) -> DexMathStatus {
+    if out_amount_x.is_null() || out_amount_y.is_null() {
+        return DexMathStatus::NullPointer;
+    }
+
+    guard(move || {
+        let (amount_x, amount_y) = crate::try_withdraw_lp(lp_tokens, total_lp_supply, reserve_x, reserve_y)?;
+        *out_amount_x = amount_x;
+        *out_amount_y = amount_y;
+        Ok(())
+    })
+}
+
+/// LP tokens minted for a deposit after minting the protocol fee, see
+/// `deposit_lp_with_protocol_fee`; `k_last` is passed and returned as its high
+/// and low 64 bits, `protocol_share` in ppm
+///
+/// # Safety
+/// `pool_balances` must point to a valid struct and `out` to writable memory
+/// for a `DexMathProtocolFeeDeposit`.
+#[no_mangle]
+pub unsafe extern "C" fn dex_math_deposit_lp_with_protocol_fee(
+    amount_x: u64,
+    amount_y: u64,
+    total_lp_supply: u64,
+    pool_balances: *const DexMathPoolBalances,
+    k_last_high: u64,
+    k_last_low: u64,
+    protocol_share: u64,
+    out: *mut DexMathProtocolFeeDeposit,
+) -> DexMathStatus {
+    if pool_balances.is_null() || out.is_null() {
+        return DexMathStatus::NullPointer;
+    }
+    let balances = PoolBalances::from(*pool_balances);
+
+    guard(move || {
+        let deposit = crate::deposit_lp_with_protocol_fee(
+            amount_x,
+            amount_y,
+            total_lp_supply,
+            &balances,
+            join_u128(k_last_high, k_last_low),
+            Ppm::new(protocol_share)?,
+        )?;
+        let (k_last_high, k_last_low) = split_u128(deposit.k_last);
+        *out = DexMathProtocolFeeDeposit {
+            lp_minted: deposit.lp_minted,
+            unused_x: deposit.unused_x,
+            unused_y: deposit.unused_y,
+            protocol_fee_lp: deposit.protocol_fee_lp,
+            k_last_high,
+            k_last_low,
+        };
+        Ok(())
+    })
+}
+
+/// Tokens returned for burning LP tokens after minting the protocol fee, see
+/// `withdraw_lp_with_protocol_fee`; `k_last` is passed and returned as its
+/// high and low 64 bits, `protocol_share` in ppm
+///
+/// # Safety
+/// `pool_balances` must point to a valid struct and `out` to writable memory
+/// for a `DexMathProtocolFeeWithdrawal`.
+#[no_mangle]
+pub unsafe extern "C" fn dex_math_withdraw_lp_with_protocol_fee(
+    lp_tokens: u64,
+    total_lp_supply: u64,
+    pool_balances: *const DexMathPoolBalances,
+    k_last_high: u64,
+    k_last_low: u64,
+    protocol_share: u64,
+    out: *mut DexMathProtocolFeeWithdrawal,
+) -> DexMathStatus {
+    if pool_balances.is_null() || out.is_null() {
+        return DexMathStatus::NullPointer;
+    }
+    let balances = PoolBalances::from(*pool_balances);
+
+    guard(move || {
+        let withdrawal = crate::withdraw_lp_with_protocol_fee(
+            lp_tokens,
+            total_lp_supply,
+            &balances,
+            join_u128(k_last_high, k_last_low),
+            Ppm::new(protocol_share)?,
+        )?;
+        let (k_last_high, k_last_low) = split_u128(withdrawal.k_last);
+        *out = DexMathProtocolFeeWithdrawal {
+            amount_x: withdrawal.amount_x,
+            amount_y: withdrawal.amount_y,
+            lp_fee_x: withdrawal.lp_fee_x,
+            lp_fee_y: withdrawal.lp_fee_y,
+            protocol_fee_lp: withdrawal.protocol_fee_lp,
+            k_last_high,
+            k_last_low,
+        };
+        Ok(())
+    })
+}
+
+fn join_u128(high: u64, low: u64) -> u128 {
+    (high as u128) << 64 | low as u128
+}
+
+fn split_u128(value: u128) -> (u64, u64) {
+    ((value >> 64) as u64, value as u64)
+}
+
+/// Name of a status, e.g. `"TradeTooBig"`, as a static NUL terminated string,
+/// `"Unknown"` for values that are not a `DexMathStatus`
+#[no_mangle]
+pub extern "C" fn dex_math_status_name(status: u32) -> *const c_char {
+    let name = match status {
+        0 => c"Ok",
+        1 => c"NullPointer",
+        2 => c"Panic",
+        3 => c"InvalidArgument",
+        6000 => c"MathOverflow",
+        6001 => c"InsufficientPoolTokenXBalance",
+        6002 => c"InsufficientPoolTokenYBalance",
+        6003 => c"TradeTooBig",
+        6004 => c"InputAmountTooSmall",
+        6005 => c"InvalidRate",
+        6006 => c"InvalidTradeFeeRate",
+        6007 => c"InvalidProtocolFeeRate",
+        6008 => c"InvalidRatioChangeToleranceRate",
+        6009 => c"InvalidHostFeeRate",
+        6010 => c"FlashSwapInvariantViolated",
+        6011 => c"ZeroLpSupply",
+        6012 => c"InsufficientLiquidityForOrders",
+        6013 => c"InsufficientLpSupply",
+        _ => c"Unknown",
+    };
+    name.as_ptr()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn statuses_mirror_error_codes() {
+        for error in MathError::ALL {
+            let status = DexMathStatus::from(error);
+            assert_eq!(status as u32, error.code());
+
+            let name = unsafe { std::ffi::CStr::from_ptr(dex_math_status_name(status as u32)) };
+            assert_eq!(format!("MathLib{}", name.to_str().unwrap()), error.name());
+        }
+        let name = unsafe { std::ffi::CStr::from_ptr(dex_math_status_name(DexMathStatus::InvalidArgument as u32)) };
+        assert_eq!(name.to_str().unwrap(), "InvalidArgument");
+        let name = unsafe { std::ffi::CStr::from_ptr(dex_math_status_name(42)) };
+        assert_eq!(name.to_str().unwrap(), "Unknown");
+    }
+
+    #[test]
+    fn quotes_through_the_c_abi() {
+        let amm_config = DexMathAmmConfig {
+            trade_fee_rate: 3_000,
+            protocol_fee_rate: 200_000,
+            ratio_change_tolerance_rate: 1_000_000,
+            host_fee_rate: 0,
+            host_fee_source: DexMathHostFeeSource::TradeFee as u32,
+            fee_side: DexMathFeeSide::Input as u32,
+            has_directional_fee_rates: 0,
+            x_to_y_trade_fee_rate: 0,
+            x_to_y_protocol_fee_rate: 0,
+            y_to_x_trade_fee_rate: 0,
+            y_to_x_protocol_fee_rate: 0,
+        };
+        let pool_balances = DexMathPoolBalances {
+            reserve_x_balance: 1_000_000,
+            reserve_y_balance: 2_000_000,
+            ..Default::default()
+        };
+
+        let mut out = DexMathQuoteOutput::default();
+        let status = unsafe { dex_math_quote(10_000, 1, &amm_config, &pool_balances, &mut out) };
+        assert_eq!(status, DexMathStatus::Ok);
+        let expected = crate::quote(10_000, true, &amm_config.to_amm_config().unwrap(), 0, 0, 0, 0, 0, 0, 1_000_000, 2_000_000, 0, 0)
+            .unwrap();
+        assert_eq!((out.to_amount, out.from_to_lock), (expected.to_amount, expected.from_to_lock));
+
+        let invalid = DexMathAmmConfig { trade_fee_rate: 1_000_000, ..amm_config };
+        let status = unsafe { dex_math_quote(10_000, 1, &invalid, &pool_balances, &mut out) };
+        assert_eq!(status, DexMathStatus::InvalidTradeFeeRate);
+
+        for invalid in [
+            DexMathAmmConfig { fee_side: 2, ..amm_config },
+            DexMathAmmConfig { host_fee_source: 2, ..amm_config },
+            DexMathAmmConfig { has_directional_fee_rates: 2, ..amm_config },
+        ] {
+            let status = unsafe { dex_math_quote(10_000, 1, &invalid, &pool_balances, &mut out) };
+            assert_eq!(status, DexMathStatus::InvalidArgument);
+        }
+        let status = unsafe { dex_math_quote(10_000, 2, &amm_config, &pool_balances, &mut out) };
+        assert_eq!(status, DexMathStatus::InvalidArgument);
+
+        let empty = DexMathPoolBalances::default();
+        let status = unsafe { dex_math_quote(10_000, 1, &amm_config, &empty, &mut out) };
+        assert_ne!(status, DexMathStatus::Ok);
+
+        let status = unsafe { dex_math_quote(10_000, 1, &amm_config, std::ptr::null(), &mut out) };
+        assert_eq!(status, DexMathStatus::NullPointer);
+    }
+}
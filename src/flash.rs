@@ -0,0 +1,190 @@
+//! Flash swap and flash loan math
+//!
+//! A flash swap hands out tokens first and checks the pool afterwards: the
+//! balances left once the repayment arrived, minus the trade fee on whatever was
+//! paid in, must keep the constant product at least where it was.
+
+use crate::{
+    state::{FlashLoanResult, FlashSwapResult},
+    utils::amount_before_fees,
+    errors::{MathError, Result},
+    Ppm,
+};
+
+/// Verify the repayment of a flash swap
+///
+/// Uniswap v2 style balance-adjusted check, with fees charged on the amounts
+/// paid back like [`crate::get_trade_fee`] / [`crate::get_protocol_fee`] do:
+///
+///   (x - x_out + x_in - fee(x_in)) * (y - y_out + y_in - fee(y_in)) >= x * y
+///
+/// # Arguments
+/// * `reserve_x` - The available X balance before the flash swap (excluding fees and locks)
+/// * `reserve_y` - The available Y balance before the flash swap (excluding fees and locks)
+/// * `amount_x_out` - The amount of X sent out
+/// * `amount_y_out` - The amount of Y sent out
+/// * `amount_x_in` - The amount of X paid back
+/// * `amount_y_in` - The amount of Y paid back
+/// * `trade_fee_rate` - The trade fee rate charged on paid back amounts
+/// * `protocol_fee_rate` - The share of the trade fee going to the protocol
+#[allow(clippy::too_many_arguments)]
+pub fn verify_flash_swap(
+    reserve_x: u64,
+    reserve_y: u64,
+    amount_x_out: u64,
+    amount_y_out: u64,
+    amount_x_in: u64,
+    amount_y_in: u64,
+    trade_fee_rate: Ppm,
+    protocol_fee_rate: Ppm,
+) -> Result<FlashSwapResult> {
+    if amount_x_out == 0 && amount_y_out == 0 {
+        return Err(MathError::InputAmountTooSmall);
+    }
+    if amount_x_out >= reserve_x && amount_x_out > 0 {
+        return Err(MathError::InsufficientPoolTokenXBalance);
+    }
+    if amount_y_out >= reserve_y && amount_y_out > 0 {
+        return Err(MathError::InsufficientPoolTokenYBalance);
+    }
+
+    let (trade_fee_x, protocol_fee_x) = flash_fees(amount_x_in, trade_fee_rate, protocol_fee_rate)?;
+    let (trade_fee_y, protocol_fee_y) = flash_fees(amount_y_in, trade_fee_rate, protocol_fee_rate)?;
+
+    // balances excluding the fees charged on the repayment
+    let adjusted_x = (reserve_x as u128 - amount_x_out as u128)
+        .checked_add(amount_x_in as u128 - trade_fee_x as u128)
+        .ok_or(MathError::MathOverflow)?;
+    let adjusted_y = (reserve_y as u128 - amount_y_out as u128)
+        .checked_add(amount_y_in as u128 - trade_fee_y as u128)
+        .ok_or(MathError::MathOverflow)?;
+
+    let previous_value = (reserve_x as u128)
+        .checked_mul(reserve_y as u128)
+        .ok_or(MathError::MathOverflow)?;
+    let new_value = adjusted_x
+        .checked_mul(adjusted_y)
+        .ok_or(MathError::MathOverflow)?;
+
+    if new_value < previous_value {
+        return Err(MathError::FlashSwapInvariantViolated);
+    }
+
+    Ok(FlashSwapResult {
+        reserve_x: u64::try_from(adjusted_x).map_err(|_| MathError::MathOverflow)?,
+        reserve_y: u64::try_from(adjusted_y).map_err(|_| MathError::MathOverflow)?,
+        trade_fee_x,
+        trade_fee_y,
+        protocol_fee_x,
+        protocol_fee_y,
+    })
+}
+
+/// Minimum repayment for a pure flash loan of `amount`, borrowed and returned
+/// in the same token
+///
+/// The fee is charged on the repayment like [`verify_flash_swap`] does, so the
+/// result is the smallest repayment that passes it with `flash_fee_rate`.
+pub fn flash_loan_repayment(
+    amount: u64,
+    flash_fee_rate: Ppm,
+    protocol_fee_rate: Ppm,
+) -> Result<FlashLoanResult> {
+    if amount == 0 {
+        return Err(MathError::InputAmountTooSmall);
+    }
+
+    let min_repayment = amount_before_fees(amount as u128, flash_fee_rate)
+        .and_then(|repayment| u64::try_from(repayment).ok())
+        .ok_or(MathError::MathOverflow)?;
+    let (flash_fee, protocol_fee) = flash_fees(min_repayment, flash_fee_rate, protocol_fee_rate)?;
+
+    Ok(FlashLoanResult {
+        min_repayment,
+        flash_fee,
+        protocol_fee,
+    })
+}
+
+fn flash_fees(amount: u64, fee_rate: Ppm, protocol_fee_rate: Ppm) -> Result<(u64, u64)> {
+    let fee = fee_rate
+        .apply_ceil(amount as u128)
+        .ok_or(MathError::MathOverflow)?;
+    let protocol_fee = protocol_fee_rate
+        .apply_floor(fee)
+        .ok_or(MathError::MathOverflow)?;
+
+    // never more than the amount itself, rates are capped at 100%
+    Ok((fee as u64, protocol_fee as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::swap, proptest::prelude::*};
+
+    fn rates() -> (Ppm, Ppm) {
+        (Ppm::new(3_000).unwrap(), Ppm::new(200_000).unwrap())
+    }
+
+    #[test]
+    fn repaying_the_borrowed_amount_plus_fee_passes() {
+        let (trade_fee_rate, protocol_fee_rate) = rates();
+        let loan = flash_loan_repayment(1_000_000, trade_fee_rate, protocol_fee_rate).unwrap();
+        // 1_003_010 - ceil(1_003_010 * 0.3%) = 1_000_000
+        assert_eq!(loan.min_repayment, 1_003_010);
+        assert_eq!(loan.flash_fee, 3_010);
+        assert_eq!(loan.protocol_fee, 602);
+
+        // the minimum repayment passes the invariant check, one less does not
+        assert!(verify_flash_swap(
+            10_000_000, 10_000_000, 1_000_000, 0, loan.min_repayment, 0, trade_fee_rate, protocol_fee_rate,
+        )
+        .is_ok());
+        assert!(verify_flash_swap(
+            10_000_000, 10_000_000, 1_000_000, 0, loan.min_repayment - 1, 0, trade_fee_rate, protocol_fee_rate,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn rejects_borrowing_the_whole_pool() {
+        let (trade_fee_rate, protocol_fee_rate) = rates();
+        assert!(verify_flash_swap(100, 100, 100, 0, 1_000, 0, trade_fee_rate, protocol_fee_rate).is_err());
+        assert!(verify_flash_swap(100, 100, 0, 0, 0, 0, trade_fee_rate, protocol_fee_rate).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn swap_output_is_a_valid_flash_swap(
+            source_amount in 1..u32::MAX as u64,
+            reserve_x in 1..u32::MAX as u64,
+            reserve_y in 1..u32::MAX as u64,
+        ) {
+            let (trade_fee_rate, protocol_fee_rate) = rates();
+            let swap_result = swap(
+                source_amount as u128,
+                reserve_x as u128,
+                reserve_y as u128,
+                trade_fee_rate,
+                protocol_fee_rate,
+            )
+            .unwrap();
+            prop_assume!(swap_result.to_amount > 0 && swap_result.to_amount < reserve_y);
+
+            // borrowing the swap output and paying the swap input is always enough
+            let result = verify_flash_swap(
+                reserve_x,
+                reserve_y,
+                0,
+                swap_result.to_amount,
+                source_amount,
+                0,
+                trade_fee_rate,
+                protocol_fee_rate,
+            )
+            .unwrap();
+            prop_assert_eq!(result.trade_fee_x, swap_result.trade_fee);
+            prop_assert_eq!(result.protocol_fee_x, swap_result.protocol_fee);
+        }
+    }
+}
@@ -1,19 +1,61 @@
-/// DEX Math Library
-/// 
-/// This library provides mathematical functions for decentralized exchange operations
-/// including quoting, liquidity pool deposits, and withdrawals.
+//! DEX Math Library
+//!
+//! This library provides mathematical functions for decentralized exchange operations
+//! including quoting, liquidity pool deposits, and withdrawals.
+//!
+//! The math is `no_std` with `default-features = false`; it only needs `alloc`
+//! for batch fills. Features:
+//!
+//! - `std` (default): `std::error::Error` for [`MathError`] and the simulators
+//! - `borsh` (default): borsh (de)serialization of the state types
+//! - `anchor`: the on-chain `ErrorCode`, with `MathError` converting into it
+//! - `serde`, `cli`, `server`, `wasm`, `ffi`: serde support and the frontends
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
 
 pub mod swap;
+pub mod batch;
 pub mod liquidity;
 pub mod state;
 pub mod errors;
 pub mod utils;
 pub mod constants;
+pub mod rate;
+pub mod amount;
+pub mod fee_schedule;
+pub mod flash;
+#[cfg(feature = "std")]
+pub mod sim;
+#[cfg(feature = "std")]
+pub mod backtest;
+#[cfg(feature = "std")]
+pub mod monte_carlo;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(kani)]
+mod proofs;
 
 // Re-export functions for convenience
-pub use swap::quote;
-pub use liquidity::{deposit_lp, withdraw_lp};
+pub use swap::{
+    max_exchange_in, quote, quote_at, quote_exact_out, quote_pool_balances, quote_pool_balances_at, quote_typed,
+};
+pub use batch::clear_batch;
+pub use flash::{flash_loan_repayment, verify_flash_swap};
+pub use liquidity::{
+    deposit_imbalanced, deposit_lp, deposit_lp_strict, deposit_lp_with_protocol_fee, mint_protocol_fee_lp,
+    required_x_for_y, required_y_for_x, try_deposit_lp, try_withdraw_lp, withdraw_exact_amounts, withdraw_lp,
+    withdraw_lp_from_pool, withdraw_lp_with_protocol_fee,
+};
 pub use state::*;
+pub use errors::MathError;
+#[cfg(feature = "anchor")]
 pub use errors::ErrorCode;
 pub use utils::*;
-pub use constants::MAX_PERCENTAGE;
\ No newline at end of file
+pub use constants::MAX_PERCENTAGE;
+pub use rate::{Bps, Ppm};
+pub use amount::{Amount, FeeAmount, Side, SwapDirection, X, Y};
+pub use fee_schedule::{effective_fee_rate, FeeDecay, FeeSchedule};
\ No newline at end of file
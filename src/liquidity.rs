@@ -1,9 +1,25 @@
-/// Liquidity operations for DEX
-/// 
-/// This module provides mathematical functions for liquidity pool operations
-/// including deposits and withdrawals.
+//! Liquidity operations for DEX
+//!
+//! This module provides mathematical functions for liquidity pool operations
+//! including deposits and withdrawals.
+
+use crate::{
+    constants::MIN_LIQUIDITY,
+    state::{
+        AmmConfig, DepositQuote, ExactWithdrawal, ImbalancedDeposit, LpWithdrawal, PoolBalances, ProtocolFeeDeposit,
+        ProtocolFeeWithdrawal,
+    },
+    utils::{
+        ceil_div, floor_div, is_ratio_change_tolerance_exceeded, mul_div_floor, sqrt, sqrt_ceil,
+        swap_base_input_without_fees,
+    },
+    errors::{MathError, Result},
+    FeeRates, Ppm, SwapDirection, MAX_PERCENTAGE,
+};
 
 /// Calculate the amount of LP tokens to mint for a deposit
+///
+/// Does not mint the protocol fee, see [`deposit_lp_with_protocol_fee`].
 /// 
 /// # Arguments
 /// * `token_a_amount` - Amount of token A being deposited
@@ -24,7 +40,7 @@ pub fn deposit_lp(
     if total_lp_supply == 0 {
         // Initial liquidity provision
         // LP tokens = sqrt(token_a * token_b)
-        ((token_a_amount as u128 * token_b_amount as u128) as f64).sqrt() as u64
+        sqrt(token_a_amount as u128 * token_b_amount as u128) as u64
     } else {
         // Calculate LP tokens based on proportional share
         let token_a_lp = (token_a_amount * total_lp_supply) / token_a_reserve;
@@ -36,6 +52,8 @@ pub fn deposit_lp(
 }
 
 /// Calculate the amount of tokens to return for a withdrawal
+///
+/// Does not mint the protocol fee, see [`withdraw_lp_with_protocol_fee`].
 /// 
 /// # Arguments
 /// * `lp_tokens` - Amount of LP tokens being burned
@@ -60,4 +78,1082 @@ pub fn withdraw_lp(
     let token_b_amount = (lp_tokens * token_b_reserve) / total_lp_supply;
     
     (token_a_amount, token_b_amount)
-}
\ No newline at end of file
+}
+
+/// [`deposit_lp`] returning `MathOverflow` instead of panicking on a zero
+/// reserve or a `u64` overflow
+pub fn try_deposit_lp(
+    token_a_amount: u64,
+    token_b_amount: u64,
+    total_lp_supply: u64,
+    token_a_reserve: u64,
+    token_b_reserve: u64,
+) -> Result<u64> {
+    if total_lp_supply == 0 {
+        return Ok(deposit_lp(token_a_amount, token_b_amount, 0, token_a_reserve, token_b_reserve));
+    }
+
+    let token_a_lp = token_a_amount
+        .checked_mul(total_lp_supply)
+        .and_then(|amount| amount.checked_div(token_a_reserve));
+    let token_b_lp = token_b_amount
+        .checked_mul(total_lp_supply)
+        .and_then(|amount| amount.checked_div(token_b_reserve));
+    match (token_a_lp, token_b_lp) {
+        (Some(token_a_lp), Some(token_b_lp)) => Ok(token_a_lp.min(token_b_lp)),
+        _ => Err(MathError::MathOverflow),
+    }
+}
+
+/// [`withdraw_lp`] returning `MathOverflow` instead of panicking on a `u64`
+/// overflow
+pub fn try_withdraw_lp(
+    lp_tokens: u64,
+    total_lp_supply: u64,
+    token_a_reserve: u64,
+    token_b_reserve: u64,
+) -> Result<(u64, u64)> {
+    if total_lp_supply == 0 {
+        return Ok((0, 0));
+    }
+
+    let token_a_amount = lp_tokens
+        .checked_mul(token_a_reserve)
+        .ok_or(MathError::MathOverflow)?
+        / total_lp_supply;
+    let token_b_amount = lp_tokens
+        .checked_mul(token_b_reserve)
+        .ok_or(MathError::MathOverflow)?
+        / total_lp_supply;
+    Ok((token_a_amount, token_b_amount))
+}
+
+/// Calculate the LP tokens to mint to the protocol for the fees earned since
+/// the last liquidity event
+///
+/// Uniswap v2 style: the growth of `sqrt(k)` since `k_last` is fee income, and
+/// the protocol gets `protocol_share` of it as new LP tokens:
+///
+///   supply * (sqrt(k) - sqrt(k_last)) * share / (sqrt(k) * (1 - share) + sqrt(k_last) * share)
+///
+/// # Arguments
+/// * `k_last` - Product of the reserves after the last liquidity event, 0 if
+///   the protocol fee was off
+/// * `reserve_x` - Current reserve of X in the pool
+/// * `reserve_y` - Current reserve of Y in the pool
+/// * `total_lp_supply` - Current total supply of LP tokens
+/// * `protocol_share` - Share of the fee growth going to the protocol
+pub fn mint_protocol_fee_lp(
+    k_last: u128,
+    reserve_x: u64,
+    reserve_y: u64,
+    total_lp_supply: u64,
+    protocol_share: Ppm,
+) -> Result<u64> {
+    if k_last == 0 || total_lp_supply == 0 || protocol_share == Ppm::ZERO {
+        return Ok(0);
+    }
+
+    let root_k = sqrt(reserve_x as u128 * reserve_y as u128);
+    let root_k_last = sqrt(k_last);
+    if root_k <= root_k_last {
+        return Ok(0);
+    }
+
+    // roots are below 2^64 and the share below 2^20, only the supply needs
+    // the wide product
+    let share = protocol_share.get() as u128;
+    let denominator = root_k * (MAX_PERCENTAGE as u128 - share) + root_k_last * share;
+    mul_div_floor(total_lp_supply as u128, (root_k - root_k_last) * share, denominator)
+        .and_then(|protocol_fee_lp| u64::try_from(protocol_fee_lp).ok())
+        .ok_or(MathError::MathOverflow)
+}
+
+/// Calculate the LP tokens to mint for a deposit, minting the protocol fee
+/// first
+///
+/// [`deposit_lp_strict`] once [`mint_protocol_fee_lp`] has been added to the
+/// supply, so the depositor does not pay for fees earned before the deposit.
+/// `k` is the product of the LP-owned balances, see [`PoolBalances::lp_owned`],
+/// as the LP fees accrue in their buckets rather than in the reserves. The
+/// first deposit mints `sqrt(amount_x * amount_y)` like [`deposit_lp`].
+pub fn deposit_lp_with_protocol_fee(
+    amount_x: u64,
+    amount_y: u64,
+    total_lp_supply: u64,
+    pool_balances: &PoolBalances,
+    k_last: u128,
+    protocol_share: Ppm,
+) -> Result<ProtocolFeeDeposit> {
+    let (lp_owned_x, lp_owned_y) = pool_balances.lp_owned().ok_or(MathError::MathOverflow)?;
+
+    let (deposit, protocol_fee_lp) = if total_lp_supply == 0 {
+        let deposit = DepositQuote {
+            amount_x,
+            amount_y,
+            lp_minted: sqrt(amount_x as u128 * amount_y as u128) as u64,
+            unused_x: 0,
+            unused_y: 0,
+        };
+        (deposit, 0)
+    } else {
+        let protocol_fee_lp =
+            mint_protocol_fee_lp(k_last, lp_owned_x, lp_owned_y, total_lp_supply, protocol_share)?;
+        let total_lp_supply = total_lp_supply
+            .checked_add(protocol_fee_lp)
+            .ok_or(MathError::MathOverflow)?;
+        (deposit_lp_strict(amount_x, amount_y, total_lp_supply, pool_balances)?, protocol_fee_lp)
+    };
+
+    let (new_x, new_y) = (
+        lp_owned_x.checked_add(amount_x - deposit.unused_x).ok_or(MathError::MathOverflow)?,
+        lp_owned_y.checked_add(amount_y - deposit.unused_y).ok_or(MathError::MathOverflow)?,
+    );
+
+    Ok(ProtocolFeeDeposit {
+        lp_minted: deposit.lp_minted,
+        unused_x: deposit.unused_x,
+        unused_y: deposit.unused_y,
+        protocol_fee_lp,
+        k_last: new_x as u128 * new_y as u128,
+    })
+}
+
+/// Calculate the tokens to return for a withdrawal, minting the protocol fee
+/// first
+///
+/// [`withdraw_lp_from_pool`] once [`mint_protocol_fee_lp`] has been added to
+/// the supply, so the protocol's share of the fees stays in the pool. `k` is
+/// the product of the LP-owned balances, see [`PoolBalances::lp_owned`].
+pub fn withdraw_lp_with_protocol_fee(
+    lp_tokens: u64,
+    total_lp_supply: u64,
+    pool_balances: &PoolBalances,
+    k_last: u128,
+    protocol_share: Ppm,
+) -> Result<ProtocolFeeWithdrawal> {
+    if total_lp_supply == 0 {
+        return Err(MathError::ZeroLpSupply);
+    }
+    if lp_tokens > total_lp_supply {
+        return Err(MathError::InsufficientLpSupply);
+    }
+
+    let (lp_owned_x, lp_owned_y) = pool_balances.lp_owned().ok_or(MathError::MathOverflow)?;
+    let protocol_fee_lp = mint_protocol_fee_lp(k_last, lp_owned_x, lp_owned_y, total_lp_supply, protocol_share)?;
+    let total_lp_supply = total_lp_supply
+        .checked_add(protocol_fee_lp)
+        .ok_or(MathError::MathOverflow)?;
+
+    let withdrawal = withdraw_lp_from_pool(lp_tokens, total_lp_supply, pool_balances)?;
+
+    Ok(ProtocolFeeWithdrawal {
+        amount_x: withdrawal.amount_x,
+        amount_y: withdrawal.amount_y,
+        lp_fee_x: withdrawal.lp_fee_x,
+        lp_fee_y: withdrawal.lp_fee_y,
+        protocol_fee_lp,
+        // the payout comes out of the LP-owned balances only
+        k_last: (lp_owned_x - withdrawal.amount_x) as u128 * (lp_owned_y - withdrawal.amount_y) as u128,
+    })
+}
+
+/// Calculate the tokens to return for a withdrawal from a pool with pending
+/// orders and fee buckets
+///
+/// LPs are paid their share of the available reserves and of the LP fees.
+/// Protocol fees, user funds of pending orders and pool funds locked for them
+/// stay in the pool.
+///
+/// # Arguments
+/// * `lp_tokens` - Amount of LP tokens being burned
+/// * `total_lp_supply` - Current total supply of LP tokens
+/// * `pool_balances` - Current balances of the pool
+///
+/// # Errors
+/// `MathLibInsufficientLpSupply` if `lp_tokens` exceeds the supply.
+///
+/// `MathLibInsufficientLiquidityForOrders` if the pool left after the
+/// withdrawal cannot settle the pending orders: their `user_locked_x/y` inputs
+/// are swapped without fees against the remaining available balances plus the
+/// pool funds locked for them, and paying that out must leave `MIN_LIQUIDITY`
+/// of the destination token.
+pub fn withdraw_lp_from_pool(
+    lp_tokens: u64,
+    total_lp_supply: u64,
+    pool_balances: &PoolBalances,
+) -> Result<LpWithdrawal> {
+    if lp_tokens == 0 {
+        return Err(MathError::InputAmountTooSmall);
+    }
+    if total_lp_supply == 0 {
+        return Err(MathError::ZeroLpSupply);
+    }
+    if lp_tokens > total_lp_supply {
+        return Err(MathError::InsufficientLpSupply);
+    }
+
+    let (available_x, available_y) = pool_balances
+        .available()
+        .ok_or(MathError::MathOverflow)?;
+
+    let share = |amount: u64| -> Result<u64> {
+        // lp_tokens <= total_lp_supply, so the share fits in u64
+        Ok(floor_div(amount as u128, lp_tokens as u128, total_lp_supply as u128)
+            .ok_or(MathError::MathOverflow)? as u64)
+    };
+
+    let (reserve_share_x, reserve_share_y) = (share(available_x)?, share(available_y)?);
+    let (lp_fee_x, lp_fee_y) = (share(pool_balances.lp_fee_x)?, share(pool_balances.lp_fee_y)?);
+
+    // pool funds backing the pending orders once the LP is paid
+    let pool_x = (available_x - reserve_share_x) as u128 + pool_balances.locked_x as u128;
+    let pool_y = (available_y - reserve_share_y) as u128 + pool_balances.locked_y as u128;
+    if !can_settle(pool_balances.user_locked_x, pool_x, pool_y)
+        || !can_settle(pool_balances.user_locked_y, pool_y, pool_x)
+    {
+        return Err(MathError::InsufficientLiquidityForOrders);
+    }
+
+    let amount_x = reserve_share_x + lp_fee_x;
+    let amount_y = reserve_share_y + lp_fee_y;
+
+    Ok(LpWithdrawal {
+        amount_x,
+        amount_y,
+        lp_fee_x,
+        lp_fee_y,
+        pool_balances: PoolBalances {
+            reserve_x_balance: pool_balances.reserve_x_balance - amount_x,
+            reserve_y_balance: pool_balances.reserve_y_balance - amount_y,
+            lp_fee_x: pool_balances.lp_fee_x - lp_fee_x,
+            lp_fee_y: pool_balances.lp_fee_y - lp_fee_y,
+            ..*pool_balances
+        },
+    })
+}
+
+/// Whether pending orders with `user_locked` input can be paid out of
+/// `pool_destination` and still leave `MIN_LIQUIDITY`
+///
+/// Each direction is checked on its own; settling the other direction first
+/// only adds to the destination side of this one.
+fn can_settle(user_locked: u64, pool_source: u128, pool_destination: u128) -> bool {
+    if user_locked == 0 {
+        return true;
+    }
+    // fees only lower the payout
+    let payout = swap_base_input_without_fees(user_locked as u128, pool_source, pool_destination);
+    pool_destination - payout >= MIN_LIQUIDITY as u128
+}
+
+/// Calculate the LP tokens to burn for withdrawing exactly `amount_x` and
+/// `amount_y`
+///
+/// The LP value of the pool is measured by the invariant `sqrt(x * y)` of its
+/// LP-owned balances, the available reserves plus the LP fee buckets, which
+/// [`withdraw_lp_from_pool`] pays out too. The amounts themselves must come out
+/// of the available reserves. The part of the withdrawal that differs from the
+/// LP-owned ratio pays the trade fee like a swap would, at the rates of that
+/// swap's direction. The fee stays in the pool and is burned on top.
+///
+/// # Arguments
+/// * `amount_x` - Amount of X to withdraw
+/// * `amount_y` - Amount of Y to withdraw
+/// * `total_lp_supply` - Current total supply of LP tokens
+/// * `pool_balances` - Current balances of the pool
+/// * `amm_config` - Trade / protocol fee rates and the ratio change tolerance
+///
+/// # Errors
+/// `MathLibTradeTooBig` if the withdrawal moves the pool ratio by more than
+/// `ratio_change_tolerance_rate`
+pub fn withdraw_exact_amounts(
+    amount_x: u64,
+    amount_y: u64,
+    total_lp_supply: u64,
+    pool_balances: &PoolBalances,
+    amm_config: &AmmConfig,
+) -> Result<ExactWithdrawal> {
+    if amount_x == 0 && amount_y == 0 {
+        return Err(MathError::InputAmountTooSmall);
+    }
+    if total_lp_supply == 0 {
+        return Err(MathError::ZeroLpSupply);
+    }
+
+    let (available_x, available_y) = available_reserves(pool_balances)?;
+    if amount_x >= available_x {
+        return Err(MathError::InsufficientPoolTokenXBalance);
+    }
+    if amount_y >= available_y {
+        return Err(MathError::InsufficientPoolTokenYBalance);
+    }
+
+    if is_ratio_change_tolerance_exceeded(
+        available_x as f64 / available_y as f64,
+        (available_x - amount_x) as f64 / (available_y - amount_y) as f64,
+        amm_config.ratio_change_tolerance_rate,
+    ) {
+        return Err(MathError::TradeTooBig);
+    }
+
+    let (owned_x, owned_y) = pool_balances.lp_owned().ok_or(MathError::MathOverflow)?;
+    let (reserve_x, reserve_y) = (owned_x as u128, owned_y as u128);
+    let (remaining_x, remaining_y) = (reserve_x - amount_x as u128, reserve_y - amount_y as u128);
+
+    // taking more X than the pool ratio is swapping Y for it
+    let fee_rates = amm_config.fee_rates(excess_direction(amount_x, amount_y, owned_x, owned_y).reverse());
+
+    // both products fit, balances are u64
+    let invariant = sqrt_ceil(reserve_x * reserve_y);
+    let remaining_invariant = sqrt(remaining_x * remaining_y);
+
+    let (imbalance_fee_x, protocol_fee_x) =
+        imbalance_fees(reserve_x, remaining_x, invariant, remaining_invariant, fee_rates)?;
+    let (imbalance_fee_y, protocol_fee_y) =
+        imbalance_fees(reserve_y, remaining_y, invariant, remaining_invariant, fee_rates)?;
+
+    let invariant_after_fees = sqrt(
+        remaining_x
+            .checked_sub(imbalance_fee_x)
+            .ok_or(MathError::TradeTooBig)?
+            * remaining_y
+                .checked_sub(imbalance_fee_y)
+                .ok_or(MathError::TradeTooBig)?,
+    );
+
+    // invariant_after_fees <= invariant, so this never exceeds the supply
+    let lp_tokens_burned = ceil_div(total_lp_supply as u128, invariant - invariant_after_fees, invariant)
+        .ok_or(MathError::MathOverflow)?;
+
+    Ok(ExactWithdrawal {
+        amount_x,
+        amount_y,
+        lp_tokens_burned: lp_tokens_burned as u64,
+        imbalance_fee_x: imbalance_fee_x as u64,
+        imbalance_fee_y: imbalance_fee_y as u64,
+        protocol_fee_x: protocol_fee_x as u64,
+        protocol_fee_y: protocol_fee_y as u64,
+    })
+}
+
+/// Calculate the LP tokens to mint for an imbalanced deposit
+///
+/// Unlike [`deposit_lp`], the excess of the larger side is not donated. The
+/// deposit is valued by the growth of the invariant `sqrt(x * y)`, and the part
+/// of it that differs from the pool ratio pays the trade fee like the implicit
+/// swap would, as in [`withdraw_exact_amounts`].
+///
+/// # Arguments
+/// * `amount_x` - Amount of X being deposited
+/// * `amount_y` - Amount of Y being deposited
+/// * `total_lp_supply` - Current total supply of LP tokens
+/// * `pool_balances` - Current balances of the pool
+/// * `amm_config` - Trade / protocol fee rates
+pub fn deposit_imbalanced(
+    amount_x: u64,
+    amount_y: u64,
+    total_lp_supply: u64,
+    pool_balances: &PoolBalances,
+    amm_config: &AmmConfig,
+) -> Result<ImbalancedDeposit> {
+    if total_lp_supply == 0 {
+        return Err(MathError::ZeroLpSupply);
+    }
+
+    let (available_x, available_y) = available_reserves(pool_balances)?;
+    let (reserve_x, reserve_y) = (available_x as u128, available_y as u128);
+    let (new_x, new_y) = (reserve_x + amount_x as u128, reserve_y + amount_y as u128);
+
+    let invariant = sqrt_ceil(reserve_x * reserve_y);
+    let new_invariant = sqrt(new_x.checked_mul(new_y).ok_or(MathError::MathOverflow)?);
+
+    // adding more X than the pool ratio is swapping it for Y
+    let fee_rates = amm_config.fee_rates(excess_direction(amount_x, amount_y, available_x, available_y));
+    let (imbalance_fee_x, protocol_fee_x) = imbalance_fees(reserve_x, new_x, invariant, new_invariant, fee_rates)?;
+    let (imbalance_fee_y, protocol_fee_y) = imbalance_fees(reserve_y, new_y, invariant, new_invariant, fee_rates)?;
+
+    // a lopsided enough deposit owes more fee on the side it did not add to
+    // than the pool holds
+    let invariant_after_fees = sqrt(
+        new_x
+            .checked_sub(imbalance_fee_x)
+            .ok_or(MathError::TradeTooBig)?
+            * new_y
+                .checked_sub(imbalance_fee_y)
+                .ok_or(MathError::TradeTooBig)?,
+    );
+
+    let lp_minted = floor_div(
+        total_lp_supply as u128,
+        invariant_after_fees.saturating_sub(invariant),
+        invariant,
+    )
+    .ok_or(MathError::MathOverflow)?;
+    if lp_minted == 0 {
+        return Err(MathError::InputAmountTooSmall);
+    }
+
+    Ok(ImbalancedDeposit {
+        amount_x,
+        amount_y,
+        lp_minted: u64::try_from(lp_minted).map_err(|_| MathError::MathOverflow)?,
+        imbalance_fee_x: imbalance_fee_x as u64,
+        imbalance_fee_y: imbalance_fee_y as u64,
+        protocol_fee_x: protocol_fee_x as u64,
+        protocol_fee_y: protocol_fee_y as u64,
+    })
+}
+
+/// Calculate the LP tokens to mint for a deposit, and the excess to refund
+///
+/// Mints the same as [`deposit_lp`], but instead of donating the excess of the
+/// larger side, returns it as `unused_x` / `unused_y` so it can be refunded.
+///
+/// # Arguments
+/// * `amount_x` - Amount of X offered
+/// * `amount_y` - Amount of Y offered
+/// * `total_lp_supply` - Current total supply of LP tokens
+/// * `pool_balances` - Current balances of the pool, fees and locks are excluded
+pub fn deposit_lp_strict(
+    amount_x: u64,
+    amount_y: u64,
+    total_lp_supply: u64,
+    pool_balances: &PoolBalances,
+) -> Result<DepositQuote> {
+    if amount_x == 0 || amount_y == 0 {
+        return Err(MathError::InputAmountTooSmall);
+    }
+    if total_lp_supply == 0 {
+        return Err(MathError::ZeroLpSupply);
+    }
+
+    let (available_x, available_y) = available_reserves(pool_balances)?;
+    let deposit = proportional_deposit(amount_x, amount_y, total_lp_supply, available_x, available_y)?;
+
+    Ok(DepositQuote {
+        amount_x,
+        amount_y,
+        lp_minted: deposit.lp_minted,
+        unused_x: deposit.unused_amount,
+        unused_y: deposit.unused_counterpart_amount,
+    })
+}
+
+/// Calculate the Y amount to pair with `amount_x` in a ratio-preserving deposit
+///
+/// The Y amount is rounded up so the deposit never dilutes the pool.
+///
+/// # Arguments
+/// * `amount_x` - Amount of X the user wants to deposit
+/// * `total_lp_supply` - Current total supply of LP tokens
+/// * `pool_balances` - Current balances of the pool, fees and locks are excluded
+pub fn required_y_for_x(
+    amount_x: u64,
+    total_lp_supply: u64,
+    pool_balances: &PoolBalances,
+) -> Result<DepositQuote> {
+    let (available_x, available_y) = available_reserves(pool_balances)?;
+    let deposit = required_counterpart(amount_x, total_lp_supply, available_x, available_y)?;
+
+    Ok(DepositQuote {
+        amount_x,
+        amount_y: deposit.counterpart_amount,
+        lp_minted: deposit.lp_minted,
+        unused_x: deposit.unused_amount,
+        unused_y: deposit.unused_counterpart_amount,
+    })
+}
+
+/// Calculate the X amount to pair with `amount_y` in a ratio-preserving deposit
+///
+/// See [`required_y_for_x`].
+pub fn required_x_for_y(
+    amount_y: u64,
+    total_lp_supply: u64,
+    pool_balances: &PoolBalances,
+) -> Result<DepositQuote> {
+    let (available_x, available_y) = available_reserves(pool_balances)?;
+    let deposit = required_counterpart(amount_y, total_lp_supply, available_y, available_x)?;
+
+    Ok(DepositQuote {
+        amount_x: deposit.counterpart_amount,
+        amount_y,
+        lp_minted: deposit.lp_minted,
+        unused_x: deposit.unused_counterpart_amount,
+        unused_y: deposit.unused_amount,
+    })
+}
+
+fn available_reserves(pool_balances: &PoolBalances) -> Result<(u64, u64)> {
+    let (available_x, available_y) = pool_balances
+        .available()
+        .ok_or(MathError::MathOverflow)?;
+
+    // an empty side has no ratio to follow
+    if available_x == 0 {
+        return Err(MathError::InsufficientPoolTokenXBalance);
+    }
+    if available_y == 0 {
+        return Err(MathError::InsufficientPoolTokenYBalance);
+    }
+    Ok((available_x, available_y))
+}
+
+/// `XToY` if `amount_x` is a larger share of `reserve_x` than `amount_y` is of
+/// `reserve_y`, the direction of the swap hidden in an imbalanced deposit
+fn excess_direction(amount_x: u64, amount_y: u64, reserve_x: u64, reserve_y: u64) -> SwapDirection {
+    if amount_x as u128 * reserve_y as u128 > amount_y as u128 * reserve_x as u128 {
+        SwapDirection::XToY
+    } else {
+        SwapDirection::YToX
+    }
+}
+
+struct CounterpartDeposit {
+    counterpart_amount: u64,
+    lp_minted: u64,
+    unused_amount: u64,
+    unused_counterpart_amount: u64,
+}
+
+fn required_counterpart(
+    amount: u64,
+    total_lp_supply: u64,
+    reserve: u64,
+    counterpart_reserve: u64,
+) -> Result<CounterpartDeposit> {
+    if amount == 0 {
+        return Err(MathError::InputAmountTooSmall);
+    }
+    if total_lp_supply == 0 {
+        return Err(MathError::ZeroLpSupply);
+    }
+
+    let counterpart_amount = ceil_div(amount as u128, counterpart_reserve as u128, reserve as u128)
+        .and_then(|counterpart_amount| u64::try_from(counterpart_amount).ok())
+        .ok_or(MathError::MathOverflow)?;
+    let deposit = proportional_deposit(amount, counterpart_amount, total_lp_supply, reserve, counterpart_reserve)?;
+
+    Ok(CounterpartDeposit {
+        counterpart_amount,
+        lp_minted: deposit.lp_minted,
+        unused_amount: deposit.unused_amount,
+        unused_counterpart_amount: deposit.unused_counterpart_amount,
+    })
+}
+
+struct ProportionalDeposit {
+    lp_minted: u64,
+    unused_amount: u64,
+    unused_counterpart_amount: u64,
+}
+
+fn proportional_deposit(
+    amount: u64,
+    counterpart_amount: u64,
+    total_lp_supply: u64,
+    reserve: u64,
+    counterpart_reserve: u64,
+) -> Result<ProportionalDeposit> {
+    let (amount, counterpart_amount) = (amount as u128, counterpart_amount as u128);
+    let (total_lp_supply, reserve, counterpart_reserve) =
+        (total_lp_supply as u128, reserve as u128, counterpart_reserve as u128);
+
+    // same as deposit_lp, without its u64 intermediate products
+    let lp_minted = floor_div(amount, total_lp_supply, reserve)
+        .ok_or(MathError::MathOverflow)?
+        .min(
+            floor_div(counterpart_amount, total_lp_supply, counterpart_reserve)
+                .ok_or(MathError::MathOverflow)?,
+        );
+
+    // what the minted LP tokens are worth, rounded in the pool's favour
+    let used_amount = ceil_div(lp_minted, reserve, total_lp_supply)
+        .ok_or(MathError::MathOverflow)?;
+    let used_counterpart_amount = ceil_div(lp_minted, counterpart_reserve, total_lp_supply)
+        .ok_or(MathError::MathOverflow)?;
+
+    let to_u64 = |amount: u128| u64::try_from(amount).map_err(|_| MathError::MathOverflow);
+
+    Ok(ProportionalDeposit {
+        lp_minted: to_u64(lp_minted)?,
+        unused_amount: to_u64(amount.saturating_sub(used_amount))?,
+        unused_counterpart_amount: to_u64(counterpart_amount.saturating_sub(used_counterpart_amount))?,
+    })
+}
+
+/// Trade fee on the distance between `balance` and the balance a proportional
+/// change of the invariant would give, with the protocol's share of it
+fn imbalance_fees(
+    reserve: u128,
+    balance: u128,
+    invariant: u128,
+    new_invariant: u128,
+    fee_rates: FeeRates,
+) -> Result<(u128, u128)> {
+    let ideal = floor_div(reserve, new_invariant, invariant).ok_or(MathError::MathOverflow)?;
+    let fee = fee_rates
+        .trade_fee_rate
+        .apply_ceil(ideal.abs_diff(balance))
+        .ok_or(MathError::MathOverflow)?;
+    let protocol_fee = fee_rates
+        .protocol_fee_rate
+        .apply_floor(fee)
+        .ok_or(MathError::MathOverflow)?;
+    Ok((fee, protocol_fee))
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::utils::widening_mul, proptest::prelude::*};
+
+    fn pool(reserve_x_balance: u64, reserve_y_balance: u64) -> PoolBalances {
+        PoolBalances {
+            reserve_x_balance,
+            reserve_y_balance,
+            ..PoolBalances::default()
+        }
+    }
+
+    #[test]
+    fn required_amounts_follow_the_available_ratio() {
+        let quote = required_y_for_x(1_000, 1_000_000, &pool(1_000_000, 3_000_000)).unwrap();
+        assert_eq!((quote.amount_x, quote.amount_y, quote.lp_minted), (1_000, 3_000, 1_000));
+        assert_eq!((quote.unused_x, quote.unused_y), (0, 0));
+
+        // 1 Y at 3 Y per X needs a third of an X, rounded up
+        let quote = required_x_for_y(1, 1_000_000, &pool(1_000_000, 3_000_000)).unwrap();
+        assert_eq!((quote.amount_x, quote.amount_y), (1, 1));
+
+        // fees and locks are not part of the ratio
+        let pool_balances = PoolBalances {
+            protocol_fee_x: 100_000,
+            locked_y: 1_000_000,
+            ..pool(1_100_000, 4_000_000)
+        };
+        let quote = required_y_for_x(1_000, 1_000_000, &pool_balances).unwrap();
+        assert_eq!(quote.amount_y, 3_000);
+    }
+
+    #[test]
+    fn checked_lp_math_rejects_what_the_raw_math_panics_on() {
+        assert_eq!(try_deposit_lp(1_000, 3_000, 1_000_000, 1_000_000, 2_000_000), Ok(1_000));
+        assert_eq!(try_deposit_lp(1_000, 4_000, 0, 0, 0), Ok(2_000));
+        assert_eq!(try_deposit_lp(1, 1, 1_000, 0, 1_000), Err(MathError::MathOverflow));
+        assert_eq!(try_deposit_lp(u64::MAX, 1, 2, 1, 1), Err(MathError::MathOverflow));
+
+        assert_eq!(try_withdraw_lp(1_000, 1_000_000, 1_000_000, 2_000_000), Ok((1_000, 2_000)));
+        assert_eq!(try_withdraw_lp(1_000, 0, 1_000_000, 2_000_000), Ok((0, 0)));
+        assert_eq!(try_withdraw_lp(u64::MAX, 1_000, 2, 2), Err(MathError::MathOverflow));
+    }
+
+    #[test]
+    fn withdrawal_pays_lp_funds_only() {
+        let pool_balances = PoolBalances {
+            reserve_x_balance: 1_600,
+            reserve_y_balance: 3_000,
+            protocol_fee_x: 100,
+            user_locked_x: 200,
+            locked_y: 500,
+            lp_fee_x: 300,
+            lp_fee_y: 500,
+            ..PoolBalances::default()
+        };
+
+        // available 1_000 X / 2_000 Y, a quarter of the LP supply
+        let withdrawal = withdraw_lp_from_pool(250, 1_000, &pool_balances).unwrap();
+        assert_eq!((withdrawal.amount_x, withdrawal.amount_y), (250 + 75, 500 + 125));
+        assert_eq!((withdrawal.lp_fee_x, withdrawal.lp_fee_y), (75, 125));
+
+        let remaining = withdrawal.pool_balances;
+        assert_eq!(remaining.available(), Some((750, 1_500)));
+        assert_eq!((remaining.protocol_fee_x, remaining.user_locked_x, remaining.locked_y), (100, 200, 500));
+        assert_eq!((remaining.lp_fee_x, remaining.lp_fee_y), (225, 375));
+
+        assert_eq!(withdraw_lp_from_pool(1_001, 1_000, &pool_balances), Err(MathError::InsufficientLpSupply));
+
+        // pending orders need liquidity left in the pool
+        assert_eq!(
+            withdraw_lp_from_pool(1_000, 1_000, &pool_balances),
+            Err(MathError::InsufficientLiquidityForOrders)
+        );
+        let settled = PoolBalances {
+            user_locked_x: 0,
+            locked_y: 0,
+            reserve_x_balance: 1_400,
+            reserve_y_balance: 2_500,
+            ..pool_balances
+        };
+        let withdrawal = withdraw_lp_from_pool(1_000, 1_000, &settled).unwrap();
+        assert_eq!((withdrawal.amount_x, withdrawal.amount_y), (1_300, 2_500));
+    }
+
+    #[test]
+    fn withdrawal_keeps_pending_orders_settleable() {
+        // an X -> Y order of 5_000 with 3_000 Y locked for it
+        let pool_balances = PoolBalances {
+            reserve_x_balance: 15_000,
+            reserve_y_balance: 13_000,
+            user_locked_x: 5_000,
+            locked_y: 3_000,
+            ..PoolBalances::default()
+        };
+        assert_eq!(pool_balances.available(), Some((10_000, 10_000)));
+
+        let withdrawal = withdraw_lp_from_pool(980, 1_000, &pool_balances).unwrap();
+        assert_eq!(withdrawal.pool_balances.available(), Some((200, 200)));
+
+        // leaves MIN_LIQUIDITY of both tokens available, but 100 X / 3_100 Y
+        // pay the order 3_039 Y and keep only 61
+        assert_eq!(
+            withdraw_lp_from_pool(990, 1_000, &pool_balances),
+            Err(MathError::InsufficientLiquidityForOrders)
+        );
+    }
+
+    fn amm_config(ratio_change_tolerance_rate: u64) -> AmmConfig {
+        AmmConfig::builder()
+            .trade_fee_rate(Ppm::new(3_000).unwrap())
+            .protocol_fee_rate(Ppm::new(200_000).unwrap())
+            .ratio_change_tolerance_rate(Ppm::new(ratio_change_tolerance_rate).unwrap())
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn exact_withdrawal_charges_the_imbalance() {
+        let pool_balances = pool(1_000_000, 4_000_000);
+        let amm_config = amm_config(Ppm::MAX.get());
+
+        // proportional, only rounding is charged
+        let proportional = withdraw_exact_amounts(10_000, 40_000, 1_000_000, &pool_balances, &amm_config).unwrap();
+        assert_eq!(proportional.lp_tokens_burned, 10_000);
+        assert_eq!((proportional.imbalance_fee_x, proportional.imbalance_fee_y), (0, 0));
+
+        // X only, roughly half of it is swapped from Y
+        let one_sided = withdraw_exact_amounts(10_000, 0, 1_000_000, &pool_balances, &amm_config).unwrap();
+        assert_eq!((one_sided.imbalance_fee_x, one_sided.imbalance_fee_y), (15, 61));
+        assert_eq!((one_sided.protocol_fee_x, one_sided.protocol_fee_y), (3, 12));
+        assert!(one_sided.lp_tokens_burned > 5_000);
+
+        // a balanced withdrawal of the same value burns less
+        assert!(withdraw_exact_amounts(5_000, 20_000, 1_000_000, &pool_balances, &amm_config)
+            .unwrap()
+            .lp_tokens_burned
+            < one_sided.lp_tokens_burned);
+    }
+
+    #[test]
+    fn exact_withdrawal_values_the_lp_fees() {
+        let pool_balances = PoolBalances {
+            lp_fee_x: 10_000,
+            lp_fee_y: 40_000,
+            ..pool(1_010_000, 4_040_000)
+        };
+        let amm_config = amm_config(Ppm::MAX.get());
+
+        // the same amounts withdraw_lp_from_pool pays for 10_000 LP tokens,
+        // lp fees included, burn no more
+        let from_pool = withdraw_lp_from_pool(10_000, 1_000_000, &pool_balances).unwrap();
+        assert_eq!((from_pool.amount_x, from_pool.amount_y), (10_100, 40_400));
+        let exact =
+            withdraw_exact_amounts(from_pool.amount_x, from_pool.amount_y, 1_000_000, &pool_balances, &amm_config)
+                .unwrap();
+        assert_eq!(exact.lp_tokens_burned, 10_000);
+        assert_eq!((exact.imbalance_fee_x, exact.imbalance_fee_y), (0, 0));
+    }
+
+    #[test]
+    fn exact_withdrawal_respects_ratio_tolerance() {
+        let pool_balances = pool(1_000_000, 4_000_000);
+
+        // 1% X out moves the ratio by about 1%
+        assert!(withdraw_exact_amounts(10_000, 0, 1_000_000, &pool_balances, &amm_config(20_000)).is_ok());
+        assert!(withdraw_exact_amounts(10_000, 0, 1_000_000, &pool_balances, &amm_config(5_000)).is_err());
+        assert!(withdraw_exact_amounts(1_000_000, 0, 1_000_000, &pool_balances, &amm_config(Ppm::MAX.get())).is_err());
+    }
+
+    #[test]
+    fn imbalanced_deposit_charges_the_implicit_swap() {
+        let pool_balances = pool(1_000_000, 4_000_000);
+        let amm_config = amm_config(Ppm::MAX.get());
+
+        // proportional, same as deposit_lp
+        let proportional = deposit_imbalanced(10_000, 40_000, 1_000_000, &pool_balances, &amm_config).unwrap();
+        assert_eq!(proportional.lp_minted, deposit_lp(10_000, 40_000, 1_000_000, 1_000_000, 4_000_000));
+        assert_eq!((proportional.imbalance_fee_x, proportional.imbalance_fee_y), (0, 0));
+
+        // X only mints about half as much, minus the fee on the swapped half
+        let one_sided = deposit_imbalanced(10_000, 0, 1_000_000, &pool_balances, &amm_config).unwrap();
+        assert_eq!(deposit_lp(10_000, 0, 1_000_000, 1_000_000, 4_000_000), 0);
+        assert!(one_sided.lp_minted > 4_900 && one_sided.lp_minted < 4_988);
+        assert!(one_sided.imbalance_fee_x > 0 && one_sided.imbalance_fee_y > 0);
+    }
+
+    #[test]
+    fn imbalance_fee_follows_the_implicit_swap_direction() {
+        let pool_balances = pool(1_000_000, 4_000_000);
+        let amm_config = AmmConfig::builder()
+            .ratio_change_tolerance_rate(Ppm::MAX)
+            .direction_fee_rates(SwapDirection::YToX, Ppm::new(3_000).unwrap(), Ppm::ZERO)
+            .build()
+            .unwrap();
+
+        // withdrawing only X swaps Y for X, withdrawing only Y is free
+        let x_only = withdraw_exact_amounts(10_000, 0, 1_000_000, &pool_balances, &amm_config).unwrap();
+        assert_eq!((x_only.imbalance_fee_x, x_only.imbalance_fee_y), (15, 61));
+        let y_only = withdraw_exact_amounts(0, 40_000, 1_000_000, &pool_balances, &amm_config).unwrap();
+        assert_eq!((y_only.imbalance_fee_x, y_only.imbalance_fee_y), (0, 0));
+
+        // depositing only Y swaps it for X, depositing only X is free
+        let y_only = deposit_imbalanced(0, 40_000, 1_000_000, &pool_balances, &amm_config).unwrap();
+        assert!(y_only.imbalance_fee_x > 0 && y_only.imbalance_fee_y > 0);
+        let x_only = deposit_imbalanced(10_000, 0, 1_000_000, &pool_balances, &amm_config).unwrap();
+        assert_eq!((x_only.imbalance_fee_x, x_only.imbalance_fee_y), (0, 0));
+    }
+
+    #[test]
+    fn strict_deposit_returns_the_excess() {
+        let quote = deposit_lp_strict(10_000, 50_000, 1_000_000, &pool(1_000_000, 4_000_000)).unwrap();
+        assert_eq!(quote.lp_minted, 10_000);
+        assert_eq!((quote.unused_x, quote.unused_y), (0, 10_000));
+
+        assert!(deposit_lp_strict(10_000, 0, 1_000_000, &pool(1_000_000, 4_000_000)).is_err());
+    }
+
+    #[test]
+    fn protocol_fee_lp_matches_uniswap_v2() {
+        let protocol_share = Ppm::new(1_000_000 / 6).unwrap();
+
+        // k grew from 1_000_000^2 to 1_010_000^2 with 1_000_000 LP out
+        let k_last = 1_000_000u128 * 1_000_000;
+        let protocol_fee_lp =
+            mint_protocol_fee_lp(k_last, 1_010_000, 1_010_000, 1_000_000, protocol_share).unwrap();
+        // v2 with 1/6: 1_000_000 * 10_000 / (1_010_000 * 5 + 1_000_000) = 1652
+        assert_eq!(protocol_fee_lp, 1_652);
+
+        assert_eq!(mint_protocol_fee_lp(0, 1_010_000, 1_010_000, 1_000_000, protocol_share).unwrap(), 0);
+        assert_eq!(mint_protocol_fee_lp(k_last, 900_000, 900_000, 1_000_000, protocol_share).unwrap(), 0);
+
+        // the fee is minted before the user's share
+        let withdrawal =
+            withdraw_lp_with_protocol_fee(1_000_000, 1_000_000, &pool(1_010_000, 1_010_000), k_last, protocol_share).unwrap();
+        assert_eq!(withdrawal.protocol_fee_lp, 1_652);
+        assert!(withdrawal.amount_x < withdraw_lp(1_000_000, 1_000_000, 1_010_000, 1_010_000).0);
+        assert_eq!(withdrawal.k_last, (1_010_000 - withdrawal.amount_x) as u128 * (1_010_000 - withdrawal.amount_y) as u128);
+
+        let deposit =
+            deposit_lp_with_protocol_fee(10_100, 10_100, 1_000_000, &pool(1_010_000, 1_010_000), k_last, protocol_share).unwrap();
+        assert_eq!(deposit.protocol_fee_lp, 1_652);
+        assert_eq!(deposit.lp_minted, 10_016);
+        assert_eq!(deposit.k_last, 1_020_100u128 * 1_020_100);
+
+        // both mint the integer sqrt on the first deposit, past f64 precision too
+        let amount = (1 << 53) + 1;
+        let first_deposit = deposit_lp_with_protocol_fee(amount, amount, 0, &pool(0, 0), 0, protocol_share).unwrap();
+        assert_eq!(first_deposit.lp_minted, amount);
+        assert_eq!(deposit_lp(amount, amount, 0, 0, 0), amount);
+    }
+
+    #[test]
+    fn protocol_fee_withdrawal_pays_the_lp_fees() {
+        let protocol_share = Ppm::new(1_000_000 / 6).unwrap();
+        let k_last = 1_000_000u128 * 1_000_000;
+
+        // the 10_000 growth sits in the LP fee buckets, not in the reserves
+        let pool_balances = PoolBalances {
+            lp_fee_x: 10_000,
+            lp_fee_y: 10_000,
+            ..pool(1_010_000, 1_010_000)
+        };
+        assert_eq!(pool_balances.lp_owned(), Some((1_010_000, 1_010_000)));
+
+        let withdrawal =
+            withdraw_lp_with_protocol_fee(100_000, 1_000_000, &pool_balances, k_last, protocol_share).unwrap();
+        assert_eq!(withdrawal.protocol_fee_lp, 1_652);
+        let from_pool = withdraw_lp_from_pool(100_000, 1_001_652, &pool_balances).unwrap();
+        assert_eq!((withdrawal.amount_x, withdrawal.amount_y), (from_pool.amount_x, from_pool.amount_y));
+        assert_eq!((withdrawal.lp_fee_x, withdrawal.lp_fee_y), (from_pool.lp_fee_x, from_pool.lp_fee_y));
+        assert!(withdrawal.lp_fee_x > 0);
+        assert_eq!(
+            Some(withdrawal.k_last),
+            from_pool.pool_balances.lp_owned().map(|(x, y)| x as u128 * y as u128)
+        );
+
+        let deposit =
+            deposit_lp_with_protocol_fee(10_100, 20_000, 1_000_000, &pool_balances, k_last, protocol_share).unwrap();
+        let strict = deposit_lp_strict(10_100, 20_000, 1_001_652, &pool_balances).unwrap();
+        assert_eq!((deposit.lp_minted, deposit.unused_x, deposit.unused_y), (strict.lp_minted, strict.unused_x, strict.unused_y));
+        assert!(deposit.unused_y > 0);
+    }
+
+    #[test]
+    fn rejects_empty_pools() {
+        assert!(required_y_for_x(1_000, 0, &pool(1_000, 1_000)).is_err());
+        assert!(required_y_for_x(1_000, 1_000, &pool(0, 1_000)).is_err());
+        assert!(required_x_for_y(0, 1_000, &pool(1_000, 1_000)).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn withdrawal_never_touches_locks_or_protocol_fees(
+            lp_tokens in 1..u32::MAX as u64,
+            total_lp_supply in 1..u32::MAX as u64,
+            balances in prop::array::uniform8(0..u32::MAX as u64),
+        ) {
+            prop_assume!(lp_tokens <= total_lp_supply);
+            let [available_x, available_y, protocol_fee_x, user_locked_y, locked_x, locked_y, lp_fee_x, lp_fee_y] = balances;
+            let pool_balances = PoolBalances {
+                reserve_x_balance: available_x + protocol_fee_x + locked_x + lp_fee_x,
+                reserve_y_balance: available_y + user_locked_y + locked_y + lp_fee_y,
+                protocol_fee_x,
+                user_locked_y,
+                locked_x,
+                locked_y,
+                lp_fee_x,
+                lp_fee_y,
+                ..PoolBalances::default()
+            };
+
+            if let Ok(withdrawal) = withdraw_lp_from_pool(lp_tokens, total_lp_supply, &pool_balances) {
+                let remaining = withdrawal.pool_balances;
+                prop_assert_eq!(remaining.protocol_fee_x, protocol_fee_x);
+                prop_assert_eq!((remaining.locked_x, remaining.locked_y), (locked_x, locked_y));
+                prop_assert_eq!(remaining.user_locked_y, user_locked_y);
+
+                // LP value per token never decreases
+                let (remaining_x, remaining_y) = remaining.available().unwrap();
+                let remaining_supply = (total_lp_supply - lp_tokens) as u128;
+                prop_assert!((remaining_x as u128 + remaining.lp_fee_x as u128) * total_lp_supply as u128
+                    >= (available_x as u128 + lp_fee_x as u128) * remaining_supply);
+                prop_assert!((remaining_y as u128 + remaining.lp_fee_y as u128) * total_lp_supply as u128
+                    >= (available_y as u128 + lp_fee_y as u128) * remaining_supply);
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn exact_withdrawal_never_dilutes_the_pool(
+            amount_x in 0..u32::MAX as u64,
+            amount_y in 0..u32::MAX as u64,
+            reserve_x in 1..u32::MAX as u64,
+            reserve_y in 1..u32::MAX as u64,
+            total_lp_supply in 1..i32::MAX as u64,
+        ) {
+            let pool_balances = pool(reserve_x, reserve_y);
+            if let Ok(withdrawal) = withdraw_exact_amounts(
+                amount_x,
+                amount_y,
+                total_lp_supply,
+                &pool_balances,
+                &amm_config(Ppm::MAX.get()),
+            ) {
+                prop_assert!(withdrawal.lp_tokens_burned <= total_lp_supply);
+
+                // invariant per LP token never decreases
+                let remaining_supply = (total_lp_supply - withdrawal.lp_tokens_burned) as u128;
+                let remaining_value = (reserve_x - amount_x) as u128 * (reserve_y - amount_y) as u128;
+                prop_assert!(
+                    remaining_value * (total_lp_supply as u128).pow(2)
+                        >= reserve_x as u128 * reserve_y as u128 * remaining_supply.pow(2)
+                );
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn imbalanced_deposit_never_dilutes_the_pool(
+            amount_x in 0..u32::MAX as u64,
+            amount_y in 0..u32::MAX as u64,
+            reserve_x in 1..u32::MAX as u64,
+            reserve_y in 1..u32::MAX as u64,
+            total_lp_supply in 1..i32::MAX as u64,
+        ) {
+            let pool_balances = pool(reserve_x, reserve_y);
+            if let Ok(deposit) = deposit_imbalanced(
+                amount_x,
+                amount_y,
+                total_lp_supply,
+                &pool_balances,
+                &amm_config(Ppm::MAX.get()),
+            ) {
+                // invariant per LP token never decreases
+                let new_supply = (total_lp_supply + deposit.lp_minted) as u128;
+                let new_value = (reserve_x + amount_x) as u128 * (reserve_y + amount_y) as u128;
+                prop_assert!(
+                    new_value * (total_lp_supply as u128).pow(2)
+                        >= reserve_x as u128 * reserve_y as u128 * new_supply.pow(2)
+                );
+            }
+        }
+
+        #[test]
+        fn strict_deposit_refund_is_never_needed_for_the_lp(
+            amount_x in 1..u32::MAX as u64,
+            amount_y in 1..u32::MAX as u64,
+            reserve_x in 1..u32::MAX as u64,
+            reserve_y in 1..u32::MAX as u64,
+            total_lp_supply in 1..u32::MAX as u64,
+        ) {
+            let quote = deposit_lp_strict(amount_x, amount_y, total_lp_supply, &pool(reserve_x, reserve_y)).unwrap();
+            let (used_x, used_y) = (
+                (amount_x - quote.unused_x) as u128,
+                (amount_y - quote.unused_y) as u128,
+            );
+            // what is kept covers the minted LP on both sides
+            prop_assert!(used_x * total_lp_supply as u128 >= quote.lp_minted as u128 * reserve_x as u128);
+            prop_assert!(used_y * total_lp_supply as u128 >= quote.lp_minted as u128 * reserve_y as u128);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn protocol_fee_lp_never_exceeds_its_share(
+            reserve_x in 1..u64::MAX,
+            reserve_y in 1..u64::MAX,
+            k_growth in 1..=4u128,
+            total_lp_supply in 1..u64::MAX,
+            protocol_share in 0..=1_000_000u64,
+        ) {
+            // sqrt(k) at most doubled, so the protocol mints less than the supply
+            let k_last = (reserve_x as u128 * reserve_y as u128 / k_growth).max(1);
+            let protocol_share = Ppm::new(protocol_share).unwrap();
+            let protocol_fee_lp =
+                mint_protocol_fee_lp(k_last, reserve_x, reserve_y, total_lp_supply, protocol_share).unwrap();
+
+            // protocol_fee_lp / (supply + protocol_fee_lp) * sqrt(k) <= share of the sqrt(k) growth,
+            // exact since flooring only lowers protocol_fee_lp
+            let root_k = sqrt(reserve_x as u128 * reserve_y as u128);
+            let growth = root_k.saturating_sub(sqrt(k_last));
+            prop_assert!(
+                widening_mul(protocol_fee_lp as u128 * root_k, MAX_PERCENTAGE as u128)
+                    <= widening_mul(growth * protocol_share.get() as u128, total_lp_supply as u128 + protocol_fee_lp as u128)
+            );
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn required_deposit_never_dilutes_the_pool(
+            // small enough for deposit_lp's u64 products
+            amount_x in 1..u16::MAX as u64,
+            total_lp_supply in 1..u16::MAX as u64,
+            reserve_x in 1..1u64 << 24,
+            reserve_y in 1..1u64 << 24,
+        ) {
+            let quote = required_y_for_x(amount_x, total_lp_supply, &pool(reserve_x, reserve_y)).unwrap();
+
+            // at least the pool ratio on both sides
+            prop_assert!(quote.amount_y as u128 * reserve_x as u128 >= amount_x as u128 * reserve_y as u128);
+
+            // and no more LP than deposit_lp would mint
+            prop_assert_eq!(
+                quote.lp_minted,
+                deposit_lp(quote.amount_x, quote.amount_y, total_lp_supply, reserve_x, reserve_y)
+            );
+        }
+    }
+}
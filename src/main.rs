@@ -1,29 +1,641 @@
-use dex_math::{quote, deposit_lp, withdraw_lp};
-
-fn main() {
-    // Example usage of the DEX math functions
-    
-    // // Example 1: Quote a swap
-    // let input_amount = 100;
-    // let input_reserve = 1000;
-    // let output_reserve = 2000;
-    // let output_amount = quote(input_amount, input_reserve, output_reserve);
-    // println!("Quote: {} tokens in -> {} tokens out", input_amount, output_amount);
-    
-    // // Example 2: Deposit liquidity
-    // let token_a_amount = 1000;
-    // let token_b_amount = 2000;
-    // let total_lp_supply = 0; // Initial deposit
-    // let token_a_reserve = 0;
-    // let token_b_reserve = 0;
-    // let lp_tokens_minted = deposit_lp(token_a_amount, token_b_amount, total_lp_supply, token_a_reserve, token_b_reserve);
-    // println!("Deposit: {} LP tokens minted for {} token A and {} token B", lp_tokens_minted, token_a_amount, token_b_amount);
-    
-    // // Example 3: Withdraw liquidity
-    // let lp_tokens_to_burn = 100;
-    // let current_lp_supply = 1000;
-    // let current_token_a_reserve = 1000;
-    // let current_token_b_reserve = 2000;
-    // let (token_a_returned, token_b_returned) = withdraw_lp(lp_tokens_to_burn, current_lp_supply, current_token_a_reserve, current_token_b_reserve);
-    // println!("Withdraw: {} LP tokens -> {} token A and {} token B", lp_tokens_to_burn, token_a_returned, token_b_returned);
+//! Command line front end for quoting and pool simulation
+//!
+//! Pool state and `AmmConfig` come from `--pool <file.json>` and/or flags,
+//! flags win over the file. Rates are in ppm. Results print as a table, or as
+//! JSON with `--json`.
+//!
+//! ```text
+//! dex-math --reserve-x 1000000 --reserve-y 2000000 --trade-fee-rate 3000 quote 10000
+//! dex-math --pool pool.json --json max-trade --direction y-to-x
+//! dex-math --pool pool.json --json monte-carlo --paths 500 --volatility 1.2
+//! ```
+
+use std::{fs, path::PathBuf, process::ExitCode};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use dex_math::{
+    backtest::{backtest, parse_trades_csv, Trade},
+    monte_carlo::{simulate, MonteCarloParams},
+    deposit_imbalanced, deposit_lp_strict, deposit_lp_with_protocol_fee, max_exchange_in, quote_exact_out,
+    quote_pool_balances, quote_pool_balances_at, rebalance_pool_ratio, withdraw_lp_from_pool,
+    withdraw_lp_with_protocol_fee, AmmConfig, FeeSide, HostFeeSource, MathError, PoolBalances, Ppm,
+};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+#[derive(Parser, Debug)]
+#[command(name = "dex-math", version, about = "Darklake DEX math")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+
+    #[command(flatten)]
+    pool: PoolArgs,
+
+    /// Print the result as JSON instead of a table
+    #[arg(long, global = true)]
+    json: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Quote the output for an exact input
+    Quote {
+        exchange_in: u64,
+        #[arg(long, value_enum, default_value_t = Direction::XToY)]
+        direction: Direction,
+        /// Slot / timestamp to apply the launch fee schedule at
+        #[arg(long)]
+        now: Option<u64>,
+    },
+    /// Quote the input needed for an exact output
+    QuoteExactOut {
+        amount_out: u64,
+        #[arg(long, value_enum, default_value_t = Direction::XToY)]
+        direction: Direction,
+    },
+    /// LP tokens minted for a deposit
+    Deposit {
+        amount_x: u64,
+        amount_y: u64,
+        #[arg(long)]
+        lp_supply: Option<u64>,
+        /// Mint for the full value, charging the trade fee on the imbalance,
+        /// instead of refunding the excess
+        #[arg(long)]
+        imbalanced: bool,
+        #[command(flatten)]
+        protocol_fee: ProtocolFeeArgs,
+    },
+    /// Tokens returned for burning LP tokens
+    Withdraw {
+        lp_tokens: u64,
+        #[arg(long)]
+        lp_supply: Option<u64>,
+        #[command(flatten)]
+        protocol_fee: ProtocolFeeArgs,
+    },
+    /// Largest input accepted by `quote`
+    MaxTrade {
+        #[arg(long, value_enum, default_value_t = Direction::XToY)]
+        direction: Direction,
+    },
+    /// Amount to lock to restore the pool ratio after a swap
+    Rebalance {
+        to_amount_swapped: u64,
+        #[arg(long, value_enum, default_value_t = Direction::XToY)]
+        direction: Direction,
+    },
+    /// Replay a trade log from the pool's reserves under one or more configs
+    Backtest {
+        /// CSV with `timestamp,direction,amount` columns, or a JSON array of
+        /// trades if the file ends in `.json`
+        trades: PathBuf,
+        /// JSON array of `AmmConfig`s, defaults to the pool's config
+        #[arg(long)]
+        configs: Option<PathBuf>,
+    },
+    /// Distribution of LP returns vs HODL under simulated GBM prices, starting
+    /// from the pool's reserves
+    MonteCarlo {
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        #[arg(long, default_value_t = 1_000)]
+        paths: u32,
+        #[arg(long, default_value_t = 365)]
+        steps: u32,
+        /// Step length in years
+        #[arg(long, default_value_t = 1.0 / 365.0)]
+        dt: f64,
+        /// Annualised drift
+        #[arg(long, default_value_t = 0.0)]
+        drift: f64,
+        /// Annualised volatility
+        #[arg(long, default_value_t = 0.8)]
+        volatility: f64,
+        #[arg(long, default_value_t = 1)]
+        noise_trades_per_step: u32,
+        /// Largest noise trade as a fraction of the source reserve
+        #[arg(long, default_value_t = 0.001)]
+        noise_trade_size: f64,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Direction {
+    XToY,
+    YToX,
+}
+
+impl Direction {
+    fn is_x_to_y(self) -> bool {
+        matches!(self, Direction::XToY)
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum FeeSideArg {
+    Input,
+    Output,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum HostFeeSourceArg {
+    TradeFee,
+    ProtocolFee,
+}
+
+/// Mint the protocol's share of the fees since the last liquidity event
+/// first, against the available reserves
+#[derive(Args, Debug)]
+struct ProtocolFeeArgs {
+    /// Product of the reserves after the last liquidity event
+    #[arg(long)]
+    k_last: Option<u128>,
+    /// Share of the `sqrt(k)` growth minted to the protocol
+    #[arg(long)]
+    protocol_share: Option<u64>,
+}
+
+#[derive(Args, Debug)]
+struct PoolArgs {
+    /// JSON file with `pool_balances`, `amm_config`, `lp_supply`, `k_last`
+    /// and `protocol_share`
+    #[arg(long, global = true)]
+    pool: Option<PathBuf>,
+
+    #[arg(long, global = true)]
+    reserve_x: Option<u64>,
+    #[arg(long, global = true)]
+    reserve_y: Option<u64>,
+    #[arg(long, global = true)]
+    protocol_fee_x: Option<u64>,
+    #[arg(long, global = true)]
+    protocol_fee_y: Option<u64>,
+    #[arg(long, global = true)]
+    user_locked_x: Option<u64>,
+    #[arg(long, global = true)]
+    user_locked_y: Option<u64>,
+    #[arg(long, global = true)]
+    locked_x: Option<u64>,
+    #[arg(long, global = true)]
+    locked_y: Option<u64>,
+    #[arg(long, global = true)]
+    lp_fee_x: Option<u64>,
+    #[arg(long, global = true)]
+    lp_fee_y: Option<u64>,
+
+    #[arg(long, global = true)]
+    trade_fee_rate: Option<u64>,
+    #[arg(long, global = true)]
+    protocol_fee_rate: Option<u64>,
+    /// Defaults to 1000000, no limit
+    #[arg(long, global = true)]
+    ratio_change_tolerance_rate: Option<u64>,
+    #[arg(long, global = true)]
+    host_fee_rate: Option<u64>,
+    #[arg(long, global = true, value_enum)]
+    host_fee_source: Option<HostFeeSourceArg>,
+    #[arg(long, global = true, value_enum)]
+    fee_side: Option<FeeSideArg>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct PoolFile {
+    pool_balances: PoolBalances,
+    amm_config: Option<AmmConfig>,
+    lp_supply: Option<u64>,
+    #[serde(with = "dex_math::u128_string::option")]
+    k_last: Option<u128>,
+    protocol_share: Option<u64>,
+}
+
+struct Pool {
+    pool_balances: PoolBalances,
+    amm_config: AmmConfig,
+    lp_supply: Option<u64>,
+    k_last: Option<u128>,
+    protocol_share: Option<u64>,
+}
+
+fn load_pool(args: &PoolArgs) -> Result<Pool, String> {
+    let file = match &args.pool {
+        Some(path) => {
+            let contents = read_file(path)?;
+            serde_json::from_str::<PoolFile>(&contents).map_err(|e| format!("{}: {e}", path.display()))?
+        }
+        None => PoolFile::default(),
+    };
+
+    let mut pool_balances = file.pool_balances;
+    let overrides = [
+        (args.reserve_x, &mut pool_balances.reserve_x_balance),
+        (args.reserve_y, &mut pool_balances.reserve_y_balance),
+        (args.protocol_fee_x, &mut pool_balances.protocol_fee_x),
+        (args.protocol_fee_y, &mut pool_balances.protocol_fee_y),
+        (args.user_locked_x, &mut pool_balances.user_locked_x),
+        (args.user_locked_y, &mut pool_balances.user_locked_y),
+        (args.locked_x, &mut pool_balances.locked_x),
+        (args.locked_y, &mut pool_balances.locked_y),
+        (args.lp_fee_x, &mut pool_balances.lp_fee_x),
+        (args.lp_fee_y, &mut pool_balances.lp_fee_y),
+    ];
+    for (value, field) in overrides {
+        if let Some(value) = value {
+            *field = value;
+        }
+    }
+
+    let mut amm_config = file.amm_config.unwrap_or(AmmConfig {
+        trade_fee_rate: Ppm::ZERO,
+        protocol_fee_rate: Ppm::ZERO,
+        ratio_change_tolerance_rate: Ppm::MAX,
+        host_fee_rate: Ppm::ZERO,
+        host_fee_source: HostFeeSource::default(),
+        directional_fee_rates: None,
+        launch_fee_schedule: None,
+        fee_side: FeeSide::default(),
+    });
+    let rate = |value: u64| Ppm::new(value).map_err(describe_error);
+    if let Some(value) = args.trade_fee_rate {
+        amm_config.trade_fee_rate = rate(value)?;
+    }
+    if let Some(value) = args.protocol_fee_rate {
+        amm_config.protocol_fee_rate = rate(value)?;
+    }
+    if let Some(value) = args.ratio_change_tolerance_rate {
+        amm_config.ratio_change_tolerance_rate = rate(value)?;
+    }
+    if let Some(value) = args.host_fee_rate {
+        amm_config.host_fee_rate = rate(value)?;
+    }
+    if let Some(source) = args.host_fee_source {
+        amm_config.host_fee_source = match source {
+            HostFeeSourceArg::TradeFee => HostFeeSource::TradeFee,
+            HostFeeSourceArg::ProtocolFee => HostFeeSource::ProtocolFee,
+        };
+    }
+    if let Some(fee_side) = args.fee_side {
+        amm_config.fee_side = match fee_side {
+            FeeSideArg::Input => FeeSide::Input,
+            FeeSideArg::Output => FeeSide::Output,
+        };
+    }
+    amm_config.validate().map_err(describe_error)?;
+
+    Ok(Pool {
+        pool_balances,
+        amm_config,
+        lp_supply: file.lp_supply,
+        k_last: file.k_last,
+        protocol_share: file.protocol_share,
+    })
+}
+
+fn run(cli: &Cli) -> Result<Value, String> {
+    let pool = load_pool(&cli.pool)?;
+    let Pool {
+        pool_balances: balances,
+        amm_config,
+        ..
+    } = &pool;
+    let lp_supply = |lp_supply: Option<u64>| {
+        lp_supply
+            .or(pool.lp_supply)
+            .ok_or_else(|| "--lp-supply or `lp_supply` in --pool is required".to_string())
+    };
+    // k_last and protocol share to mint the protocol fee with
+    let protocol_fee = |args: &ProtocolFeeArgs| -> Result<Option<(u128, Ppm)>, String> {
+        let Some(k_last) = args.k_last.or(pool.k_last) else {
+            return Ok(None);
+        };
+        let protocol_share = args
+            .protocol_share
+            .or(pool.protocol_share)
+            .ok_or_else(|| "--protocol-share or `protocol_share` in --pool is required with a k_last".to_string())?;
+        Ok(Some((k_last, Ppm::new(protocol_share).map_err(describe_error)?)))
+    };
+
+    let result = match cli.command {
+        Command::Quote {
+            exchange_in,
+            direction,
+            now,
+        } => to_value(match now {
+            Some(now) => quote_pool_balances_at(now, exchange_in, direction.is_x_to_y(), amm_config, balances),
+            None => quote_pool_balances(exchange_in, direction.is_x_to_y(), amm_config, balances),
+        })?,
+        Command::QuoteExactOut {
+            amount_out,
+            direction,
+        } => to_value(quote_exact_out(amount_out, direction.is_x_to_y(), amm_config, balances))?,
+        Command::Deposit {
+            amount_x,
+            amount_y,
+            lp_supply: supply,
+            imbalanced,
+            protocol_fee: ref args,
+        } => {
+            let supply = lp_supply(supply)?;
+            if let Some((k_last, share)) = protocol_fee(args)? {
+                if imbalanced {
+                    return Err("imbalanced deposits do not mint the protocol fee".to_string());
+                }
+                to_value(deposit_lp_with_protocol_fee(amount_x, amount_y, supply, balances, k_last, share))?
+            } else if imbalanced {
+                to_value(deposit_imbalanced(amount_x, amount_y, supply, balances, amm_config))?
+            } else {
+                to_value(deposit_lp_strict(amount_x, amount_y, supply, balances))?
+            }
+        }
+        Command::Withdraw {
+            lp_tokens,
+            lp_supply: supply,
+            protocol_fee: ref args,
+        } => {
+            let supply = lp_supply(supply)?;
+            if let Some((k_last, share)) = protocol_fee(args)? {
+                to_value(withdraw_lp_with_protocol_fee(lp_tokens, supply, balances, k_last, share))?
+            } else {
+                to_value(withdraw_lp_from_pool(lp_tokens, supply, balances))?
+            }
+        }
+        Command::MaxTrade { direction } => {
+            let max = max_exchange_in(direction.is_x_to_y(), amm_config, balances).map_err(describe_error)?;
+            let output = quote_pool_balances(max, direction.is_x_to_y(), amm_config, balances);
+            json!({ "max_exchange_in": max, "quote": to_value(output)? })
+        }
+        Command::Rebalance {
+            to_amount_swapped,
+            direction,
+        } => {
+            let (total_x, total_y) = balances.total().ok_or_else(|| describe_error(MathError::MathOverflow))?;
+            let (available_x, available_y) =
+                balances.available().ok_or_else(|| describe_error(MathError::MathOverflow))?;
+            let ((available_source, available_destination), (total_source, total_destination)) = match direction {
+                Direction::XToY => ((available_x, available_y), (total_x, total_y)),
+                Direction::YToX => ((available_y, available_x), (total_y, total_x)),
+            };
+            let result = rebalance_pool_ratio(
+                to_amount_swapped,
+                available_source,
+                available_destination,
+                total_source,
+                total_destination,
+                amm_config.ratio_change_tolerance_rate,
+            )
+            .ok_or_else(|| describe_error(MathError::MathOverflow))?;
+            serde_json::to_value(result).map_err(|e| e.to_string())?
+        }
+        Command::Backtest {
+            ref trades,
+            ref configs,
+        } => {
+            let contents = read_file(trades)?;
+            let trades: Vec<Trade> = if trades.extension().is_some_and(|extension| extension == "json") {
+                serde_json::from_str(&contents).map_err(|e| format!("{}: {e}", trades.display()))?
+            } else {
+                parse_trades_csv(&contents).map_err(|e| format!("{}: {e}", trades.display()))?
+            };
+            let amm_configs: Vec<AmmConfig> = match configs {
+                Some(path) => serde_json::from_str(&read_file(path)?).map_err(|e| format!("{}: {e}", path.display()))?,
+                None => vec![amm_config.clone()],
+            };
+            for amm_config in &amm_configs {
+                amm_config.validate().map_err(describe_error)?;
+            }
+
+            let reports = backtest(&trades, balances.reserve_x_balance, balances.reserve_y_balance, &amm_configs);
+            serde_json::to_value(reports).map_err(|e| e.to_string())?
+        }
+        Command::MonteCarlo {
+            seed,
+            paths,
+            steps,
+            dt,
+            drift,
+            volatility,
+            noise_trades_per_step,
+            noise_trade_size,
+        } => {
+            let params = MonteCarloParams {
+                seed,
+                paths,
+                steps,
+                dt,
+                drift,
+                volatility,
+                reserve_x: balances.reserve_x_balance,
+                reserve_y: balances.reserve_y_balance,
+                noise_trades_per_step,
+                noise_trade_size,
+            };
+            let report =
+                simulate(&params, amm_config).ok_or_else(|| "--paths and both reserves must be non-zero".to_string())?;
+            serde_json::to_value(report).map_err(|e| e.to_string())?
+        }
+    };
+    Ok(result)
+}
+
+fn read_file(path: &PathBuf) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+fn to_value<T: serde::Serialize>(result: dex_math::errors::Result<T>) -> Result<Value, String> {
+    let result = result.map_err(describe_error)?;
+    serde_json::to_value(result).map_err(|e| e.to_string())
+}
+
+fn describe_error(error: MathError) -> String {
+    format!("{} ({}): {}", error.name(), error.code(), error)
+}
+
+/// Key / value rows, nested objects and arrays flattened to dotted keys
+fn table_rows(prefix: &str, value: &Value, rows: &mut Vec<(String, String)>) {
+    let key = |key: &dyn std::fmt::Display| {
+        if prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{prefix}.{key}")
+        }
+    };
+    match value {
+        Value::Object(map) => {
+            for (name, value) in map {
+                table_rows(&key(name), value, rows);
+            }
+        }
+        Value::Array(values) => {
+            for (index, value) in values.iter().enumerate() {
+                table_rows(&key(&index), value, rows);
+            }
+        }
+        Value::Null => rows.push((prefix.to_string(), "-".to_string())),
+        Value::String(value) => rows.push((prefix.to_string(), value.clone())),
+        value => rows.push((prefix.to_string(), value.to_string())),
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(&cli) {
+        Ok(result) if cli.json => {
+            println!("{result:#}");
+            ExitCode::SUCCESS
+        }
+        Ok(result) => {
+            let mut rows = Vec::new();
+            table_rows("", &result, &mut rows);
+            let width = rows.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+            for (key, value) in rows {
+                println!("{key:<width$}  {value}");
+            }
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            if cli.json {
+                println!("{:#}", Value::Object(Map::from_iter([("error".to_string(), Value::String(error))])));
+            } else {
+                eprintln!("error: {error}");
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_args(args: &[&str]) -> Result<Value, String> {
+        let cli = Cli::try_parse_from(["dex-math"].iter().chain(args)).unwrap();
+        run(&cli)
+    }
+
+    #[test]
+    fn quotes_from_flags() {
+        let result = run_args(&["quote", "100", "--reserve-x", "1000", "--reserve-y", "2000"]).unwrap();
+        assert_eq!(result["to_amount"], 181);
+        assert_eq!(result["from_to_lock"], 90);
+
+        let result = run_args(&[
+            "--reserve-x", "1000000", "--reserve-y", "2000000", "--trade-fee-rate", "3000",
+            "quote-exact-out", "10000", "--direction", "x-to-y",
+        ])
+        .unwrap();
+        assert!(result["to_amount"].as_u64().unwrap() >= 10_000);
+    }
+
+    #[test]
+    fn rejects_inconsistent_balances() {
+        let error = run_args(&["--reserve-x", "10", "--protocol-fee-x", "20", "--reserve-y", "100", "quote", "1"]).unwrap_err();
+        assert!(error.starts_with("MathLibMathOverflow"), "{error}");
+    }
+
+    #[test]
+    fn mints_the_protocol_fee_on_liquidity_events() {
+        // sqrt(k) grew from 2000000 to 2020000, half of it is the protocol's
+        let pool = ["--reserve-x", "1010000", "--reserve-y", "4040000"];
+        let protocol_fee = ["--lp-supply", "2000000", "--k-last", "4000000000000", "--protocol-share", "500000"];
+
+        let result = run_args(&[&pool[..], &["deposit", "10100", "40400"], &protocol_fee[..]].concat()).unwrap();
+        assert_eq!(result["protocol_fee_lp"], 9_950);
+        assert_eq!(result["lp_minted"], 20_099);
+        assert_eq!(result["unused_y"], 1);
+        assert_eq!(result["k_last"], "4162415019900");
+
+        let result = run_args(&[&pool[..], &["withdraw", "20000"], &protocol_fee[..]].concat()).unwrap();
+        assert_eq!(result["protocol_fee_lp"], 9_950);
+        assert_eq!(result["amount_x"], 10_050);
+
+        let error = run_args(&[&pool[..], &["withdraw", "20000", "--lp-supply", "2000000", "--k-last", "1"]].concat());
+        assert!(error.unwrap_err().contains("--protocol-share"));
+    }
+
+    #[test]
+    fn reports_tolerance_outcome() {
+        let pool = ["--reserve-x", "1000000", "--reserve-y", "2000000", "--ratio-change-tolerance-rate", "10000"];
+
+        let result = run_args(&[&pool[..], &["max-trade"]].concat()).unwrap();
+        let max = result["max_exchange_in"].as_u64().unwrap();
+        assert_eq!(result["quote"]["from_amount"], max);
+
+        let error = run_args(&[&pool[..], &["quote", &(max + 1).to_string()]].concat()).unwrap_err();
+        assert!(error.starts_with("MathLib"));
+
+        let result = run_args(&[&pool[..], &["rebalance", "100000"]].concat()).unwrap();
+        assert_eq!(result["is_rate_tolerance_exceeded"], false);
+        let result = run_args(&[&pool[..], &["rebalance", "1999999"]].concat()).unwrap();
+        assert_eq!(result["is_rate_tolerance_exceeded"], true);
+    }
+
+    #[test]
+    fn reads_the_pool_file() {
+        let path = std::env::temp_dir().join(format!("dex-math-cli-{}.json", std::process::id()));
+        fs::write(
+            &path,
+            r#"{ "pool_balances": { "reserve_x_balance": 1000000, "reserve_y_balance": 4000000 }, "lp_supply": 1000000 }"#,
+        )
+        .unwrap();
+        let pool = path.to_str().unwrap();
+
+        let result = run_args(&["--pool", pool, "deposit", "10000", "50000"]).unwrap();
+        assert_eq!(result["lp_minted"], 10_000);
+        assert_eq!(result["unused_y"], 10_000);
+
+        // flags override the file
+        let result = run_args(&["--pool", pool, "withdraw", "1000", "--lp-supply", "2000000"]).unwrap();
+        assert_eq!(result["amount_x"], 500);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn backtests_a_trade_log() {
+        let dir = std::env::temp_dir();
+        let trades = dir.join(format!("dex-math-cli-trades-{}.csv", std::process::id()));
+        let configs = dir.join(format!("dex-math-cli-configs-{}.json", std::process::id()));
+        fs::write(&trades, "timestamp,direction,amount\n1,x_to_y,10000\n2,y_to_x,5000\n").unwrap();
+        let amm_config = |trade_fee_rate: u64| {
+            AmmConfig::builder()
+                .trade_fee_rate(Ppm::new(trade_fee_rate).unwrap())
+                .ratio_change_tolerance_rate(Ppm::MAX)
+                .build()
+                .unwrap()
+        };
+        fs::write(&configs, serde_json::to_string(&[amm_config(1_000), amm_config(3_000)]).unwrap()).unwrap();
+
+        let result = run_args(&[
+            "--reserve-x", "1000000", "--reserve-y", "2000000",
+            "backtest", trades.to_str().unwrap(), "--configs", configs.to_str().unwrap(),
+        ])
+        .unwrap();
+        assert_eq!(result[0]["executed_trades"], 2);
+        assert_eq!(result[0]["lp_fee_x"], 10);
+        assert_eq!(result[1]["lp_fee_x"], 30);
+
+        let mut rows = Vec::new();
+        table_rows("", &result, &mut rows);
+        assert!(rows.contains(&("1.lp_fee_y".to_string(), "15".to_string())));
+
+        fs::remove_file(trades).unwrap();
+        fs::remove_file(configs).unwrap();
+    }
+
+    #[test]
+    fn simulates_lp_returns() {
+        let args = [
+            "--reserve-x", "1000000000", "--reserve-y", "2000000000", "--trade-fee-rate", "3000",
+            "--ratio-change-tolerance-rate", "1000000",
+            "monte-carlo", "--paths", "20", "--steps", "10", "--seed", "42",
+        ];
+        let result = run_args(&args).unwrap();
+        assert_eq!(result, run_args(&args).unwrap());
+        assert!(result["lp_return"]["p50"].is_f64());
+        assert!(result["impermanent_loss"]["mean"].as_f64().unwrap() <= 0.0);
+
+        assert!(run_args(&["--reserve-x", "1000", "--reserve-y", "2000", "monte-carlo", "--paths", "0"]).is_err());
+    }
 }
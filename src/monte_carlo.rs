@@ -0,0 +1,332 @@
+//! Monte Carlo LP return simulator
+//!
+//! Each path moves an external price (Y per X) by geometric Brownian motion.
+//! After every move an arbitrageur trades the pool back towards it and noise
+//! traders swap random amounts, all through [`SimPool`]. At the end of a path
+//! the LP position is valued at the external price and compared to holding the
+//! initial reserves.
+//!
+//! Randomness comes from a seeded SplitMix64, so a given seed always gives the
+//! same report.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    sim::{Operation, SimError, SimPool},
+    AmmConfig, MathError, SwapDirection, MAX_PERCENTAGE,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MonteCarloParams {
+    pub seed: u64,
+    pub paths: u32,
+    pub steps: u32,
+    /// Length of a step in years, e.g. `1.0 / 365.0` for daily steps
+    pub dt: f64,
+    /// Annualised drift of the external price
+    pub drift: f64,
+    /// Annualised volatility of the external price
+    pub volatility: f64,
+    /// Starting reserves, the initial external price is `reserve_y / reserve_x`
+    pub reserve_x: u64,
+    pub reserve_y: u64,
+    /// Noise trades per step, each in a random direction
+    pub noise_trades_per_step: u32,
+    /// Largest noise trade as a fraction of the available source reserve
+    pub noise_trade_size: f64,
+}
+
+/// Distribution of a per-path result
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Summary {
+    pub mean: f64,
+    pub std_dev: f64,
+    pub min: f64,
+    pub p5: f64,
+    pub p25: f64,
+    pub p50: f64,
+    pub p75: f64,
+    pub p95: f64,
+    pub max: f64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MonteCarloReport {
+    pub params: MonteCarloParams,
+    pub amm_config: AmmConfig,
+
+    /// LP position value, fee buckets included, over its initial value, minus 1
+    pub lp_return: Summary,
+    /// Value of the initial reserves held outside the pool, over their initial value, minus 1
+    pub hodl_return: Summary,
+    /// LP position value over the HODL value, minus 1
+    pub lp_vs_hodl: Summary,
+    /// Curve reserves alone, without fee income, over the HODL value, minus 1
+    pub impermanent_loss: Summary,
+    /// Final price over the initial price
+    pub price_ratio: Summary,
+
+    pub arbitrage_trades: u64,
+    pub noise_trades: u64,
+    /// Trades refused by the pool, e.g. over the ratio change tolerance
+    pub rejected_trades: u64,
+}
+
+/// Run `params.paths` simulations of a pool with `amm_config`
+///
+/// `None` if there are no paths or either starting reserve is empty.
+pub fn simulate(params: &MonteCarloParams, amm_config: &AmmConfig) -> Option<MonteCarloReport> {
+    if params.paths == 0 || params.reserve_x == 0 || params.reserve_y == 0 {
+        return None;
+    }
+
+    let mut rng = SplitMix64(params.seed);
+    let mut counts = TradeCounts::default();
+    let paths: Vec<PathResult> = (0..params.paths)
+        .map(|_| simulate_path(params, amm_config, &mut rng, &mut counts))
+        .collect();
+
+    let summary = |value: fn(&PathResult) -> f64| summarize(paths.iter().map(value).collect());
+    Some(MonteCarloReport {
+        params: *params,
+        amm_config: amm_config.clone(),
+        lp_return: summary(|path| path.lp_value / path.initial_value - 1.0),
+        hodl_return: summary(|path| path.hodl_value / path.initial_value - 1.0),
+        lp_vs_hodl: summary(|path| path.lp_value / path.hodl_value - 1.0),
+        impermanent_loss: summary(|path| path.curve_value / path.hodl_value - 1.0),
+        price_ratio: summary(|path| path.price_ratio),
+        arbitrage_trades: counts.arbitrage,
+        noise_trades: counts.noise,
+        rejected_trades: counts.rejected,
+    })
+}
+
+#[derive(Default)]
+struct TradeCounts {
+    arbitrage: u64,
+    noise: u64,
+    rejected: u64,
+}
+
+struct PathResult {
+    initial_value: f64,
+    hodl_value: f64,
+    lp_value: f64,
+    curve_value: f64,
+    price_ratio: f64,
+}
+
+fn simulate_path(
+    params: &MonteCarloParams,
+    amm_config: &AmmConfig,
+    rng: &mut SplitMix64,
+    counts: &mut TradeCounts,
+) -> PathResult {
+    let mut pool = SimPool::new(amm_config.clone(), params.reserve_x, params.reserve_y);
+    let (reserve_x, reserve_y) = (params.reserve_x as f64, params.reserve_y as f64);
+    let initial_price = reserve_y / reserve_x;
+    let mut price = initial_price;
+
+    let drift = (params.drift - params.volatility * params.volatility / 2.0) * params.dt;
+    let diffusion = params.volatility * params.dt.sqrt();
+
+    for _ in 0..params.steps {
+        price *= (drift + diffusion * rng.next_normal()).exp();
+
+        if let Some((exchange_in, is_swap_x_to_y)) = arbitrage_trade(&pool, amm_config, price) {
+            // shrink the trade until the pool accepts it
+            let mut exchange_in = exchange_in;
+            while exchange_in > 0 {
+                match pool.apply(Operation::Swap { exchange_in, is_swap_x_to_y }) {
+                    Ok(_) => {
+                        counts.arbitrage += 1;
+                        break;
+                    }
+                    Err(error) => {
+                        counts.rejected += 1;
+                        if !is_trade_too_big(&error) {
+                            break;
+                        }
+                        exchange_in /= 2;
+                    }
+                }
+            }
+        }
+
+        for _ in 0..params.noise_trades_per_step {
+            let is_swap_x_to_y = rng.next_u64() & 1 == 0;
+            let (available_x, available_y) = pool.pool_balances().available().unwrap_or_default();
+            let available_source = if is_swap_x_to_y { available_x } else { available_y };
+            let exchange_in = (available_source as f64 * params.noise_trade_size * rng.next_f64()) as u64;
+            if exchange_in == 0 {
+                continue;
+            }
+            match pool.apply(Operation::Swap { exchange_in, is_swap_x_to_y }) {
+                Ok(_) => counts.noise += 1,
+                Err(_) => counts.rejected += 1,
+            }
+        }
+    }
+
+    let balances = pool.pool_balances();
+    let (available_x, available_y) = balances.available().unwrap_or_default();
+    let curve_value = available_x as f64 * price + available_y as f64;
+    PathResult {
+        initial_value: reserve_x * initial_price + reserve_y,
+        hodl_value: reserve_x * price + reserve_y,
+        lp_value: curve_value + balances.lp_fee_x as f64 * price + balances.lp_fee_y as f64,
+        curve_value,
+        price_ratio: price / initial_price,
+    }
+}
+
+/// Input that moves the pool's marginal price, after the trade fee, to the
+/// external `price`
+///
+/// For `x * y = k` and a fee `f` on the input, swapping Y in stops paying once
+/// `y + (1 - f) * dy = sqrt((1 - f) * k * price)`, and symmetrically for X.
+fn arbitrage_trade(pool: &SimPool, amm_config: &AmmConfig, price: f64) -> Option<(u64, bool)> {
+    let (available_x, available_y) = pool.pool_balances().available()?;
+    let (x, y) = (available_x as f64, available_y as f64);
+    let k = x * y;
+
+    let fee_factor = |direction: SwapDirection| {
+        1.0 - amm_config.fee_rates(direction).trade_fee_rate.get() as f64 / MAX_PERCENTAGE as f64
+    };
+
+    if price > y / x {
+        // X is cheap in the pool, buy it with Y
+        let gamma = fee_factor(SwapDirection::YToX);
+        let exchange_in = ((gamma * k * price).sqrt() - y) / gamma;
+        (exchange_in >= 1.0).then_some((exchange_in as u64, false))
+    } else {
+        let gamma = fee_factor(SwapDirection::XToY);
+        let exchange_in = ((gamma * k / price).sqrt() - x) / gamma;
+        (exchange_in >= 1.0).then_some((exchange_in as u64, true))
+    }
+}
+
+fn is_trade_too_big(error: &SimError) -> bool {
+    matches!(error, SimError::Math(MathError::TradeTooBig))
+}
+
+fn summarize(mut values: Vec<f64>) -> Summary {
+    values.sort_by(f64::total_cmp);
+    let count = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / count;
+    let variance = values.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / count;
+
+    // linear interpolation between the closest ranks
+    let percentile = |p: f64| {
+        let rank = p * (count - 1.0);
+        let (low, high) = (rank.floor() as usize, rank.ceil() as usize);
+        values[low] + (values[high] - values[low]) * (rank - rank.floor())
+    };
+
+    Summary {
+        mean,
+        std_dev: variance.sqrt(),
+        min: values[0],
+        p5: percentile(0.05),
+        p25: percentile(0.25),
+        p50: percentile(0.5),
+        p75: percentile(0.75),
+        p95: percentile(0.95),
+        max: values[values.len() - 1],
+    }
+}
+
+/// SplitMix64, small and stable across platforms and versions
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1)
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Standard normal, Box-Muller
+    fn next_normal(&mut self) -> f64 {
+        let u1 = 1.0 - self.next_f64();
+        let u2 = self.next_f64();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * core::f64::consts::PI * u2).cos()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::Ppm};
+
+    fn params(seed: u64) -> MonteCarloParams {
+        MonteCarloParams {
+            seed,
+            paths: 50,
+            steps: 30,
+            dt: 1.0 / 365.0,
+            drift: 0.0,
+            volatility: 0.8,
+            reserve_x: 1_000_000_000,
+            reserve_y: 2_000_000_000,
+            noise_trades_per_step: 2,
+            noise_trade_size: 0.001,
+        }
+    }
+
+    fn amm_config() -> AmmConfig {
+        AmmConfig::builder()
+            .trade_fee_rate(Ppm::new(3_000).unwrap())
+            .protocol_fee_rate(Ppm::new(200_000).unwrap())
+            .ratio_change_tolerance_rate(Ppm::MAX)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn same_seed_same_report() {
+        let report = simulate(&params(7), &amm_config()).unwrap();
+        assert_eq!(report, simulate(&params(7), &amm_config()).unwrap());
+        assert_ne!(report.lp_return, simulate(&params(8), &amm_config()).unwrap().lp_return);
+        assert!(report.arbitrage_trades > 0 && report.noise_trades > 0);
+    }
+
+    #[test]
+    fn lp_position_tracks_impermanent_loss_and_fees() {
+        let report = simulate(&params(1), &amm_config()).unwrap();
+
+        // a constant product curve never beats holding, fees make up part of it
+        assert!(report.impermanent_loss.max <= 1e-6);
+        assert!(report.impermanent_loss.mean < 0.0);
+        assert!(report.lp_vs_hodl.mean > report.impermanent_loss.mean);
+
+        let percentiles = report.lp_return;
+        assert!(percentiles.min <= percentiles.p5 && percentiles.p5 <= percentiles.p50);
+        assert!(percentiles.p50 <= percentiles.p95 && percentiles.p95 <= percentiles.max);
+    }
+
+    #[test]
+    fn flat_prices_without_flow_match_hodl() {
+        let params = MonteCarloParams {
+            volatility: 0.0,
+            noise_trades_per_step: 0,
+            ..params(3)
+        };
+        let report = simulate(&params, &amm_config()).unwrap();
+        assert_eq!(report.arbitrage_trades, 0);
+        assert_eq!(report.lp_vs_hodl.max, 0.0);
+        assert_eq!(report.hodl_return.mean, 0.0);
+
+        assert!(simulate(&MonteCarloParams { paths: 0, ..params }, &amm_config()).is_none());
+    }
+}
@@ -0,0 +1,109 @@
+//! Kani proof harnesses for the core arithmetic
+//!
+//! ```text
+//! cargo install --locked kani-verifier && cargo kani setup
+//! cargo kani
+//! ```
+//!
+//! Inputs are bounded the way the callers bound them: token amounts and pool
+//! reserves fit a `u64`, rates are at most `MAX_PERCENTAGE`. Within those
+//! bounds Kani also proves every unchecked operation along the way free of
+//! overflow, so none of the `unwrap`s in the curve math can panic.
+//!
+//! The curve harnesses multiply and divide 128 bit values and are by far the
+//! slowest, pick harnesses by name with e.g. `cargo kani --harness fee`.
+
+use crate::{
+    utils::{ceil_div, floor_div, swap, swap_base_input_without_fees},
+    get_protocol_fee, get_trade_fee, Ppm, MAX_PERCENTAGE,
+};
+
+fn amount() -> u128 {
+    u128::from(kani::any::<u64>())
+}
+
+fn rate() -> u64 {
+    let rate: u64 = kani::any();
+    kani::assume(rate <= MAX_PERCENTAGE);
+    rate
+}
+
+#[kani::proof]
+fn ceil_div_rounds_up() {
+    let (amount, numerator) = (amount(), u128::from(rate()));
+    let denominator: u128 = kani::any();
+    kani::assume(denominator > 0 && denominator <= u128::from(MAX_PERCENTAGE));
+
+    let result = ceil_div(amount, numerator, denominator).unwrap();
+    let exact = amount * numerator;
+    assert!(result * denominator >= exact);
+    assert!(result == 0 || (result - 1) * denominator < exact);
+}
+
+#[kani::proof]
+fn floor_div_rounds_down() {
+    let (amount, numerator) = (amount(), u128::from(rate()));
+    let denominator: u128 = kani::any();
+    kani::assume(denominator > 0 && denominator <= u128::from(MAX_PERCENTAGE));
+
+    let result = floor_div(amount, numerator, denominator).unwrap();
+    let exact = amount * numerator;
+    assert!(result * denominator <= exact);
+    assert!((result + 1) * denominator > exact);
+}
+
+#[kani::proof]
+fn trade_fee_fits_in_amount() {
+    let amount = amount();
+    let fee = get_trade_fee(amount, rate()).unwrap();
+    assert!(fee <= amount);
+}
+
+#[kani::proof]
+fn protocol_fee_fits_in_amount() {
+    let amount = amount();
+    let fee = get_protocol_fee(amount, rate()).unwrap();
+    assert!(fee <= amount);
+}
+
+#[kani::proof]
+fn fee_rates_over_100_percent_are_rejected() {
+    let rate: u64 = kani::any();
+    kani::assume(rate > MAX_PERCENTAGE);
+    assert!(get_trade_fee(amount(), rate).is_none());
+    assert!(get_protocol_fee(amount(), rate).is_none());
+}
+
+#[kani::proof]
+fn curve_keeps_k() {
+    let (source_amount, swap_source_amount, swap_destination_amount) = (amount(), amount(), amount());
+
+    let destination_amount = swap_base_input_without_fees(source_amount, swap_source_amount, swap_destination_amount);
+    assert!(destination_amount <= swap_destination_amount);
+
+    // a product over u128 is above any k of u64 reserves
+    let k = swap_source_amount * swap_destination_amount;
+    let next_source = swap_source_amount + source_amount;
+    if let Some(next_k) = next_source.checked_mul(swap_destination_amount - destination_amount) {
+        assert!(next_k >= k);
+    }
+}
+
+#[kani::proof]
+fn swap_splits_the_input_and_keeps_k() {
+    let (source_amount, swap_source_amount, swap_destination_amount) = (amount(), amount(), amount());
+    let trade_fee_rate = Ppm::new(rate()).unwrap();
+    let protocol_fee_rate = Ppm::new(rate()).unwrap();
+
+    let result = swap(source_amount, swap_source_amount, swap_destination_amount, trade_fee_rate, protocol_fee_rate)
+        .unwrap();
+    assert_eq!(u128::from(result.from_amount) + u128::from(result.trade_fee), source_amount);
+    assert!(result.protocol_fee <= result.trade_fee);
+    assert!(u128::from(result.to_amount) <= swap_destination_amount);
+
+    let k = swap_source_amount * swap_destination_amount;
+    let next_source = swap_source_amount + u128::from(result.from_amount);
+    if let Some(next_k) = next_source.checked_mul(swap_destination_amount - u128::from(result.to_amount)) {
+        assert!(next_k >= k);
+    }
+}
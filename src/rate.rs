@@ -0,0 +1,126 @@
+//! Typed rates
+//!
+//! Fee and tolerance rates are parts per million of `MAX_PERCENTAGE`. Wrapping
+//! them keeps basis points from being passed where ppm is expected.
+
+use crate::{
+    errors::{MathError, Result},
+    utils::ceil_div,
+    utils::floor_div,
+    MAX_PERCENTAGE,
+};
+#[cfg(feature = "borsh")]
+use borsh::{BorshDeserialize, BorshSerialize};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+pub const MAX_BPS: u64 = 10_000;
+
+/// Rate in parts per million, 10^6 = 100%
+///
+/// Serialized as a bare `u64`. Deserializing does not range check, see
+/// [`crate::AmmConfig::validate`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(transparent))]
+pub struct Ppm(u64);
+
+impl Ppm {
+    pub const ZERO: Ppm = Ppm(0);
+    pub const MAX: Ppm = Ppm(MAX_PERCENTAGE);
+
+    /// Create a rate, rejecting anything above `MAX_PERCENTAGE`
+    pub fn new(ppm: u64) -> Result<Ppm> {
+        if ppm > MAX_PERCENTAGE {
+            return Err(MathError::InvalidRate);
+        }
+        Ok(Ppm(ppm))
+    }
+
+    pub const fn get(self) -> u64 {
+        self.0
+    }
+
+    /// `amount * rate`, rounded up
+    pub fn apply_ceil(self, amount: u128) -> Option<u128> {
+        ceil_div(amount, u128::from(self.0), u128::from(MAX_PERCENTAGE))
+    }
+
+    /// `amount * rate`, rounded down
+    pub fn apply_floor(self, amount: u128) -> Option<u128> {
+        floor_div(amount, u128::from(self.0), u128::from(MAX_PERCENTAGE))
+    }
+}
+
+impl From<Bps> for Ppm {
+    fn from(bps: Bps) -> Self {
+        // MAX_BPS * 100 == MAX_PERCENTAGE, never out of range
+        Ppm(bps.0 * (MAX_PERCENTAGE / MAX_BPS))
+    }
+}
+
+/// Rate in basis points, 10^4 = 100%
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(transparent))]
+pub struct Bps(u64);
+
+impl Bps {
+    /// Create a rate, rejecting anything above `MAX_BPS`
+    pub fn new(bps: u64) -> Result<Bps> {
+        if bps > MAX_BPS {
+            return Err(MathError::InvalidRate);
+        }
+        Ok(Bps(bps))
+    }
+
+    pub const fn get(self) -> u64 {
+        self.0
+    }
+
+    pub fn to_ppm(self) -> Ppm {
+        Ppm::from(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::AmmConfig};
+
+    #[test]
+    fn rejects_rates_above_one_hundred_percent() {
+        assert_eq!(Ppm::new(MAX_PERCENTAGE).unwrap(), Ppm::MAX);
+        assert!(Ppm::new(MAX_PERCENTAGE + 1).is_err());
+        assert_eq!(Bps::new(MAX_BPS).unwrap().to_ppm(), Ppm::MAX);
+        assert!(Bps::new(MAX_BPS + 1).is_err());
+    }
+
+    #[test]
+    fn bps_converts_to_ppm() {
+        // 30 bps == 0.3% == 3000 ppm
+        assert_eq!(Ppm::from(Bps::new(30).unwrap()).get(), 3_000);
+    }
+
+    #[test]
+    fn apply_rounds_in_the_requested_direction() {
+        let rate = Ppm::new(3_000).unwrap();
+        assert_eq!(rate.apply_ceil(1_001), Some(4));
+        assert_eq!(rate.apply_floor(1_001), Some(3));
+        assert_eq!(rate.apply_ceil(1_000), Some(3));
+        assert_eq!(rate.apply_floor(1_000), Some(3));
+        assert_eq!(Ppm::MAX.apply_ceil(u128::MAX), None);
+    }
+
+    #[test]
+    fn builder_accepts_bps_and_ppm() {
+        let amm_config = AmmConfig::builder()
+            .trade_fee_rate(Bps::new(30).unwrap())
+            .protocol_fee_rate(Ppm::new(200_000).unwrap())
+            .ratio_change_tolerance_rate(Ppm::new(10_000).unwrap())
+            .build()
+            .unwrap();
+        assert_eq!(amm_config.trade_fee_rate.get(), 3_000);
+
+        assert!(AmmConfig::builder().trade_fee_rate(Ppm::MAX).build().is_err());
+    }
+}
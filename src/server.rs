@@ -0,0 +1,363 @@
+//! Local JSON quote server for frontend development
+//!
+//! Every endpoint takes a `POST` with a JSON body carrying the pool state and
+//! calls the library directly. Failures answer with
+//! `{ "error": { "code", "name", "message" } }`, `code` being the
+//! `MathError::code`, the on-chain `ErrorCode` number, for math errors.
+//!
+//! ```text
+//! dex-math-server 127.0.0.1:8080
+//! curl -d '{"amm_config": {...}, "pool_balances": {...}, "exchange_in": 1000, "is_swap_x_to_y": true}' \
+//!     -H 'content-type: application/json' localhost:8080/quote
+//! ```
+
+use axum::{
+    extract::rejection::JsonRejection,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use dex_math::{
+    deposit_imbalanced, deposit_lp_strict, deposit_lp_with_protocol_fee, max_exchange_in, quote_exact_out,
+    quote_pool_balances, quote_pool_balances_at, withdraw_lp_from_pool, withdraw_lp_with_protocol_fee, AmmConfig,
+    MathError, PoolBalances, Ppm, QuoteOutput,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+const DEFAULT_ADDRESS: &str = "127.0.0.1:8080";
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    let address = std::env::args().nth(1).unwrap_or_else(|| DEFAULT_ADDRESS.to_string());
+    let listener = tokio::net::TcpListener::bind(&address).await?;
+    println!("dex-math-server listening on {}", listener.local_addr()?);
+    axum::serve(listener, router()).await
+}
+
+fn router() -> Router {
+    Router::new()
+        .route("/quote", post(quote_handler))
+        .route("/quote-exact-out", post(quote_exact_out_handler))
+        .route("/deposit", post(deposit_handler))
+        .route("/withdraw", post(withdraw_handler))
+        .route("/max-trade", post(max_trade_handler))
+}
+
+#[derive(Deserialize)]
+struct QuoteRequest {
+    amm_config: AmmConfig,
+    pool_balances: PoolBalances,
+    exchange_in: u64,
+    is_swap_x_to_y: bool,
+    /// Slot / timestamp to apply the launch fee schedule at
+    now: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct QuoteExactOutRequest {
+    amm_config: AmmConfig,
+    pool_balances: PoolBalances,
+    amount_out: u64,
+    is_swap_x_to_y: bool,
+}
+
+#[derive(Deserialize)]
+struct DepositRequest {
+    /// Only needed for `imbalanced` deposits, which pay the trade fee
+    amm_config: Option<AmmConfig>,
+    pool_balances: PoolBalances,
+    lp_supply: u64,
+    amount_x: u64,
+    amount_y: u64,
+    /// Mint for the full value instead of refunding the excess
+    #[serde(default)]
+    imbalanced: bool,
+    protocol_fee: Option<ProtocolFee>,
+}
+
+#[derive(Deserialize)]
+struct WithdrawRequest {
+    pool_balances: PoolBalances,
+    lp_supply: u64,
+    lp_tokens: u64,
+    protocol_fee: Option<ProtocolFee>,
+}
+
+/// Mint the protocol's share of the fees since the last liquidity event
+/// first, against the available reserves
+#[derive(Deserialize)]
+struct ProtocolFee {
+    /// Product of the reserves after the last liquidity event
+    #[serde(with = "dex_math::u128_string")]
+    k_last: u128,
+    protocol_share: Ppm,
+}
+
+#[derive(Deserialize)]
+struct MaxTradeRequest {
+    amm_config: AmmConfig,
+    pool_balances: PoolBalances,
+    is_swap_x_to_y: bool,
+}
+
+#[derive(Serialize)]
+struct MaxTradeResponse {
+    max_exchange_in: u64,
+    quote: QuoteOutput,
+}
+
+struct ApiError {
+    status: StatusCode,
+    code: Option<u32>,
+    name: String,
+    message: String,
+}
+
+impl From<MathError> for ApiError {
+    fn from(error: MathError) -> Self {
+        ApiError {
+            status: StatusCode::UNPROCESSABLE_ENTITY,
+            code: Some(error.code()),
+            name: error.name().to_string(),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        invalid_request(&rejection.body_text())
+    }
+}
+
+fn invalid_request(message: &str) -> ApiError {
+    ApiError {
+        status: StatusCode::BAD_REQUEST,
+        code: None,
+        name: "InvalidRequest".to_string(),
+        message: message.to_string(),
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = json!({ "error": { "code": self.code, "name": self.name, "message": self.message } });
+        (self.status, Json(body)).into_response()
+    }
+}
+
+type ApiResult = Result<Json<Value>, ApiError>;
+
+fn respond<T: Serialize>(result: dex_math::errors::Result<T>) -> ApiResult {
+    let value = serde_json::to_value(result?).expect("results serialize to JSON");
+    Ok(Json(value))
+}
+
+async fn quote_handler(request: Result<Json<QuoteRequest>, JsonRejection>) -> ApiResult {
+    let Json(request) = request?;
+    request.amm_config.validate()?;
+    let (amm_config, balances) = (&request.amm_config, &request.pool_balances);
+    respond(match request.now {
+        Some(now) => quote_pool_balances_at(now, request.exchange_in, request.is_swap_x_to_y, amm_config, balances),
+        None => quote_pool_balances(request.exchange_in, request.is_swap_x_to_y, amm_config, balances),
+    })
+}
+
+async fn quote_exact_out_handler(request: Result<Json<QuoteExactOutRequest>, JsonRejection>) -> ApiResult {
+    let Json(request) = request?;
+    request.amm_config.validate()?;
+    respond(quote_exact_out(
+        request.amount_out,
+        request.is_swap_x_to_y,
+        &request.amm_config,
+        &request.pool_balances,
+    ))
+}
+
+async fn deposit_handler(request: Result<Json<DepositRequest>, JsonRejection>) -> ApiResult {
+    let Json(request) = request?;
+    if let Some(protocol_fee) = request.protocol_fee {
+        if request.imbalanced {
+            return Err(invalid_request("imbalanced deposits do not mint the protocol fee"));
+        }
+        return respond(deposit_lp_with_protocol_fee(
+            request.amount_x,
+            request.amount_y,
+            request.lp_supply,
+            &request.pool_balances,
+            protocol_fee.k_last,
+            protocol_fee.protocol_share,
+        ));
+    }
+    if !request.imbalanced {
+        return respond(deposit_lp_strict(
+            request.amount_x,
+            request.amount_y,
+            request.lp_supply,
+            &request.pool_balances,
+        ));
+    }
+
+    let amm_config = request
+        .amm_config
+        .ok_or_else(|| invalid_request("`amm_config` is required for imbalanced deposits"))?;
+    amm_config.validate()?;
+    respond(deposit_imbalanced(
+        request.amount_x,
+        request.amount_y,
+        request.lp_supply,
+        &request.pool_balances,
+        &amm_config,
+    ))
+}
+
+async fn withdraw_handler(request: Result<Json<WithdrawRequest>, JsonRejection>) -> ApiResult {
+    let Json(request) = request?;
+    if let Some(protocol_fee) = request.protocol_fee {
+        return respond(withdraw_lp_with_protocol_fee(
+            request.lp_tokens,
+            request.lp_supply,
+            &request.pool_balances,
+            protocol_fee.k_last,
+            protocol_fee.protocol_share,
+        ));
+    }
+    respond(withdraw_lp_from_pool(
+        request.lp_tokens,
+        request.lp_supply,
+        &request.pool_balances,
+    ))
+}
+
+async fn max_trade_handler(request: Result<Json<MaxTradeRequest>, JsonRejection>) -> ApiResult {
+    let Json(request) = request?;
+    request.amm_config.validate()?;
+    let (amm_config, balances) = (&request.amm_config, &request.pool_balances);
+    let max_exchange_in = max_exchange_in(request.is_swap_x_to_y, amm_config, balances)?;
+    let quote = quote_pool_balances(max_exchange_in, request.is_swap_x_to_y, amm_config, balances)?;
+    respond(Ok(MaxTradeResponse { max_exchange_in, quote }))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        axum::{body::Body, http::Request},
+        dex_math::{MathError, Ppm},
+        tower::ServiceExt,
+    };
+
+    async fn post_json(path: &str, body: String) -> (StatusCode, Value) {
+        let request = Request::post(path)
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .unwrap();
+        let response = router().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    fn pool(ratio_change_tolerance_rate: u64) -> Value {
+        let amm_config = AmmConfig::builder()
+            .trade_fee_rate(Ppm::new(3_000).unwrap())
+            .ratio_change_tolerance_rate(Ppm::new(ratio_change_tolerance_rate).unwrap())
+            .build()
+            .unwrap();
+        let pool_balances = PoolBalances {
+            reserve_x_balance: 1_000_000,
+            reserve_y_balance: 2_000_000,
+            ..Default::default()
+        };
+        json!({ "amm_config": amm_config, "pool_balances": pool_balances, "lp_supply": 1_000_000 })
+    }
+
+    fn with(mut pool: Value, fields: Value) -> String {
+        pool.as_object_mut().unwrap().extend(fields.as_object().unwrap().clone());
+        pool.to_string()
+    }
+
+    #[tokio::test]
+    async fn serves_quotes() {
+        let body = with(pool(1_000_000), json!({ "exchange_in": 10_000, "is_swap_x_to_y": true }));
+        let (status, quote) = post_json("/quote", body).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(quote["trade_fee"], 30);
+
+        let body = with(pool(1_000_000), json!({ "amount_out": 10_000, "is_swap_x_to_y": true }));
+        let (status, quote) = post_json("/quote-exact-out", body).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(quote["to_amount"].as_u64().unwrap() >= 10_000);
+
+        let (status, result) = post_json("/max-trade", with(pool(10_000), json!({ "is_swap_x_to_y": false }))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(result["max_exchange_in"].as_u64().unwrap() > 0);
+        assert!(result["quote"]["to_amount"].as_u64().unwrap() > 0);
+    }
+
+    #[tokio::test]
+    async fn serves_liquidity() {
+        let (status, deposit) = post_json("/deposit", with(pool(1_000_000), json!({ "amount_x": 1_000, "amount_y": 3_000 }))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(deposit["lp_minted"], 1_000);
+        assert_eq!(deposit["unused_y"], 1_000);
+
+        let body = with(pool(1_000_000), json!({ "amount_x": 1_000, "amount_y": 3_000, "imbalanced": true }));
+        let (status, deposit) = post_json("/deposit", body).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(deposit["lp_minted"].as_u64().unwrap() > 1_000);
+
+        let (status, withdrawal) = post_json("/withdraw", with(pool(1_000_000), json!({ "lp_tokens": 1_000 }))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((withdrawal["amount_x"].clone(), withdrawal["amount_y"].clone()), (json!(1_000), json!(2_000)));
+
+        // sqrt(k) grew by 10% since k_last, the protocol's share is minted first
+        let protocol_fee = json!({ "protocol_fee": { "k_last": "1652892561983", "protocol_share": 500_000 } });
+        let mut body: Value = serde_json::from_str(&with(pool(1_000_000), protocol_fee)).unwrap();
+        body["amount_x"] = json!(1_000);
+        body["amount_y"] = json!(2_000);
+        let (status, deposit) = post_json("/deposit", body.to_string()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(deposit["protocol_fee_lp"].as_u64().unwrap() > 0);
+        assert_eq!(deposit["unused_y"], 1);
+        assert_eq!(deposit["k_last"], "2004000999000");
+
+        body["lp_tokens"] = json!(1_000);
+        let (status, withdrawal) = post_json("/withdraw", body.to_string()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(withdrawal["protocol_fee_lp"], deposit["protocol_fee_lp"]);
+    }
+
+    #[tokio::test]
+    async fn maps_errors() {
+        let body = with(pool(1_000_000), json!({ "lp_tokens": 1_000_001 }));
+        let (status, error) = post_json("/withdraw", body).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error["error"]["code"], MathError::InsufficientLpSupply.code());
+        assert_eq!(error["error"]["name"], "MathLibInsufficientLpSupply");
+
+        let (status, error) = post_json("/withdraw", with(pool(1_000_000), json!({ "lp_tokens": "all" }))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error["error"]["name"], "InvalidRequest");
+
+        // the whole Y reserve can never be bought
+        let body = with(pool(1_000_000), json!({ "amount_out": 2_000_000, "is_swap_x_to_y": true }));
+        let (status, error) = post_json("/quote-exact-out", body).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error["error"]["code"], MathError::InsufficientPoolTokenYBalance.code());
+
+        let mut body = pool(1_000_000);
+        body["amm_config"]["trade_fee_rate"] = json!(1_000_000);
+        let (status, error) = post_json("/quote", with(body, json!({ "exchange_in": 1, "is_swap_x_to_y": true }))).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error["error"]["code"], MathError::InvalidTradeFeeRate.code());
+
+        let body = with(pool(1_000_000), json!({ "amount_x": 1_000, "amount_y": 3_000, "imbalanced": true }));
+        let mut body: Value = serde_json::from_str(&body).unwrap();
+        body.as_object_mut().unwrap().remove("amm_config");
+        let (status, _) = post_json("/deposit", body.to_string()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
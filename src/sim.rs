@@ -0,0 +1,538 @@
+//! In-memory pool simulation
+//!
+//! [`SimPool`] applies swaps, pending orders, deposits and withdrawals with the
+//! crate's math, records every step and checks after each one that:
+//!
+//! - fee buckets and locks never exceed the vault balances
+//! - swaps and settlements never decrease `k` of the available reserves
+//! - deposits and withdrawals never decrease `sqrt(k)` per LP token
+//!
+//! A step that fails, either in the math or an invariant, leaves the pool as
+//! it was.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    deposit_lp_strict, quote, quote_at, withdraw_lp_from_pool,
+    utils::{sqrt, sqrt_ceil},
+    AmmConfig, DepositQuote, FeeSide, LpWithdrawal, MathError, PoolBalances, QuoteOutput,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Operation {
+    /// Swap settled immediately
+    Swap { exchange_in: u64, is_swap_x_to_y: bool },
+    /// Quote a swap and lock its funds until it is settled or cancelled
+    PlaceOrder { exchange_in: u64, is_swap_x_to_y: bool },
+    SettleOrder { order_id: u64 },
+    CancelOrder { order_id: u64 },
+    /// Proportional deposit, the excess is refunded
+    Deposit { amount_x: u64, amount_y: u64 },
+    Withdraw { lp_tokens: u64 },
+    CollectProtocolFees,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PendingOrder {
+    pub order_id: u64,
+    pub is_swap_x_to_y: bool,
+    pub exchange_in: u64,
+    pub quote: QuoteOutput,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Outcome {
+    Swapped(QuoteOutput),
+    OrderPlaced(PendingOrder),
+    OrderSettled(PendingOrder),
+    OrderCancelled(PendingOrder),
+    Deposited(DepositQuote),
+    Withdrawn(LpWithdrawal),
+    ProtocolFeesCollected { amount_x: u64, amount_y: u64 },
+}
+
+/// An applied operation and the pool right after it
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Step {
+    pub operation: Operation,
+    pub outcome: Outcome,
+    pub pool_balances: PoolBalances,
+    pub lp_supply: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum InvariantViolation {
+    /// Fee buckets and locks add up to more than a vault holds
+    BucketsExceedVault,
+    /// A swap or settlement decreased `k` of the available reserves
+    CurveValueDecreased,
+    /// A deposit or withdrawal decreased `sqrt(k)` per LP token
+    LpValueDecreased,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SimError {
+    Math(MathError),
+    UnknownOrder(u64),
+    /// The step that broke the invariant, it was not applied
+    Invariant {
+        violation: InvariantViolation,
+        step: Box<Step>,
+    },
+}
+
+impl From<MathError> for SimError {
+    fn from(error: MathError) -> Self {
+        SimError::Math(error)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SimPool {
+    pub amm_config: AmmConfig,
+    pool_balances: PoolBalances,
+    lp_supply: u64,
+    orders: Vec<PendingOrder>,
+    next_order_id: u64,
+    history: Vec<Step>,
+    now: Option<u64>,
+}
+
+impl SimPool {
+    /// Pool seeded with `reserve_x` / `reserve_y`, minting `sqrt(x * y)` LP
+    /// tokens like the first [`crate::deposit_lp`]
+    pub fn new(amm_config: AmmConfig, reserve_x: u64, reserve_y: u64) -> Self {
+        SimPool {
+            amm_config,
+            pool_balances: PoolBalances {
+                reserve_x_balance: reserve_x,
+                reserve_y_balance: reserve_y,
+                ..PoolBalances::default()
+            },
+            lp_supply: sqrt(reserve_x as u128 * reserve_y as u128) as u64,
+            orders: Vec::new(),
+            next_order_id: 0,
+            history: Vec::new(),
+            now: None,
+        }
+    }
+
+    /// Quote at slot / timestamp `now` from here on, applying the launch fee
+    /// schedule like [`crate::quote_at`]
+    pub fn set_now(&mut self, now: u64) {
+        self.now = Some(now);
+    }
+
+    pub fn pool_balances(&self) -> &PoolBalances {
+        &self.pool_balances
+    }
+
+    pub fn lp_supply(&self) -> u64 {
+        self.lp_supply
+    }
+
+    pub fn pending_orders(&self) -> &[PendingOrder] {
+        &self.orders
+    }
+
+    pub fn history(&self) -> &[Step] {
+        &self.history
+    }
+
+    /// Apply `operation`, check the invariants and record the step
+    pub fn apply(&mut self, operation: Operation) -> Result<&Step, SimError> {
+        // work on a copy without the history, it can be long
+        let history = core::mem::take(&mut self.history);
+        let mut next = self.clone();
+        self.history = history;
+
+        let outcome = next.execute(operation)?;
+        let step = Step {
+            operation,
+            outcome,
+            pool_balances: next.pool_balances,
+            lp_supply: next.lp_supply,
+        };
+
+        if let Some(violation) = self.check_invariants(&next, &operation) {
+            return Err(SimError::Invariant {
+                violation,
+                step: Box::new(step),
+            });
+        }
+
+        self.pool_balances = next.pool_balances;
+        self.lp_supply = next.lp_supply;
+        self.orders = next.orders;
+        self.next_order_id = next.next_order_id;
+        self.history.push(step);
+        Ok(self.history.last().unwrap())
+    }
+
+    fn execute(&mut self, operation: Operation) -> Result<Outcome, SimError> {
+        match operation {
+            Operation::Swap {
+                exchange_in,
+                is_swap_x_to_y,
+            } => {
+                let quote = self.quote(exchange_in, is_swap_x_to_y)?;
+                let (source, _) = sides(&mut self.pool_balances, is_swap_x_to_y);
+                *source.reserve = checked_add(*source.reserve, exchange_in)?;
+                self.pay_out(is_swap_x_to_y, &quote)?;
+                Ok(Outcome::Swapped(quote))
+            }
+            Operation::PlaceOrder {
+                exchange_in,
+                is_swap_x_to_y,
+            } => {
+                let quote = self.quote(exchange_in, is_swap_x_to_y)?;
+                let to_amount_swapped = self.to_amount_swapped(&quote)?;
+                let (source, destination) = sides(&mut self.pool_balances, is_swap_x_to_y);
+                *source.reserve = checked_add(*source.reserve, exchange_in)?;
+                *source.user_locked = checked_add(*source.user_locked, exchange_in)?;
+                *source.locked = checked_add(*source.locked, quote.from_to_lock)?;
+                *destination.locked = checked_add(*destination.locked, to_amount_swapped)?;
+
+                let order = PendingOrder {
+                    order_id: self.next_order_id,
+                    is_swap_x_to_y,
+                    exchange_in,
+                    quote,
+                };
+                self.next_order_id += 1;
+                self.orders.push(order.clone());
+                Ok(Outcome::OrderPlaced(order))
+            }
+            Operation::SettleOrder { order_id } => {
+                let order = self.release_order(order_id)?;
+                self.pay_out(order.is_swap_x_to_y, &order.quote)?;
+                Ok(Outcome::OrderSettled(order))
+            }
+            Operation::CancelOrder { order_id } => {
+                let order = self.release_order(order_id)?;
+                let (source, _) = sides(&mut self.pool_balances, order.is_swap_x_to_y);
+                *source.reserve = checked_sub(*source.reserve, order.exchange_in)?;
+                Ok(Outcome::OrderCancelled(order))
+            }
+            Operation::Deposit { amount_x, amount_y } => {
+                let deposit = deposit_lp_strict(amount_x, amount_y, self.lp_supply, &self.pool_balances)?;
+                self.pool_balances.reserve_x_balance =
+                    checked_add(self.pool_balances.reserve_x_balance, amount_x - deposit.unused_x)?;
+                self.pool_balances.reserve_y_balance =
+                    checked_add(self.pool_balances.reserve_y_balance, amount_y - deposit.unused_y)?;
+                self.lp_supply = checked_add(self.lp_supply, deposit.lp_minted)?;
+                Ok(Outcome::Deposited(deposit))
+            }
+            Operation::Withdraw { lp_tokens } => {
+                let withdrawal = withdraw_lp_from_pool(lp_tokens, self.lp_supply, &self.pool_balances)?;
+                self.pool_balances = withdrawal.pool_balances;
+                self.lp_supply -= lp_tokens;
+                Ok(Outcome::Withdrawn(withdrawal))
+            }
+            Operation::CollectProtocolFees => {
+                let (amount_x, amount_y) = (self.pool_balances.protocol_fee_x, self.pool_balances.protocol_fee_y);
+                self.pool_balances.reserve_x_balance -= amount_x;
+                self.pool_balances.reserve_y_balance -= amount_y;
+                self.pool_balances.protocol_fee_x = 0;
+                self.pool_balances.protocol_fee_y = 0;
+                Ok(Outcome::ProtocolFeesCollected { amount_x, amount_y })
+            }
+        }
+    }
+
+    fn quote(&self, exchange_in: u64, is_swap_x_to_y: bool) -> Result<QuoteOutput, SimError> {
+        let pool_balances = &self.pool_balances;
+        let quote = match self.now {
+            Some(now) => quote_at(
+                now,
+                exchange_in,
+                is_swap_x_to_y,
+                &self.amm_config,
+                pool_balances.protocol_fee_x,
+                pool_balances.protocol_fee_y,
+                pool_balances.user_locked_x,
+                pool_balances.user_locked_y,
+                pool_balances.locked_x,
+                pool_balances.locked_y,
+                pool_balances.reserve_x_balance,
+                pool_balances.reserve_y_balance,
+                pool_balances.lp_fee_x,
+                pool_balances.lp_fee_y,
+            ),
+            None => quote(
+                exchange_in,
+                is_swap_x_to_y,
+                &self.amm_config,
+                pool_balances.protocol_fee_x,
+                pool_balances.protocol_fee_y,
+                pool_balances.user_locked_x,
+                pool_balances.user_locked_y,
+                pool_balances.locked_x,
+                pool_balances.locked_y,
+                pool_balances.reserve_x_balance,
+                pool_balances.reserve_y_balance,
+                pool_balances.lp_fee_x,
+                pool_balances.lp_fee_y,
+            ),
+        };
+        Ok(quote?)
+    }
+
+    /// Destination amount leaving the curve, fees on output included
+    fn to_amount_swapped(&self, quote: &QuoteOutput) -> Result<u64, SimError> {
+        match self.amm_config.fee_side {
+            FeeSide::Input => Ok(quote.to_amount),
+            FeeSide::Output => checked_add(quote.to_amount, quote.trade_fee),
+        }
+    }
+
+    /// Unlock an order's funds, its input stays in the vault
+    fn release_order(&mut self, order_id: u64) -> Result<PendingOrder, SimError> {
+        let index = self
+            .orders
+            .iter()
+            .position(|order| order.order_id == order_id)
+            .ok_or(SimError::UnknownOrder(order_id))?;
+        let order = self.orders.remove(index);
+        let to_amount_swapped = self.to_amount_swapped(&order.quote)?;
+
+        let (source, destination) = sides(&mut self.pool_balances, order.is_swap_x_to_y);
+        *source.user_locked = checked_sub(*source.user_locked, order.exchange_in)?;
+        *source.locked = checked_sub(*source.locked, order.quote.from_to_lock)?;
+        *destination.locked = checked_sub(*destination.locked, to_amount_swapped)?;
+        Ok(order)
+    }
+
+    /// Send the output and host fee out and move the protocol / LP shares of
+    /// the trade fee into their buckets
+    fn pay_out(&mut self, is_swap_x_to_y: bool, quote: &QuoteOutput) -> Result<(), SimError> {
+        let fee_side = self.amm_config.fee_side;
+        let (source, destination) = sides(&mut self.pool_balances, is_swap_x_to_y);
+        *destination.reserve = checked_sub(*destination.reserve, quote.to_amount)?;
+
+        let fee_token = match fee_side {
+            FeeSide::Input => source,
+            FeeSide::Output => destination,
+        };
+        let lp_fee = quote
+            .trade_fee
+            .checked_sub(quote.protocol_fee)
+            .and_then(|lp_fee| lp_fee.checked_sub(quote.host_fee))
+            .ok_or(MathError::MathOverflow)?;
+        *fee_token.reserve = checked_sub(*fee_token.reserve, quote.host_fee)?;
+        *fee_token.protocol_fee = checked_add(*fee_token.protocol_fee, quote.protocol_fee)?;
+        *fee_token.lp_fee = checked_add(*fee_token.lp_fee, lp_fee)?;
+        Ok(())
+    }
+
+    fn check_invariants(&self, next: &SimPool, operation: &Operation) -> Option<InvariantViolation> {
+        let Some((next_x, next_y)) = next.pool_balances.available() else {
+            return Some(InvariantViolation::BucketsExceedVault);
+        };
+        // the current state passed its own checks
+        let (available_x, available_y) = self.pool_balances.available().unwrap();
+        let k = available_x as u128 * available_y as u128;
+        let next_k = next_x as u128 * next_y as u128;
+
+        match operation {
+            Operation::Swap { .. } | Operation::SettleOrder { .. } if next_k < k => {
+                Some(InvariantViolation::CurveValueDecreased)
+            }
+            // sqrt(next_k) / next_supply < sqrt(k) / supply, rounded so only
+            // a certain decrease is reported
+            Operation::Deposit { .. } | Operation::Withdraw { .. }
+                if next.lp_supply > 0
+                    && sqrt_ceil(next_k) * (self.lp_supply as u128) < sqrt(k) * next.lp_supply as u128 =>
+            {
+                Some(InvariantViolation::LpValueDecreased)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// One side of the pool's balances
+struct SideBalances<'a> {
+    reserve: &'a mut u64,
+    protocol_fee: &'a mut u64,
+    user_locked: &'a mut u64,
+    locked: &'a mut u64,
+    lp_fee: &'a mut u64,
+}
+
+/// (source, destination) sides of a swap
+fn sides(pool_balances: &mut PoolBalances, is_swap_x_to_y: bool) -> (SideBalances<'_>, SideBalances<'_>) {
+    let PoolBalances {
+        reserve_x_balance,
+        reserve_y_balance,
+        protocol_fee_x,
+        protocol_fee_y,
+        user_locked_x,
+        user_locked_y,
+        locked_x,
+        locked_y,
+        lp_fee_x,
+        lp_fee_y,
+    } = pool_balances;
+    let x = SideBalances {
+        reserve: reserve_x_balance,
+        protocol_fee: protocol_fee_x,
+        user_locked: user_locked_x,
+        locked: locked_x,
+        lp_fee: lp_fee_x,
+    };
+    let y = SideBalances {
+        reserve: reserve_y_balance,
+        protocol_fee: protocol_fee_y,
+        user_locked: user_locked_y,
+        locked: locked_y,
+        lp_fee: lp_fee_y,
+    };
+    if is_swap_x_to_y {
+        (x, y)
+    } else {
+        (y, x)
+    }
+}
+
+fn checked_add(a: u64, b: u64) -> Result<u64, SimError> {
+    a.checked_add(b).ok_or(SimError::Math(MathError::MathOverflow))
+}
+
+fn checked_sub(a: u64, b: u64) -> Result<u64, SimError> {
+    a.checked_sub(b).ok_or(SimError::Math(MathError::MathOverflow))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{HostFeeSource, Ppm},
+        proptest::prelude::*,
+    };
+
+    fn amm_config(fee_side: FeeSide) -> AmmConfig {
+        AmmConfig::builder()
+            .trade_fee_rate(Ppm::new(3_000).unwrap())
+            .protocol_fee_rate(Ppm::new(200_000).unwrap())
+            .host_fee_rate(Ppm::new(100_000).unwrap(), HostFeeSource::TradeFee)
+            .ratio_change_tolerance_rate(Ppm::MAX)
+            .fee_side(fee_side)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn orders_lock_until_settled() {
+        let mut pool = SimPool::new(amm_config(FeeSide::Input), 1_000_000, 2_000_000);
+
+        let placed = pool
+            .apply(Operation::PlaceOrder {
+                exchange_in: 10_000,
+                is_swap_x_to_y: true,
+            })
+            .unwrap()
+            .clone();
+        let Outcome::OrderPlaced(order) = placed.outcome else {
+            panic!("expected a placed order");
+        };
+        let balances = *pool.pool_balances();
+        assert_eq!(balances.user_locked_x, 10_000);
+        assert_eq!(balances.locked_x, order.quote.from_to_lock);
+        assert_eq!(balances.locked_y, order.quote.to_amount);
+
+        pool.apply(Operation::SettleOrder { order_id: order.order_id }).unwrap();
+        let balances = *pool.pool_balances();
+        assert_eq!((balances.user_locked_x, balances.locked_x, balances.locked_y), (0, 0, 0));
+        assert_eq!(balances.reserve_y_balance, 2_000_000 - order.quote.to_amount);
+        assert_eq!(balances.protocol_fee_x, order.quote.protocol_fee);
+        assert_eq!(
+            balances.lp_fee_x,
+            order.quote.trade_fee - order.quote.protocol_fee - order.quote.host_fee
+        );
+
+        assert_eq!(
+            pool.apply(Operation::SettleOrder { order_id: order.order_id }),
+            Err(SimError::UnknownOrder(order.order_id))
+        );
+        assert_eq!(pool.history().len(), 2);
+    }
+
+    #[test]
+    fn cancelled_orders_refund_the_input() {
+        let mut pool = SimPool::new(amm_config(FeeSide::Output), 1_000_000, 2_000_000);
+        pool.apply(Operation::PlaceOrder {
+            exchange_in: 10_000,
+            is_swap_x_to_y: false,
+        })
+        .unwrap();
+        pool.apply(Operation::CancelOrder { order_id: 0 }).unwrap();
+
+        let balances = *pool.pool_balances();
+        assert_eq!(
+            balances,
+            PoolBalances {
+                reserve_x_balance: 1_000_000,
+                reserve_y_balance: 2_000_000,
+                ..PoolBalances::default()
+            }
+        );
+    }
+
+    #[test]
+    fn failed_steps_leave_the_pool_unchanged() {
+        let mut pool = SimPool::new(amm_config(FeeSide::Input), 1_000_000, 2_000_000);
+        let before = *pool.pool_balances();
+
+        assert!(matches!(pool.apply(Operation::Withdraw { lp_tokens: u64::MAX }), Err(SimError::Math(_))));
+        assert!(matches!(
+            pool.apply(Operation::Swap {
+                exchange_in: 0,
+                is_swap_x_to_y: true
+            }),
+            Err(SimError::Math(_))
+        ));
+        assert_eq!(*pool.pool_balances(), before);
+        assert!(pool.history().is_empty());
+    }
+
+    fn operation() -> impl Strategy<Value = Operation> {
+        prop_oneof![
+            (1..1_000_000u64, any::<bool>())
+                .prop_map(|(exchange_in, is_swap_x_to_y)| Operation::Swap { exchange_in, is_swap_x_to_y }),
+            (1..1_000_000u64, any::<bool>())
+                .prop_map(|(exchange_in, is_swap_x_to_y)| Operation::PlaceOrder { exchange_in, is_swap_x_to_y }),
+            (0..8u64).prop_map(|order_id| Operation::SettleOrder { order_id }),
+            (0..8u64).prop_map(|order_id| Operation::CancelOrder { order_id }),
+            (1..1_000_000u64, 1..1_000_000u64)
+                .prop_map(|(amount_x, amount_y)| Operation::Deposit { amount_x, amount_y }),
+            (1..1_000_000u64).prop_map(|lp_tokens| Operation::Withdraw { lp_tokens }),
+            Just(Operation::CollectProtocolFees),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn random_sequences_keep_the_invariants(
+            reserve_x in 1_000..10_000_000u64,
+            reserve_y in 1_000..10_000_000u64,
+            fee_on_output in any::<bool>(),
+            operations in prop::collection::vec(operation(), 1..40),
+        ) {
+            let fee_side = if fee_on_output { FeeSide::Output } else { FeeSide::Input };
+            let mut pool = SimPool::new(amm_config(fee_side), reserve_x, reserve_y);
+            for operation in operations {
+                match pool.apply(operation) {
+                    Ok(_) | Err(SimError::Math(_)) | Err(SimError::UnknownOrder(_)) => {}
+                    Err(error) => prop_assert!(false, "{:?}", error),
+                }
+            }
+        }
+    }
+}
@@ -1,34 +1,274 @@
+use alloc::vec::Vec;
+
+#[cfg(feature = "borsh")]
+use borsh::{BorshDeserialize, BorshSerialize};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    effective_fee_rate,
+    errors::{MathError, Result},
+    Amount, FeeAmount, FeeSchedule, Ppm, Side, SwapDirection,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct AmmConfig {
-    pub trade_fee_rate: u64,    // 10^6 = 100%
-    pub protocol_fee_rate: u64, // 10^6 = 100% (precentage of trade fee)
-    pub ratio_change_tolerance_rate: u64, // 10^6 = 100%
+    pub trade_fee_rate: Ppm,
+    pub protocol_fee_rate: Ppm, // precentage of trade fee
+    pub ratio_change_tolerance_rate: Ppm,
+
+    /// Referral cut for routers / wallets, a percentage of `host_fee_source`
+    pub host_fee_rate: Ppm,
+    pub host_fee_source: HostFeeSource,
+
+    /// Per-direction overrides of `trade_fee_rate` / `protocol_fee_rate`
+    pub directional_fee_rates: Option<DirectionalFeeRates>,
+
+    /// Launch fee decaying to the trade fee rate, only honoured by [`crate::quote_at`]
+    pub launch_fee_schedule: Option<FeeSchedule>,
+
+    /// Token the trade fee is charged in
+    pub fee_side: FeeSide,
 }
 
-pub struct SwapResultWithFromToLock {
-    pub from_amount: u64,
-    pub to_amount: u64,
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum FeeSide {
+    /// Fees are deducted from the input before it hits the curve
+    #[default]
+    Input,
+    /// The curve runs on the full input and fees are deducted from the output,
+    /// e.g. to accrue fees in the quote asset
+    Output,
+}
 
-    pub trade_fee: u64,
-    pub protocol_fee: u64,
-    pub from_to_lock: u64,
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FeeRates {
+    pub trade_fee_rate: Ppm,
+    pub protocol_fee_rate: Ppm, // precentage of trade fee
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DirectionalFeeRates {
+    pub x_to_y: FeeRates,
+    pub y_to_x: FeeRates,
+}
+
+/// Which fee the host fee is carved out of
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum HostFeeSource {
+    /// Taken from the trade fee next to the protocol fee, reducing the LP share
+    #[default]
+    TradeFee,
+    /// Taken from the protocol fee, leaving the LP share untouched
+    ProtocolFee,
+}
+
+impl AmmConfig {
+    pub fn builder() -> AmmConfigBuilder {
+        AmmConfigBuilder::default()
+    }
+
+    /// Fee rates applied to a swap in `direction`
+    pub fn fee_rates(&self, direction: SwapDirection) -> FeeRates {
+        match (self.directional_fee_rates, direction) {
+            (Some(rates), SwapDirection::XToY) => rates.x_to_y,
+            (Some(rates), SwapDirection::YToX) => rates.y_to_x,
+            (None, _) => FeeRates {
+                trade_fee_rate: self.trade_fee_rate,
+                protocol_fee_rate: self.protocol_fee_rate,
+            },
+        }
+    }
+
+    /// Fee rates applied to a swap in `direction` at slot / timestamp `now`
+    pub fn fee_rates_at(&self, direction: SwapDirection, now: u64) -> FeeRates {
+        let fee_rates = self.fee_rates(direction);
+        match &self.launch_fee_schedule {
+            Some(schedule) => FeeRates {
+                trade_fee_rate: effective_fee_rate(schedule, fee_rates.trade_fee_rate, now),
+                ..fee_rates
+            },
+            None => fee_rates,
+        }
+    }
+
+    /// Check the rates of a config that did not come through the builder,
+    /// e.g. one deserialized from an account
+    pub fn validate(&self) -> Result<()> {
+        self.validate_fee_rates(self.fee_rates(SwapDirection::XToY))?;
+        self.validate_fee_rates(self.fee_rates(SwapDirection::YToX))?;
+        // the base rates stay valid even when both directions override them
+        self.validate_fee_rates(FeeRates {
+            trade_fee_rate: self.trade_fee_rate,
+            protocol_fee_rate: self.protocol_fee_rate,
+        })?;
+
+        if let Some(schedule) = &self.launch_fee_schedule {
+            if schedule.start_fee_rate >= Ppm::MAX {
+                return Err(MathError::InvalidTradeFeeRate);
+            }
+        }
+
+        if self.ratio_change_tolerance_rate == Ppm::ZERO
+            || self.ratio_change_tolerance_rate > Ppm::MAX
+        {
+            return Err(MathError::InvalidRatioChangeToleranceRate);
+        }
+        Ok(())
+    }
+
+    fn validate_fee_rates(&self, fee_rates: FeeRates) -> Result<()> {
+        // a 100% trade fee would swallow every input
+        if fee_rates.trade_fee_rate >= Ppm::MAX {
+            return Err(MathError::InvalidTradeFeeRate);
+        }
+        if fee_rates.protocol_fee_rate > Ppm::MAX {
+            return Err(MathError::InvalidProtocolFeeRate);
+        }
+        if self.host_fee_rate > Ppm::MAX {
+            return Err(MathError::InvalidHostFeeRate);
+        }
+        // both slices of the trade fee must fit in it
+        if self.host_fee_source == HostFeeSource::TradeFee
+            && self.host_fee_rate.get() + fee_rates.protocol_fee_rate.get() > Ppm::MAX.get()
+        {
+            return Err(MathError::InvalidHostFeeRate);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct AmmConfigBuilder {
+    trade_fee_rate: Ppm,
+    protocol_fee_rate: Ppm,
+    ratio_change_tolerance_rate: Ppm,
+    host_fee_rate: Ppm,
+    host_fee_source: HostFeeSource,
+    x_to_y_fee_rates: Option<FeeRates>,
+    y_to_x_fee_rates: Option<FeeRates>,
+    launch_fee_schedule: Option<FeeSchedule>,
+    fee_side: FeeSide,
+}
+
+impl AmmConfigBuilder {
+    /// Accepts either [`Ppm`] or [`crate::Bps`]
+    pub fn trade_fee_rate(mut self, rate: impl Into<Ppm>) -> Self {
+        self.trade_fee_rate = rate.into();
+        self
+    }
+
+    pub fn protocol_fee_rate(mut self, rate: impl Into<Ppm>) -> Self {
+        self.protocol_fee_rate = rate.into();
+        self
+    }
+
+    pub fn ratio_change_tolerance_rate(mut self, rate: impl Into<Ppm>) -> Self {
+        self.ratio_change_tolerance_rate = rate.into();
+        self
+    }
+
+    pub fn host_fee_rate(mut self, rate: impl Into<Ppm>, source: HostFeeSource) -> Self {
+        self.host_fee_rate = rate.into();
+        self.host_fee_source = source;
+        self
+    }
+
+    /// Override the fee rates of one direction; the other keeps the base rates
+    pub fn direction_fee_rates(
+        mut self,
+        direction: SwapDirection,
+        trade_fee_rate: impl Into<Ppm>,
+        protocol_fee_rate: impl Into<Ppm>,
+    ) -> Self {
+        let fee_rates = Some(FeeRates {
+            trade_fee_rate: trade_fee_rate.into(),
+            protocol_fee_rate: protocol_fee_rate.into(),
+        });
+        match direction {
+            SwapDirection::XToY => self.x_to_y_fee_rates = fee_rates,
+            SwapDirection::YToX => self.y_to_x_fee_rates = fee_rates,
+        }
+        self
+    }
+
+    pub fn launch_fee_schedule(mut self, schedule: FeeSchedule) -> Self {
+        self.launch_fee_schedule = Some(schedule);
+        self
+    }
+
+    pub fn fee_side(mut self, fee_side: FeeSide) -> Self {
+        self.fee_side = fee_side;
+        self
+    }
+
+    pub fn build(self) -> Result<AmmConfig> {
+        let base_fee_rates = FeeRates {
+            trade_fee_rate: self.trade_fee_rate,
+            protocol_fee_rate: self.protocol_fee_rate,
+        };
+        let directional_fee_rates = match (self.x_to_y_fee_rates, self.y_to_x_fee_rates) {
+            (None, None) => None,
+            (x_to_y, y_to_x) => Some(DirectionalFeeRates {
+                x_to_y: x_to_y.unwrap_or(base_fee_rates),
+                y_to_x: y_to_x.unwrap_or(base_fee_rates),
+            }),
+        };
+
+        let amm_config = AmmConfig {
+            trade_fee_rate: self.trade_fee_rate,
+            protocol_fee_rate: self.protocol_fee_rate,
+            ratio_change_tolerance_rate: self.ratio_change_tolerance_rate,
+            host_fee_rate: self.host_fee_rate,
+            host_fee_source: self.host_fee_source,
+            directional_fee_rates,
+            launch_fee_schedule: self.launch_fee_schedule,
+            fee_side: self.fee_side,
+        };
+        amm_config.validate()?;
+        Ok(amm_config)
+    }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct QuoteOutput {
     // post trade fees
     pub from_amount: u64,
     pub to_amount: u64,
 
+    /// Trade fee rate applied to this quote
+    pub trade_fee_rate: Ppm,
+
+    // in the destination token when `AmmConfig::fee_side` is `FeeSide::Output`
     pub trade_fee: u64,
     pub protocol_fee: u64,
+    pub host_fee: u64,
     pub from_to_lock: u64,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct RebalanceResult {
     pub from_to_lock: u64,
     pub is_rate_tolerance_exceeded: bool,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct SwapResult {
     /// Amount of source token swapped
     pub from_amount: u64,
@@ -36,5 +276,442 @@ pub struct SwapResult {
     pub to_amount: u64,
 
     pub trade_fee: u64,
+    /// After the host fee is carved out, see [`crate::split_host_fee`]
+    pub protocol_fee: u64,
+    /// Part of the trade fee paid out to the host, not kept by the pool
+    pub host_fee: u64,
+}
+
+/// [`SwapResult`] with the source side `S` carried in the type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TypedSwapResult<S: Side> {
+    pub from_amount: Amount<S>,
+    pub to_amount: Amount<S::Other>,
+
+    pub trade_fee: FeeAmount<S>,
+    pub protocol_fee: FeeAmount<S>,
+    pub host_fee: FeeAmount<S>,
+}
+
+impl<S: Side> From<TypedSwapResult<S>> for SwapResult {
+    fn from(result: TypedSwapResult<S>) -> Self {
+        SwapResult {
+            from_amount: result.from_amount.get(),
+            to_amount: result.to_amount.get(),
+            trade_fee: result.trade_fee.get(),
+            protocol_fee: result.protocol_fee.get(),
+            host_fee: result.host_fee.get(),
+        }
+    }
+}
+
+/// [`RebalanceResult`] with the source side `S` carried in the type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TypedRebalanceResult<S: Side> {
+    pub from_to_lock: Amount<S>,
+    pub is_rate_tolerance_exceeded: bool,
+}
+
+impl<S: Side> From<TypedRebalanceResult<S>> for RebalanceResult {
+    fn from(result: TypedRebalanceResult<S>) -> Self {
+        RebalanceResult {
+            from_to_lock: result.from_to_lock.get(),
+            is_rate_tolerance_exceeded: result.is_rate_tolerance_exceeded,
+        }
+    }
+}
+
+/// [`QuoteOutput`] with the source side `S` carried in the type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TypedQuoteOutput<S: Side> {
+    // post trade fees
+    pub from_amount: Amount<S>,
+    pub to_amount: Amount<S::Other>,
+
+    pub trade_fee_rate: Ppm,
+
+    pub trade_fee: FeeAmount<S>,
+    pub protocol_fee: FeeAmount<S>,
+    pub host_fee: FeeAmount<S>,
+    pub from_to_lock: Amount<S>,
+}
+
+impl<S: Side> From<TypedQuoteOutput<S>> for QuoteOutput {
+    fn from(output: TypedQuoteOutput<S>) -> Self {
+        QuoteOutput {
+            from_amount: output.from_amount.get(),
+            to_amount: output.to_amount.get(),
+            trade_fee_rate: output.trade_fee_rate,
+            trade_fee: output.trade_fee.get(),
+            protocol_fee: output.protocol_fee.get(),
+            host_fee: output.host_fee.get(),
+            from_to_lock: output.from_to_lock.get(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BatchOrder {
+    /// Amount of source token offered, before trade fees
+    pub amount_in: u64,
+    pub is_swap_x_to_y: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchFill {
+    /// Amount of source token swapped (post trade fees)
+    pub from_amount: u64,
+    /// Amount of destination token received
+    pub to_amount: u64,
+
+    pub trade_fee: u64,
+    /// After the host fee is carved out, see [`crate::split_host_fee`]
     pub protocol_fee: u64,
-}
\ No newline at end of file
+    /// Part of the trade fee paid out to the host, not kept by the pool
+    pub host_fee: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchResult {
+    /// Fills in the same order as the submitted orders
+    pub fills: Vec<BatchFill>,
+
+    /// Uniform clearing price as Y per X (numerator / denominator)
+    pub price_numerator: u128,
+    pub price_denominator: u128,
+
+    // resulting available reserves, excluding fees
+    pub reserve_x: u64,
+    pub reserve_y: u64,
+
+    pub protocol_fee_x: u64,
+    pub protocol_fee_y: u64,
+    pub lp_fee_x: u64,
+    pub lp_fee_y: u64,
+    // paid out to the host, in neither the buckets nor the reserves
+    pub host_fee_x: u64,
+    pub host_fee_y: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FlashSwapResult {
+    // resulting available reserves, excluding fees
+    pub reserve_x: u64,
+    pub reserve_y: u64,
+
+    pub trade_fee_x: u64,
+    pub trade_fee_y: u64,
+    pub protocol_fee_x: u64,
+    pub protocol_fee_y: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FlashLoanResult {
+    /// Borrowed amount plus `flash_fee`
+    pub min_repayment: u64,
+
+    pub flash_fee: u64,
+    pub protocol_fee: u64,
+}
+
+/// Balance breakdown of a pool's token vaults
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
+pub struct PoolBalances {
+    /// The total balance of X in the pool
+    pub reserve_x_balance: u64,
+    /// The total balance of Y in the pool
+    pub reserve_y_balance: u64,
+
+    pub protocol_fee_x: u64,
+    pub protocol_fee_y: u64,
+    /// User funds of pending orders
+    pub user_locked_x: u64,
+    pub user_locked_y: u64,
+    /// Pool funds reserved for pending orders
+    pub locked_x: u64,
+    pub locked_y: u64,
+    pub lp_fee_x: u64,
+    pub lp_fee_y: u64,
+}
+
+impl PoolBalances {
+    /// Reserves excluding protocol fees, user pending orders and LP fees
+    pub fn total(&self) -> Option<(u64, u64)> {
+        Some((
+            self.reserve_x_balance
+                .checked_sub(self.protocol_fee_x)?
+                .checked_sub(self.user_locked_x)?
+                .checked_sub(self.lp_fee_x)?,
+            self.reserve_y_balance
+                .checked_sub(self.protocol_fee_y)?
+                .checked_sub(self.user_locked_y)?
+                .checked_sub(self.lp_fee_y)?,
+        ))
+    }
+
+    /// [`PoolBalances::total`] also excluding pool funds locked for pending orders
+    pub fn available(&self) -> Option<(u64, u64)> {
+        let (total_x, total_y) = self.total()?;
+        Some((
+            total_x.checked_sub(self.locked_x)?,
+            total_y.checked_sub(self.locked_y)?,
+        ))
+    }
+
+    /// [`PoolBalances::available`] plus the LP fees, what the LP tokens are
+    /// paid out of
+    pub fn lp_owned(&self) -> Option<(u64, u64)> {
+        let (available_x, available_y) = self.available()?;
+        Some((
+            available_x.checked_add(self.lp_fee_x)?,
+            available_y.checked_add(self.lp_fee_y)?,
+        ))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DepositQuote {
+    pub amount_x: u64,
+    pub amount_y: u64,
+
+    /// LP tokens `deposit_lp` mints for `amount_x` / `amount_y`
+    pub lp_minted: u64,
+    /// Parts of `amount_x` / `amount_y` not backing any minted LP
+    pub unused_x: u64,
+    pub unused_y: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LpWithdrawal {
+    /// Total paid out, including `lp_fee_x` / `lp_fee_y`
+    pub amount_x: u64,
+    pub amount_y: u64,
+
+    /// Part of the payout taken from the LP fee buckets
+    pub lp_fee_x: u64,
+    pub lp_fee_y: u64,
+
+    /// Pool balances once the payout has left the vaults
+    pub pool_balances: PoolBalances,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ExactWithdrawal {
+    pub amount_x: u64,
+    pub amount_y: u64,
+
+    /// LP tokens to burn for `amount_x` / `amount_y`, including the imbalance fee
+    pub lp_tokens_burned: u64,
+
+    /// Trade fee on the part of the withdrawal that is not proportional to the
+    /// pool, left in the pool
+    pub imbalance_fee_x: u64,
+    pub imbalance_fee_y: u64,
+
+    /// Part of the imbalance fee going to the protocol
+    pub protocol_fee_x: u64,
+    pub protocol_fee_y: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ImbalancedDeposit {
+    pub amount_x: u64,
+    pub amount_y: u64,
+
+    /// LP tokens minted for the full deposit, net of the imbalance fee
+    pub lp_minted: u64,
+
+    /// Trade fee on the part of the deposit that is not proportional to the
+    /// pool, left in the pool
+    pub imbalance_fee_x: u64,
+    pub imbalance_fee_y: u64,
+
+    /// Part of the imbalance fee going to the protocol
+    pub protocol_fee_x: u64,
+    pub protocol_fee_y: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ProtocolFeeDeposit {
+    /// LP tokens minted to the depositor
+    pub lp_minted: u64,
+    /// Parts of `amount_x` / `amount_y` not backing any minted LP
+    pub unused_x: u64,
+    pub unused_y: u64,
+    /// LP tokens minted to the protocol before the deposit
+    pub protocol_fee_lp: u64,
+    /// Product of the LP-owned balances after the deposit, to store for the
+    /// next event
+    #[cfg_attr(feature = "serde", serde(with = "u128_string"))]
+    pub k_last: u128,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ProtocolFeeWithdrawal {
+    /// Total paid out, including `lp_fee_x` / `lp_fee_y`
+    pub amount_x: u64,
+    pub amount_y: u64,
+    /// Part of the payout taken from the LP fee buckets
+    pub lp_fee_x: u64,
+    pub lp_fee_y: u64,
+    /// LP tokens minted to the protocol before the withdrawal
+    pub protocol_fee_lp: u64,
+    /// Product of the LP-owned balances after the withdrawal, to store for the
+    /// next event
+    #[cfg_attr(feature = "serde", serde(with = "u128_string"))]
+    pub k_last: u128,
+}
+
+/// `#[serde(with)]` module writing a `u128` as a decimal string, JSON numbers
+/// past `u64` don't survive most parsers (or `serde_json::Value`); integers
+/// are accepted when reading
+#[cfg(feature = "serde")]
+pub mod u128_string {
+    use core::fmt;
+
+    use serde::{de, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &u128, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(value)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u128, D::Error> {
+        struct Visitor;
+
+        impl de::Visitor<'_> for Visitor {
+            type Value = u128;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a u128 as a decimal string or an integer")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<u128, E> {
+                value.parse().map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<u128, E> {
+                Ok(value.into())
+            }
+
+            fn visit_u128<E: de::Error>(self, value: u128) -> Result<u128, E> {
+                Ok(value)
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+
+    /// The same for an `Option<u128>`
+    pub mod option {
+        use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+        struct Decimal(u128);
+
+        impl Serialize for Decimal {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                super::serialize(&self.0, serializer)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for Decimal {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                super::deserialize(deserializer).map(Decimal)
+            }
+        }
+
+        pub fn serialize<S: Serializer>(value: &Option<u128>, serializer: S) -> Result<S::Ok, S::Error> {
+            value.map(Decimal).serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u128>, D::Error> {
+            Ok(Option::<Decimal>::deserialize(deserializer)?.map(|value| value.0))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> AmmConfig {
+        AmmConfig::builder()
+            .trade_fee_rate(Ppm::new(3_000).unwrap())
+            .protocol_fee_rate(Ppm::new(200_000).unwrap())
+            .ratio_change_tolerance_rate(Ppm::new(10_000).unwrap())
+            .build()
+            .unwrap()
+    }
+
+    #[cfg(feature = "borsh")]
+    #[test]
+    fn validate_rejects_invalid_rates() {
+        assert!(config().validate().is_ok());
+
+        // out of range values can only arrive through deserialization
+        let out_of_range: Ppm = BorshDeserialize::try_from_slice(&(crate::MAX_PERCENTAGE + 1).to_le_bytes()).unwrap();
+
+        let mut amm_config = config();
+        amm_config.trade_fee_rate = out_of_range;
+        assert_eq!(amm_config.validate(), Err(MathError::InvalidTradeFeeRate));
+
+        let mut amm_config = config();
+        amm_config.protocol_fee_rate = out_of_range;
+        assert_eq!(amm_config.validate(), Err(MathError::InvalidProtocolFeeRate));
+
+        let mut amm_config = config();
+        amm_config.ratio_change_tolerance_rate = Ppm::ZERO;
+        assert_eq!(amm_config.validate(), Err(MathError::InvalidRatioChangeToleranceRate));
+    }
+
+    #[test]
+    fn directional_fee_rates_fall_back_to_base_rates() {
+        let amm_config = config();
+        assert_eq!(amm_config.fee_rates(SwapDirection::XToY), amm_config.fee_rates(SwapDirection::YToX));
+
+        let amm_config = AmmConfig::builder()
+            .trade_fee_rate(Ppm::new(3_000).unwrap())
+            .protocol_fee_rate(Ppm::new(200_000).unwrap())
+            .ratio_change_tolerance_rate(Ppm::new(10_000).unwrap())
+            .direction_fee_rates(SwapDirection::YToX, Ppm::new(50_000).unwrap(), Ppm::new(100_000).unwrap())
+            .build()
+            .unwrap();
+        assert_eq!(amm_config.fee_rates(SwapDirection::XToY).trade_fee_rate.get(), 3_000);
+        assert_eq!(amm_config.fee_rates(SwapDirection::YToX).trade_fee_rate.get(), 50_000);
+        assert_eq!(amm_config.fee_rates(SwapDirection::YToX).protocol_fee_rate.get(), 100_000);
+
+        let result = AmmConfig::builder()
+            .ratio_change_tolerance_rate(Ppm::new(10_000).unwrap())
+            .direction_fee_rates(SwapDirection::XToY, Ppm::MAX, Ppm::ZERO)
+            .build();
+        assert_eq!(result.map(|_| ()), Err(MathError::InvalidTradeFeeRate));
+    }
+
+    #[cfg(feature = "borsh")]
+    #[test]
+    fn amm_config_borsh_round_trip() {
+        let amm_config = config();
+        let bytes = amm_config.try_to_vec().unwrap();
+
+        // four u64 rates, two one byte enum tags and two one byte option tags
+        assert_eq!(bytes.len(), 36);
+        assert_eq!(AmmConfig::try_from_slice(&bytes).unwrap(), amm_config);
+    }
+}
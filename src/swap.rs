@@ -1,7 +1,14 @@
-use crate::{rebalance_pool_ratio, state::{QuoteOutput, SwapResultWithFromToLock}, swap, AmmConfig, ErrorCode};
-use anchor_lang::prelude::{Result, err};
+use crate::{
+    errors::{MathError, Result},
+    rebalance_pool_ratio_typed, split_host_fee,
+    state::{FeeRates, FeeSide, PoolBalances, QuoteOutput, TypedQuoteOutput},
+    swap_base_output, swap_typed, AmmConfig, Amount, FeeAmount, Side, SwapDirection, X, Y,
+};
 
 /// Quote the output amount for a given input amount
+///
+/// Charges the base or directional fee rates; a `launch_fee_schedule` in
+/// `amm_config` needs the current slot / timestamp, see [`quote_at`].
 /// 
 /// # Arguments
 /// * `exchange_in` - The amount of input tokens after transfer fees
@@ -17,7 +24,7 @@ use anchor_lang::prelude::{Result, err};
 /// * `reserve_y_balance` - The total balance of Y in the pool
 /// * `lp_fee_x` - The accumulated LP fee balance for X
 /// * `lp_fee_y` - The accumulated LP fee balance for Y
-
+#[allow(clippy::too_many_arguments)]
 pub fn quote(
     exchange_in: u64,
     is_swap_x_to_y: bool,
@@ -33,138 +40,616 @@ pub fn quote(
     lp_fee_x: u64,
     lp_fee_y: u64,
 ) -> Result<QuoteOutput> {
-    // exclude protocol fees / locked pool reserves / user pending orders
-    let (total_token_x_amount, total_token_y_amount) = (
-        reserve_x_balance
-            .checked_sub(protocol_fee_x)
-            .unwrap()
-            .checked_sub(user_locked_x)
-            .unwrap()
-            .checked_sub(lp_fee_x)
-            .unwrap(),
-        reserve_y_balance
-            .checked_sub(protocol_fee_y)
-            .unwrap()
-            .checked_sub(user_locked_y)
-            .unwrap()
-            .checked_sub(lp_fee_y)
-            .unwrap(),
-    );
-
-    let (available_token_x_amount, available_token_y_amount) = (
-        total_token_x_amount
-            .checked_sub(locked_x)
-            .unwrap(),
-        total_token_y_amount
-            .checked_sub(locked_y)
-            .unwrap(),
-    );
+    let direction = SwapDirection::from(is_swap_x_to_y);
+    quote_with_fee_rates(
+        exchange_in,
+        direction,
+        amm_config,
+        amm_config.fee_rates(direction),
+        protocol_fee_x,
+        protocol_fee_y,
+        user_locked_x,
+        user_locked_y,
+        locked_x,
+        locked_y,
+        reserve_x_balance,
+        reserve_y_balance,
+        lp_fee_x,
+        lp_fee_y,
+    )
+}
 
-    // the amount we receive excluding any outside transfer fees
-    if exchange_in == 0 {
-        return err!(ErrorCode::MathLibInputAmountTooSmall);
+/// Quote the output amount for a given input amount at slot / timestamp `now`
+///
+/// Same as [`quote`], applying the launch fee schedule of `amm_config` if it
+/// has one. The rate used is reported in `trade_fee_rate`.
+#[allow(clippy::too_many_arguments)]
+pub fn quote_at(
+    now: u64,
+    exchange_in: u64,
+    is_swap_x_to_y: bool,
+    amm_config: &AmmConfig,
+    protocol_fee_x: u64,
+    protocol_fee_y: u64,
+    user_locked_x: u64,
+    user_locked_y: u64,
+    locked_x: u64,
+    locked_y: u64,
+    reserve_x_balance: u64,
+    reserve_y_balance: u64,
+    lp_fee_x: u64,
+    lp_fee_y: u64,
+) -> Result<QuoteOutput> {
+    let direction = SwapDirection::from(is_swap_x_to_y);
+    quote_with_fee_rates(
+        exchange_in,
+        direction,
+        amm_config,
+        amm_config.fee_rates_at(direction, now),
+        protocol_fee_x,
+        protocol_fee_y,
+        user_locked_x,
+        user_locked_y,
+        locked_x,
+        locked_y,
+        reserve_x_balance,
+        reserve_y_balance,
+        lp_fee_x,
+        lp_fee_y,
+    )
+}
+
+/// Quote the input needed to receive at least `amount_out`
+///
+/// The input is found with [`swap_base_output`] on the available reserves and
+/// then quoted, so the result is what [`quote`] returns for it: `to_amount` is
+/// at least `amount_out`, and `from_to_lock` / tolerance are checked the same way.
+pub fn quote_exact_out(
+    amount_out: u64,
+    is_swap_x_to_y: bool,
+    amm_config: &AmmConfig,
+    pool_balances: &PoolBalances,
+) -> Result<QuoteOutput> {
+    if amount_out == 0 {
+        return Err(MathError::InputAmountTooSmall);
     }
-    // Calculate the output amount using the constant product formula
-    let result_amounts: SwapResultWithFromToLock = if is_swap_x_to_y {
-        // Swap X to Y
-
-        let result_amounts = swap(
-            exchange_in as u128,
-            available_token_x_amount as u128,
-            available_token_y_amount as u128,
-            amm_config.trade_fee_rate,
-            amm_config.protocol_fee_rate,
-        )
-        .ok_or(ErrorCode::MathLibMathOverflow)?;
-
-        let rebalance_result = rebalance_pool_ratio(
-            result_amounts.to_amount,
-            available_token_x_amount,
-            available_token_y_amount,
-            total_token_x_amount,
-            total_token_y_amount,
-            amm_config.ratio_change_tolerance_rate,
-        )
-        .ok_or(ErrorCode::MathLibMathOverflow)?;
 
-        if rebalance_result.is_rate_tolerance_exceeded {
-            return err!(ErrorCode::MathLibTradeTooBig);
-        }
+    let direction = SwapDirection::from(is_swap_x_to_y);
+    let (available_x, available_y) = pool_balances
+        .available()
+        .ok_or(MathError::MathOverflow)?;
+    let (available_source, available_destination, insufficient_destination) = match direction {
+        SwapDirection::XToY => (available_x, available_y, Y::INSUFFICIENT_BALANCE),
+        SwapDirection::YToX => (available_y, available_x, X::INSUFFICIENT_BALANCE),
+    };
+    if amount_out >= available_destination {
+        return Err(insufficient_destination);
+    }
 
-        // can't reserve to 0 or negative
-        if rebalance_result.from_to_lock >= available_token_x_amount {
-            return err!(ErrorCode::MathLibInsufficientPoolTokenXBalance);
-        }
+    let fee_rates = amm_config.fee_rates(direction);
+    let swap_result = swap_base_output(
+        amount_out as u128,
+        available_source as u128,
+        available_destination as u128,
+        fee_rates.trade_fee_rate,
+        fee_rates.protocol_fee_rate,
+        amm_config.fee_side,
+    )
+    .ok_or(MathError::MathOverflow)?;
+
+    let exchange_in = match amm_config.fee_side {
+        FeeSide::Input => swap_result.from_amount.checked_add(swap_result.trade_fee),
+        FeeSide::Output => Some(swap_result.from_amount),
+    }
+    .ok_or(MathError::MathOverflow)?;
+
+    quote_pool_balances(exchange_in, is_swap_x_to_y, amm_config, pool_balances)
+}
 
-        SwapResultWithFromToLock {
-            from_amount: result_amounts.from_amount, // applied trade fee + transfer fee
-            to_amount: result_amounts.to_amount,     // nothing applied
-            from_to_lock: rebalance_result.from_to_lock,
-            trade_fee: result_amounts.trade_fee,
-            protocol_fee: result_amounts.protocol_fee,
+/// Largest input [`quote`] accepts, limited by the ratio change tolerance and
+/// the available source balance
+pub fn max_exchange_in(
+    is_swap_x_to_y: bool,
+    amm_config: &AmmConfig,
+    pool_balances: &PoolBalances,
+) -> Result<u64> {
+    // quote does not guard against an empty source side
+    let (available_x, available_y) = pool_balances
+        .available()
+        .ok_or(MathError::MathOverflow)?;
+    if available_x == 0 {
+        return Err(MathError::InsufficientPoolTokenXBalance);
+    }
+    if available_y == 0 {
+        return Err(MathError::InsufficientPoolTokenYBalance);
+    }
+
+    // binary search for the last accepted input
+    let (mut accepted, mut rejected) = (0u64, u64::MAX);
+    while rejected - accepted > 1 {
+        let exchange_in = accepted + (rejected - accepted) / 2;
+        match quote_pool_balances(exchange_in, is_swap_x_to_y, amm_config, pool_balances) {
+            Ok(_) => accepted = exchange_in,
+            Err(_) => rejected = exchange_in,
         }
-    } else {
-        // Swap Y to X
-        let result_amounts = swap(
-            exchange_in as u128,
-            available_token_y_amount as u128,
-            available_token_x_amount as u128,
-            amm_config.trade_fee_rate,
-            amm_config.protocol_fee_rate,
+    }
+
+    if accepted == 0 {
+        // report why even the smallest trade fails
+        quote_pool_balances(1, is_swap_x_to_y, amm_config, pool_balances)?;
+        return Err(MathError::TradeTooBig);
+    }
+    Ok(accepted)
+}
+
+/// [`quote`] on the balances of `pool_balances`
+pub fn quote_pool_balances(
+    exchange_in: u64,
+    is_swap_x_to_y: bool,
+    amm_config: &AmmConfig,
+    pool_balances: &PoolBalances,
+) -> Result<QuoteOutput> {
+    quote(
+        exchange_in,
+        is_swap_x_to_y,
+        amm_config,
+        pool_balances.protocol_fee_x,
+        pool_balances.protocol_fee_y,
+        pool_balances.user_locked_x,
+        pool_balances.user_locked_y,
+        pool_balances.locked_x,
+        pool_balances.locked_y,
+        pool_balances.reserve_x_balance,
+        pool_balances.reserve_y_balance,
+        pool_balances.lp_fee_x,
+        pool_balances.lp_fee_y,
+    )
+}
+
+/// [`quote_at`] on the balances of `pool_balances`
+pub fn quote_pool_balances_at(
+    now: u64,
+    exchange_in: u64,
+    is_swap_x_to_y: bool,
+    amm_config: &AmmConfig,
+    pool_balances: &PoolBalances,
+) -> Result<QuoteOutput> {
+    quote_at(
+        now,
+        exchange_in,
+        is_swap_x_to_y,
+        amm_config,
+        pool_balances.protocol_fee_x,
+        pool_balances.protocol_fee_y,
+        pool_balances.user_locked_x,
+        pool_balances.user_locked_y,
+        pool_balances.locked_x,
+        pool_balances.locked_y,
+        pool_balances.reserve_x_balance,
+        pool_balances.reserve_y_balance,
+        pool_balances.lp_fee_x,
+        pool_balances.lp_fee_y,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn quote_with_fee_rates(
+    exchange_in: u64,
+    direction: SwapDirection,
+    amm_config: &AmmConfig,
+    fee_rates: FeeRates,
+    protocol_fee_x: u64,
+    protocol_fee_y: u64,
+    user_locked_x: u64,
+    user_locked_y: u64,
+    locked_x: u64,
+    locked_y: u64,
+    reserve_x_balance: u64,
+    reserve_y_balance: u64,
+    lp_fee_x: u64,
+    lp_fee_y: u64,
+) -> Result<QuoteOutput> {
+    match direction {
+        SwapDirection::XToY => quote_typed_with_fee_rates::<X>(
+            Amount::new(exchange_in),
+            amm_config,
+            fee_rates,
+            Amount::new(protocol_fee_x),
+            Amount::new(protocol_fee_y),
+            Amount::new(user_locked_x),
+            Amount::new(user_locked_y),
+            Amount::new(locked_x),
+            Amount::new(locked_y),
+            Amount::new(reserve_x_balance),
+            Amount::new(reserve_y_balance),
+            Amount::new(lp_fee_x),
+            Amount::new(lp_fee_y),
         )
-        .ok_or(ErrorCode::MathLibMathOverflow)?;
-
-        let rebalance_result = rebalance_pool_ratio(
-            result_amounts.to_amount,
-            available_token_y_amount,
-            available_token_x_amount,
-            total_token_y_amount,
-            total_token_x_amount,
-            amm_config.ratio_change_tolerance_rate,
+        .map(QuoteOutput::from),
+        SwapDirection::YToX => quote_typed_with_fee_rates::<Y>(
+            Amount::new(exchange_in),
+            amm_config,
+            fee_rates,
+            Amount::new(protocol_fee_x),
+            Amount::new(protocol_fee_y),
+            Amount::new(user_locked_x),
+            Amount::new(user_locked_y),
+            Amount::new(locked_x),
+            Amount::new(locked_y),
+            Amount::new(reserve_x_balance),
+            Amount::new(reserve_y_balance),
+            Amount::new(lp_fee_x),
+            Amount::new(lp_fee_y),
         )
-        .ok_or(ErrorCode::MathLibMathOverflow)?;
+        .map(QuoteOutput::from),
+    }
+}
 
-        if rebalance_result.is_rate_tolerance_exceeded {
-            return err!(ErrorCode::MathLibTradeTooBig);
-        }
+/// Quote the output amount for a given input amount of side `S`
+///
+/// Same as [`quote`], with the direction given by the type of `exchange_in`
+/// and every pool balance tagged with its side.
+#[allow(clippy::too_many_arguments)]
+pub fn quote_typed<S: Side>(
+    exchange_in: Amount<S>,
+    amm_config: &AmmConfig,
+    protocol_fee_x: Amount<X>,
+    protocol_fee_y: Amount<Y>,
+    user_locked_x: Amount<X>,
+    user_locked_y: Amount<Y>,
+    locked_x: Amount<X>,
+    locked_y: Amount<Y>,
+    reserve_x_balance: Amount<X>,
+    reserve_y_balance: Amount<Y>,
+    lp_fee_x: Amount<X>,
+    lp_fee_y: Amount<Y>,
+) -> Result<TypedQuoteOutput<S>> {
+    quote_typed_with_fee_rates(
+        exchange_in,
+        amm_config,
+        amm_config.fee_rates(S::DIRECTION),
+        protocol_fee_x,
+        protocol_fee_y,
+        user_locked_x,
+        user_locked_y,
+        locked_x,
+        locked_y,
+        reserve_x_balance,
+        reserve_y_balance,
+        lp_fee_x,
+        lp_fee_y,
+    )
+}
 
-        // can't reserve to 0 or negative
-        if rebalance_result.from_to_lock > available_token_y_amount {
-            return err!(ErrorCode::MathLibInsufficientPoolTokenYBalance);
-        }
+#[allow(clippy::too_many_arguments)]
+fn quote_typed_with_fee_rates<S: Side>(
+    exchange_in: Amount<S>,
+    amm_config: &AmmConfig,
+    fee_rates: FeeRates,
+    protocol_fee_x: Amount<X>,
+    protocol_fee_y: Amount<Y>,
+    user_locked_x: Amount<X>,
+    user_locked_y: Amount<Y>,
+    locked_x: Amount<X>,
+    locked_y: Amount<Y>,
+    reserve_x_balance: Amount<X>,
+    reserve_y_balance: Amount<Y>,
+    lp_fee_x: Amount<X>,
+    lp_fee_y: Amount<Y>,
+) -> Result<TypedQuoteOutput<S>> {
+    // exclude protocol fees / locked pool reserves / user pending orders
+    let pool_balances = PoolBalances {
+        protocol_fee_x: protocol_fee_x.into(),
+        protocol_fee_y: protocol_fee_y.into(),
+        user_locked_x: user_locked_x.into(),
+        user_locked_y: user_locked_y.into(),
+        locked_x: locked_x.into(),
+        locked_y: locked_y.into(),
+        reserve_x_balance: reserve_x_balance.into(),
+        reserve_y_balance: reserve_y_balance.into(),
+        lp_fee_x: lp_fee_x.into(),
+        lp_fee_y: lp_fee_y.into(),
+    };
+    let (total_token_x_amount, total_token_y_amount) =
+        pool_balances.total().ok_or(MathError::MathOverflow)?;
+    let (available_token_x_amount, available_token_y_amount) =
+        pool_balances.available().ok_or(MathError::MathOverflow)?;
 
-        SwapResultWithFromToLock {
-            from_amount: result_amounts.from_amount, // applied trade fee + transfer fee
-            to_amount: result_amounts.to_amount,     // nothing applied
-            from_to_lock: rebalance_result.from_to_lock,
-            trade_fee: result_amounts.trade_fee,
-            protocol_fee: result_amounts.protocol_fee,
-        }
+    // the amount we receive excluding any outside transfer fees
+    if exchange_in == Amount::ZERO {
+        return Err(MathError::InputAmountTooSmall);
+    }
+
+    let (available_source_amount, available_destination_amount) =
+        S::split(Amount::<X>::new(available_token_x_amount), Amount::<Y>::new(available_token_y_amount));
+    let (total_source_amount, total_destination_amount) =
+        S::split(Amount::<X>::new(total_token_x_amount), Amount::<Y>::new(total_token_y_amount));
+
+    // Calculate the output amount using the constant product formula
+    let result_amounts = swap_typed(
+        exchange_in,
+        available_source_amount,
+        available_destination_amount,
+        fee_rates.trade_fee_rate,
+        fee_rates.protocol_fee_rate,
+        amm_config.fee_side,
+    )
+    .ok_or(MathError::MathOverflow)?;
+
+    // fees taken from the output still leave the curve reserves
+    let to_amount_swapped = match result_amounts.trade_fee {
+        FeeAmount::Source(_) => result_amounts.to_amount,
+        FeeAmount::Destination(trade_fee) => result_amounts
+            .to_amount
+            .checked_add(trade_fee)
+            .ok_or(MathError::MathOverflow)?,
     };
 
-    Ok(QuoteOutput {
-        from_amount: result_amounts.from_amount,
-        to_amount: result_amounts.to_amount,
+    let rebalance_result = rebalance_pool_ratio_typed(
+        to_amount_swapped,
+        available_source_amount,
+        available_destination_amount,
+        total_source_amount,
+        total_destination_amount,
+        amm_config.ratio_change_tolerance_rate,
+    )
+    .ok_or(MathError::MathOverflow)?;
+
+    if rebalance_result.is_rate_tolerance_exceeded {
+        return Err(MathError::TradeTooBig);
+    }
+
+    // can't reserve to 0 or negative
+    if rebalance_result.from_to_lock >= available_source_amount {
+        return Err(S::INSUFFICIENT_BALANCE);
+    }
+
+    let (protocol_fee, host_fee) = split_host_fee(
+        result_amounts.trade_fee.get().into(),
+        result_amounts.protocol_fee.get().into(),
+        amm_config.host_fee_rate,
+        amm_config.host_fee_source,
+    )
+    .ok_or(MathError::MathOverflow)?;
+
+    Ok(TypedQuoteOutput {
+        from_amount: result_amounts.from_amount, // applied trade fee + transfer fee
+        to_amount: result_amounts.to_amount,     // nothing applied
+        trade_fee_rate: fee_rates.trade_fee_rate,
         trade_fee: result_amounts.trade_fee,
-        protocol_fee: result_amounts.protocol_fee,
-        from_to_lock: result_amounts.from_to_lock,
+        protocol_fee: FeeAmount::new(protocol_fee as u64, amm_config.fee_side),
+        host_fee: FeeAmount::new(host_fee as u64, amm_config.fee_side),
+        from_to_lock: rebalance_result.from_to_lock,
     })
 }
 
 
 #[cfg(test)]
 mod tests {
-    use super::*;
-
-    // #[test]
-    // fn test_quote_basic() {
-    //     let result = quote(100, 1000, 2000);
-    //     assert_eq!(result, 181); // 100 * 2000 / (1000 + 100) = 200000 / 1100 ≈ 181
-    // }
-
-    // #[test]
-    // fn test_quote_zero_reserves() {
-    //     assert_eq!(quote(100, 0, 2000), 0);
-    //     assert_eq!(quote(100, 1000, 0), 0);
-    // }
+    use {super::*, crate::{FeeDecay, FeeSchedule, FeeSide, HostFeeSource, Ppm}};
+
+    fn no_fee_config() -> AmmConfig {
+        AmmConfig::builder()
+            .ratio_change_tolerance_rate(Ppm::MAX)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_quote_basic() {
+        let result = quote(100, true, &no_fee_config(), 0, 0, 0, 0, 0, 0, 1000, 2000, 0, 0).unwrap();
+        assert_eq!(result.to_amount, 181); // 100 * 2000 / (1000 + 100) = 200000 / 1100 ≈ 181
+    }
+
+    #[test]
+    fn test_quote_rejects_balances_exceeding_reserves() {
+        let config = no_fee_config();
+        assert_eq!(quote(1, true, &config, 20, 0, 0, 0, 0, 0, 10, 100, 0, 0), Err(MathError::MathOverflow));
+        assert_eq!(quote(1, false, &config, 0, 0, 0, 0, 0, 101, 10, 100, 0, 0), Err(MathError::MathOverflow));
+    }
+
+    #[test]
+    fn test_quote_lock_stops_short_of_available_source() {
+        // 1000 Y in takes 500 of the 1000 available X; locked_x puts the
+        // original ratio at 1 Y : 500 X, so the rebalance locks all but one of
+        // the available Y. The rebalance never returns a lock of all of it, so
+        // `>=` only rejects unreachable locks
+        let result = quote(1_000, false, &no_fee_config(), 0, 0, 0, 0, 499_000, 0, 500_000, 1_000, 0, 0).unwrap();
+        assert_eq!((result.to_amount, result.from_to_lock), (500, 999));
+
+        let typed = quote_typed(
+            Amount::<Y>::new(1_000),
+            &no_fee_config(),
+            Amount::new(0),
+            Amount::new(0),
+            Amount::new(0),
+            Amount::new(0),
+            Amount::new(499_000),
+            Amount::new(0),
+            Amount::new(500_000),
+            Amount::new(1_000),
+            Amount::new(0),
+            Amount::new(0),
+        )
+        .unwrap();
+        assert_eq!(typed.from_to_lock, Amount::new(999));
+
+        // the same pool mirrored for X->Y
+        let result = quote(1_000, true, &no_fee_config(), 0, 0, 0, 0, 0, 499_000, 1_000, 500_000, 0, 0).unwrap();
+        assert_eq!((result.to_amount, result.from_to_lock), (500, 999));
+    }
+
+    #[test]
+    fn test_quote_zero_reserves() {
+        assert!(quote(100, true, &no_fee_config(), 0, 0, 0, 0, 0, 0, 0, 2000, 0, 0).is_err());
+        assert!(quote(100, true, &no_fee_config(), 0, 0, 0, 0, 0, 0, 1000, 0, 0, 0).is_err());
+
+        // the trade fee takes the whole input, nothing reaches the empty curve
+        let amm_config = AmmConfig::builder()
+            .trade_fee_rate(Ppm::new(3_000).unwrap())
+            .ratio_change_tolerance_rate(Ppm::MAX)
+            .build()
+            .unwrap();
+        assert!(quote(1, true, &amm_config, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0).is_err());
+    }
+
+    #[test]
+    fn test_quote_typed_matches_raw() {
+        let amm_config = AmmConfig::builder()
+            .trade_fee_rate(Ppm::new(3_000).unwrap())
+            .protocol_fee_rate(Ppm::new(200_000).unwrap())
+            .ratio_change_tolerance_rate(Ppm::new(10_000).unwrap())
+            .build()
+            .unwrap();
+
+        let raw = quote(1_000, false, &amm_config, 10, 20, 30, 40, 50, 60, 1_000_000, 2_000_000, 70, 80).unwrap();
+        let typed = quote_typed(
+            Amount::<Y>::new(1_000),
+            &amm_config,
+            Amount::new(10),
+            Amount::new(20),
+            Amount::new(30),
+            Amount::new(40),
+            Amount::new(50),
+            Amount::new(60),
+            Amount::new(1_000_000),
+            Amount::new(2_000_000),
+            Amount::new(70),
+            Amount::new(80),
+        )
+        .unwrap();
+
+        let to_amount: Amount<X> = typed.to_amount;
+        assert_eq!(to_amount.get(), raw.to_amount);
+        assert_eq!(typed.from_amount.get(), raw.from_amount);
+        assert_eq!(typed.trade_fee.get(), raw.trade_fee);
+        assert_eq!(typed.protocol_fee.get(), raw.protocol_fee);
+        assert_eq!(typed.from_to_lock.get(), raw.from_to_lock);
+    }
+
+    #[test]
+    fn test_quote_host_fee() {
+        let amm_config = |source| {
+            AmmConfig::builder()
+                .trade_fee_rate(Ppm::new(10_000).unwrap())
+                .protocol_fee_rate(Ppm::new(200_000).unwrap())
+                .ratio_change_tolerance_rate(Ppm::MAX)
+                .host_fee_rate(Ppm::new(100_000).unwrap(), source)
+                .build()
+                .unwrap()
+        };
+
+        // 1% of 100_000 = 1_000 trade fee, 200 of it protocol
+        let result = quote(100_000, true, &amm_config(HostFeeSource::TradeFee), 0, 0, 0, 0, 0, 0, 10_000_000, 10_000_000, 0, 0).unwrap();
+        assert_eq!((result.trade_fee, result.protocol_fee, result.host_fee), (1_000, 200, 100));
+
+        let result = quote(100_000, true, &amm_config(HostFeeSource::ProtocolFee), 0, 0, 0, 0, 0, 0, 10_000_000, 10_000_000, 0, 0).unwrap();
+        assert_eq!((result.trade_fee, result.protocol_fee, result.host_fee), (1_000, 180, 20));
+    }
+
+    #[test]
+    fn test_quote_directional_fees() {
+        let amm_config = AmmConfig::builder()
+            .trade_fee_rate(Ppm::new(3_000).unwrap())
+            .ratio_change_tolerance_rate(Ppm::MAX)
+            .direction_fee_rates(SwapDirection::YToX, Ppm::new(50_000).unwrap(), Ppm::ZERO)
+            .build()
+            .unwrap();
+
+        let buy = quote(100_000, true, &amm_config, 0, 0, 0, 0, 0, 0, 10_000_000, 10_000_000, 0, 0).unwrap();
+        let sell = quote(100_000, false, &amm_config, 0, 0, 0, 0, 0, 0, 10_000_000, 10_000_000, 0, 0).unwrap();
+        assert_eq!(buy.trade_fee, 300);
+        assert_eq!(sell.trade_fee, 5_000);
+    }
+
+    #[test]
+    fn test_quote_at_applies_launch_fee() {
+        let amm_config = AmmConfig::builder()
+            .trade_fee_rate(Ppm::new(3_000).unwrap())
+            .ratio_change_tolerance_rate(Ppm::MAX)
+            .launch_fee_schedule(FeeSchedule {
+                start_fee_rate: Ppm::new(500_000).unwrap(),
+                start: 1_000,
+                duration: 100,
+                decay: FeeDecay::Linear,
+            })
+            .build()
+            .unwrap();
+
+        let at_launch = quote_at(1_000, 100_000, true, &amm_config, 0, 0, 0, 0, 0, 0, 10_000_000, 10_000_000, 0, 0).unwrap();
+        assert_eq!(at_launch.trade_fee_rate.get(), 500_000);
+        assert_eq!(at_launch.trade_fee, 50_000);
+
+        let after_launch = quote_at(1_100, 100_000, true, &amm_config, 0, 0, 0, 0, 0, 0, 10_000_000, 10_000_000, 0, 0).unwrap();
+        assert_eq!(after_launch.trade_fee_rate.get(), 3_000);
+
+        // plain quote ignores the schedule
+        let result = quote(100_000, true, &amm_config, 0, 0, 0, 0, 0, 0, 10_000_000, 10_000_000, 0, 0).unwrap();
+        assert_eq!(result.trade_fee, 300);
+    }
+
+    #[test]
+    fn test_quote_fee_on_output() {
+        let amm_config = AmmConfig::builder()
+            .trade_fee_rate(Ppm::new(10_000).unwrap())
+            .ratio_change_tolerance_rate(Ppm::MAX)
+            .fee_side(FeeSide::Output)
+            .build()
+            .unwrap();
+
+        let result = quote(100_000, true, &amm_config, 0, 0, 0, 0, 0, 0, 10_000_000, 10_000_000, 0, 0).unwrap();
+        // curve: 100_000 * 10m / 10.1m = 99_009, 1% fee rounded up
+        assert_eq!(result.from_amount, 100_000);
+        assert_eq!(result.trade_fee, 991);
+        assert_eq!(result.to_amount, 99_009 - 991);
+    }
+
+    #[test]
+    fn quote_exact_out_delivers_at_least_the_amount() {
+        let pool_balances = PoolBalances {
+            reserve_x_balance: 1_000_000,
+            reserve_y_balance: 2_000_000,
+            ..PoolBalances::default()
+        };
+        for fee_side in [FeeSide::Input, FeeSide::Output] {
+            let amm_config = AmmConfig::builder()
+                .trade_fee_rate(Ppm::new(3_000).unwrap())
+                .ratio_change_tolerance_rate(Ppm::MAX)
+                .fee_side(fee_side)
+                .build()
+                .unwrap();
+
+            let result = quote_exact_out(10_000, true, &amm_config, &pool_balances).unwrap();
+            assert!(result.to_amount >= 10_000);
+            // one less input falls short
+            let exchange_in = match fee_side {
+                FeeSide::Input => result.from_amount + result.trade_fee,
+                FeeSide::Output => result.from_amount,
+            };
+            let short = quote(exchange_in - 1, true, &amm_config, 0, 0, 0, 0, 0, 0, 1_000_000, 2_000_000, 0, 0).unwrap();
+            assert!(short.to_amount < 10_000);
+        }
+
+        assert!(quote_exact_out(2_000_000, true, &no_fee_config(), &pool_balances).is_err());
+    }
+
+    #[test]
+    fn max_exchange_in_is_the_last_accepted_input() {
+        let pool_balances = PoolBalances {
+            reserve_x_balance: 1_000_000,
+            reserve_y_balance: 2_000_000,
+            ..PoolBalances::default()
+        };
+        let amm_config = AmmConfig::builder()
+            .trade_fee_rate(Ppm::new(3_000).unwrap())
+            .ratio_change_tolerance_rate(Ppm::new(10_000).unwrap())
+            .build()
+            .unwrap();
+
+        let max = max_exchange_in(true, &amm_config, &pool_balances).unwrap();
+        assert!(quote_pool_balances(max, true, &amm_config, &pool_balances).is_ok());
+        assert!(quote_pool_balances(max + 1, true, &amm_config, &pool_balances).is_err());
+
+        assert!(max_exchange_in(true, &amm_config, &PoolBalances::default()).is_err());
+    }
 }
@@ -1,37 +1,95 @@
-use crate::{state::SwapResult, RebalanceResult, MAX_PERCENTAGE};
+use crate::{
+    state::{FeeSide, HostFeeSource, SwapResult, TypedRebalanceResult, TypedSwapResult},
+    Amount, FeeAmount, Ppm, RebalanceResult, Side, MAX_PERCENTAGE,
+};
 
 
-fn ceil_div(token_amount: u128, fee_numerator: u128, fee_denominator: u128) -> Option<u128> {
+pub(crate) fn ceil_div(token_amount: u128, fee_numerator: u128, fee_denominator: u128) -> Option<u128> {
     token_amount
-        .checked_mul(u128::from(fee_numerator))
-        .unwrap()
+        .checked_mul(fee_numerator)?
         .checked_add(fee_denominator)?
         .checked_sub(1)?
         .checked_div(fee_denominator)
 }
 
 pub fn floor_div(token_amount: u128, fee_numerator: u128, fee_denominator: u128) -> Option<u128> {
-    Some(
-        token_amount
-            .checked_mul(fee_numerator)?
-            .checked_div(fee_denominator)?,
-    )
+    token_amount
+        .checked_mul(fee_numerator)?
+        .checked_div(fee_denominator)
+}
+
+/// `a * b / c` rounded down, with a 256-bit intermediate product
+///
+/// `None` if `c` is zero or the result does not fit in `u128`.
+pub(crate) fn mul_div_floor(a: u128, b: u128, c: u128) -> Option<u128> {
+    if c == 0 {
+        return None;
+    }
+    let (high, low) = widening_mul(a, b);
+    if high >= c {
+        return None;
+    }
+
+    // binary long division of (high, low) by c, remainder stays below c
+    let mut remainder = high;
+    let mut quotient = 0u128;
+    for bit in (0..u128::BITS).rev() {
+        let carry = remainder >> 127;
+        remainder = (remainder << 1) | ((low >> bit) & 1);
+        quotient <<= 1;
+        if carry == 1 || remainder >= c {
+            remainder = remainder.wrapping_sub(c);
+            quotient |= 1;
+        }
+    }
+    Some(quotient)
 }
 
+/// Full 256-bit product as (high, low)
+pub(crate) fn widening_mul(a: u128, b: u128) -> (u128, u128) {
+    let (a_low, a_high) = (a as u64 as u128, a >> 64);
+    let (b_low, b_high) = (b as u64 as u128, b >> 64);
+
+    let (middle, middle_carry) = (a_low * b_high).overflowing_add(a_high * b_low);
+    let (low, low_carry) = (a_low * b_low).overflowing_add(middle << 64);
+    let high = a_high * b_high + (middle >> 64) + ((middle_carry as u128) << 64) + low_carry as u128;
+    (high, low)
+}
+
+/// Integer square root, rounded down
+pub(crate) fn sqrt(value: u128) -> u128 {
+    if value < 2 {
+        return value;
+    }
+    // Newton's method from a power of two above the root
+    let mut root = 1u128 << (u128::BITS - value.leading_zeros()).div_ceil(2);
+    loop {
+        let next = (root + value / root) / 2;
+        if next >= root {
+            return root;
+        }
+        root = next;
+    }
+}
+
+/// Integer square root, rounded up
+pub(crate) fn sqrt_ceil(value: u128) -> u128 {
+    let root = sqrt(value);
+    if root * root < value {
+        root + 1
+    } else {
+        root
+    }
+}
+
+/// Raw-rate form of [`Ppm::apply_ceil`], `None` if the rate exceeds 100%
 pub fn get_trade_fee(amount: u128, trade_fee_rate: u64) -> Option<u128> {
-    ceil_div(
-        amount,
-        u128::from(trade_fee_rate),
-        u128::from(MAX_PERCENTAGE),
-    )
+    Ppm::new(trade_fee_rate).ok()?.apply_ceil(amount)
 }
 
+/// Raw-rate form of [`Ppm::apply_floor`], `None` if the rate exceeds 100%
 pub fn get_protocol_fee(amount: u128, protocol_fee_rate: u64) -> Option<u128> {
-    floor_div(
-        amount,
-        u128::from(protocol_fee_rate),
-        u128::from(MAX_PERCENTAGE),
-    )
+    Ppm::new(protocol_fee_rate).ok()?.apply_floor(amount)
 }
 
 pub fn swap_base_input_without_fees(
@@ -43,25 +101,90 @@ pub fn swap_base_input_without_fees(
     // delta_y = (delta_x * y) / (x + delta_x)
     let numerator = source_amount.checked_mul(swap_destination_amount).unwrap();
     let denominator = swap_source_amount.checked_add(source_amount).unwrap();
-    let destination_amount_swapped = numerator.checked_div(denominator).unwrap();
-    destination_amount_swapped
+    if denominator == 0 {
+        // nothing swapped into an empty pool
+        return 0;
+    }
+    numerator.checked_div(denominator).unwrap()
 }
 
+/// Source amount needed to take `destination_amount` out of the pool
+///
+/// Rounded up, `None` if the pool does not hold more than `destination_amount`
+pub fn swap_base_output_without_fees(
+    destination_amount: u128,
+    swap_source_amount: u128,
+    swap_destination_amount: u128,
+) -> Option<u128> {
+    // (x + delta_x) * (y - delta_y) = x * y
+    // delta_x = (x * delta_y) / (y - delta_y)
+    let numerator = swap_source_amount.checked_mul(destination_amount)?;
+    let denominator = swap_destination_amount.checked_sub(destination_amount)?;
+    if denominator == 0 {
+        return None;
+    }
+    ceil_div(numerator, 1, denominator)
+}
+
+/// Smallest amount that is still `amount_post_fees` once the trade fee is
+/// taken from it
+///
+/// `amount - ceil(amount * rate)` is `floor(amount * (1 - rate))`, so the
+/// ceiling of the exact inverse is already the smallest such amount.
+pub(crate) fn amount_before_fees(amount_post_fees: u128, trade_fee_rate: Ppm) -> Option<u128> {
+    let fee_complement = MAX_PERCENTAGE.checked_sub(trade_fee_rate.get())?;
+    if fee_complement == 0 {
+        return None;
+    }
+    ceil_div(
+        amount_post_fees,
+        u128::from(MAX_PERCENTAGE),
+        u128::from(fee_complement),
+    )
+}
+
+
+/// Carve the host fee out of the trade fee or the protocol fee
+///
+/// Returns `(protocol_fee, host_fee)`. Whatever is left of `trade_fee` after
+/// both goes to LPs, so the three slices always sum to `trade_fee`.
+pub fn split_host_fee(
+    trade_fee: u128,
+    protocol_fee: u128,
+    host_fee_rate: Ppm,
+    host_fee_source: HostFeeSource,
+) -> Option<(u128, u128)> {
+    match host_fee_source {
+        HostFeeSource::TradeFee => {
+            let host_fee = host_fee_rate.apply_floor(trade_fee)?;
+            // only reachable with an unvalidated config
+            if host_fee.checked_add(protocol_fee)? > trade_fee {
+                return None;
+            }
+            Some((protocol_fee, host_fee))
+        }
+        HostFeeSource::ProtocolFee => {
+            let host_fee = host_fee_rate.apply_floor(protocol_fee)?;
+            Some((protocol_fee.checked_sub(host_fee)?, host_fee))
+        }
+    }
+}
 
 /// This is guaranteed to work for all values such that:
 ///  - 1 <= swap_source_amount * swap_destination_amount <= u128::MAX
 ///  - 1 <= source_amount <= u64::MAX
+///
 /// dev: invariant is increased due to ceil_div
 /// dev: because of ceil_div the destination_amount_swapped is rounded down
 pub fn swap(
     source_amount: u128,
     pool_source_amount: u128,
     pool_destination_amount: u128,
-    trade_fee_rate: u64,
-    protocol_fee_rate: u64,
+    trade_fee_rate: Ppm,
+    protocol_fee_rate: Ppm,
 ) -> Option<SwapResult> {
-    let trade_fee = get_trade_fee(source_amount, trade_fee_rate).unwrap();
-    let protocol_fee = get_protocol_fee(trade_fee, protocol_fee_rate).unwrap();
+    let trade_fee = trade_fee_rate.apply_ceil(source_amount)?;
+    let protocol_fee = protocol_fee_rate.apply_floor(trade_fee)?;
 
     let source_amount_post_fees = source_amount.checked_sub(trade_fee).unwrap();
 
@@ -76,6 +199,144 @@ pub fn swap(
         to_amount: destination_amount_swapped as u64,
         trade_fee: trade_fee as u64,
         protocol_fee: protocol_fee as u64,
+        host_fee: 0,
+    })
+}
+
+/// Same as [`swap`] but the curve runs on the full `source_amount` and the
+/// fees are taken from the destination amount
+///
+/// `from_amount` is the whole `source_amount`; `to_amount`, `trade_fee` and
+/// `protocol_fee` are in the destination token.
+pub fn swap_fee_on_output(
+    source_amount: u128,
+    pool_source_amount: u128,
+    pool_destination_amount: u128,
+    trade_fee_rate: Ppm,
+    protocol_fee_rate: Ppm,
+) -> Option<SwapResult> {
+    let destination_amount_swapped = swap_base_input_without_fees(
+        source_amount,
+        pool_source_amount,
+        pool_destination_amount,
+    );
+
+    let trade_fee = trade_fee_rate.apply_ceil(destination_amount_swapped)?;
+    let protocol_fee = protocol_fee_rate.apply_floor(trade_fee)?;
+
+    let destination_amount_post_fees = destination_amount_swapped.checked_sub(trade_fee)?;
+
+    Some(SwapResult {
+        from_amount: source_amount as u64,
+        to_amount: destination_amount_post_fees as u64,
+        trade_fee: trade_fee as u64,
+        protocol_fee: protocol_fee as u64,
+        host_fee: 0,
+    })
+}
+
+/// [`swap`] or [`swap_fee_on_output`], depending on `fee_side`
+pub fn swap_with_fee_side(
+    source_amount: u128,
+    pool_source_amount: u128,
+    pool_destination_amount: u128,
+    trade_fee_rate: Ppm,
+    protocol_fee_rate: Ppm,
+    fee_side: FeeSide,
+) -> Option<SwapResult> {
+    match fee_side {
+        FeeSide::Input => swap(
+            source_amount,
+            pool_source_amount,
+            pool_destination_amount,
+            trade_fee_rate,
+            protocol_fee_rate,
+        ),
+        FeeSide::Output => swap_fee_on_output(
+            source_amount,
+            pool_source_amount,
+            pool_destination_amount,
+            trade_fee_rate,
+            protocol_fee_rate,
+        ),
+    }
+}
+
+/// Exact-out counterpart of [`swap_with_fee_side`]
+///
+/// Finds the smallest source amount that delivers `destination_amount` to the
+/// user after fees. `to_amount` is exactly `destination_amount`, the source
+/// amount paid is `from_amount + trade_fee` for fees on input and
+/// `from_amount` for fees on output.
+pub fn swap_base_output(
+    destination_amount: u128,
+    pool_source_amount: u128,
+    pool_destination_amount: u128,
+    trade_fee_rate: Ppm,
+    protocol_fee_rate: Ppm,
+    fee_side: FeeSide,
+) -> Option<SwapResult> {
+    let (from_amount, trade_fee) = match fee_side {
+        FeeSide::Input => {
+            let source_amount_post_fees = swap_base_output_without_fees(
+                destination_amount,
+                pool_source_amount,
+                pool_destination_amount,
+            )?;
+            let source_amount = amount_before_fees(source_amount_post_fees, trade_fee_rate)?;
+            (
+                source_amount_post_fees,
+                source_amount.checked_sub(source_amount_post_fees)?,
+            )
+        }
+        FeeSide::Output => {
+            let destination_amount_swapped = amount_before_fees(destination_amount, trade_fee_rate)?;
+            let source_amount = swap_base_output_without_fees(
+                destination_amount_swapped,
+                pool_source_amount,
+                pool_destination_amount,
+            )?;
+            (
+                source_amount,
+                destination_amount_swapped.checked_sub(destination_amount)?,
+            )
+        }
+    };
+    let protocol_fee = protocol_fee_rate.apply_floor(trade_fee)?;
+
+    Some(SwapResult {
+        from_amount: u64::try_from(from_amount).ok()?,
+        to_amount: u64::try_from(destination_amount).ok()?,
+        trade_fee: u64::try_from(trade_fee).ok()?,
+        protocol_fee: protocol_fee as u64,
+        host_fee: 0,
+    })
+}
+
+/// [`swap_with_fee_side`] with the source side carried in the type
+pub fn swap_typed<S: Side>(
+    source_amount: Amount<S>,
+    pool_source_amount: Amount<S>,
+    pool_destination_amount: Amount<S::Other>,
+    trade_fee_rate: Ppm,
+    protocol_fee_rate: Ppm,
+    fee_side: FeeSide,
+) -> Option<TypedSwapResult<S>> {
+    let result = swap_with_fee_side(
+        source_amount.into(),
+        pool_source_amount.into(),
+        pool_destination_amount.into(),
+        trade_fee_rate,
+        protocol_fee_rate,
+        fee_side,
+    )?;
+
+    Some(TypedSwapResult {
+        from_amount: Amount::new(result.from_amount),
+        to_amount: Amount::new(result.to_amount),
+        trade_fee: FeeAmount::new(result.trade_fee, fee_side),
+        protocol_fee: FeeAmount::new(result.protocol_fee, fee_side),
+        host_fee: FeeAmount::new(result.host_fee, fee_side),
     })
 }
 
@@ -86,11 +347,13 @@ pub fn rebalance_pool_ratio(
     current_destination_amount: u64,
     original_source_amount: u64,
     original_destination_amount: u64,
-    ratio_change_tolerance_rate: u64,
+    ratio_change_tolerance_rate: Ppm,
 ) -> Option<RebalanceResult> {
     if to_amount_swapped >= current_destination_amount
         || current_source_amount == 0
         || current_destination_amount == 0
+        || original_source_amount == 0
+        || original_destination_amount == 0
     {
         // Should never happen, but just in case
         return Some(RebalanceResult {
@@ -135,15 +398,51 @@ pub fn rebalance_pool_ratio(
     let new_source_amount = current_source_amount.checked_sub(from_to_lock)?;
     let new_ratio = new_source_amount as f64 / remaining_destination as f64;
 
+    Some(RebalanceResult {
+        from_to_lock,
+        is_rate_tolerance_exceeded: is_ratio_change_tolerance_exceeded(
+            original_ratio,
+            new_ratio,
+            ratio_change_tolerance_rate,
+        ),
+    })
+}
+
+/// Whether the pool ratio moving from `original_ratio` to `new_ratio` is a
+/// larger relative change than `ratio_change_tolerance_rate`
+pub(crate) fn is_ratio_change_tolerance_exceeded(
+    original_ratio: f64,
+    new_ratio: f64,
+    ratio_change_tolerance_rate: Ppm,
+) -> bool {
     // Calculate percentage change
     let percentage_change = (new_ratio - original_ratio).abs() / original_ratio * 100.0;
 
-    let tolerance_percentage = (ratio_change_tolerance_rate as f64 / MAX_PERCENTAGE as f64) * 100.0;
-    let is_rate_tolerance_exceeded = percentage_change > tolerance_percentage;
+    let tolerance_percentage = (ratio_change_tolerance_rate.get() as f64 / MAX_PERCENTAGE as f64) * 100.0;
+    percentage_change > tolerance_percentage
+}
 
-    Some(RebalanceResult {
-        from_to_lock,
-        is_rate_tolerance_exceeded,
+/// [`rebalance_pool_ratio`] with the source side carried in the type
+pub fn rebalance_pool_ratio_typed<S: Side>(
+    to_amount_swapped: Amount<S::Other>,
+    current_source_amount: Amount<S>,
+    current_destination_amount: Amount<S::Other>,
+    original_source_amount: Amount<S>,
+    original_destination_amount: Amount<S::Other>,
+    ratio_change_tolerance_rate: Ppm,
+) -> Option<TypedRebalanceResult<S>> {
+    let result = rebalance_pool_ratio(
+        to_amount_swapped.get(),
+        current_source_amount.get(),
+        current_destination_amount.get(),
+        original_source_amount.get(),
+        original_destination_amount.get(),
+        ratio_change_tolerance_rate,
+    )?;
+
+    Some(TypedRebalanceResult {
+        from_to_lock: Amount::new(result.from_to_lock),
+        is_rate_tolerance_exceeded: result.is_rate_tolerance_exceeded,
     })
 }
 
@@ -222,8 +521,8 @@ pub mod tests {
         source_token_amount: u128,
         swap_source_amount: u128,
         swap_destination_amount: u128,
-        trade_fee_rate: u64,
-        protocol_fee_rate: u64,
+        trade_fee_rate: Ppm,
+        protocol_fee_rate: Ppm,
         is_x_to_y: bool,
     ) {
         let swap_result = swap(
@@ -274,6 +573,108 @@ pub mod tests {
         assert!(new_value >= previous_value);
     }
 
+    pub fn check_curve_value_from_swap_fee_on_output(
+        source_token_amount: u128,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        trade_fee_rate: Ppm,
+        protocol_fee_rate: Ppm,
+    ) {
+        let swap_result = swap_fee_on_output(
+            source_token_amount,
+            swap_source_amount,
+            swap_destination_amount,
+            trade_fee_rate,
+            protocol_fee_rate,
+        )
+        .unwrap();
+
+        // the whole input hits the curve
+        assert_eq!(u128::from(swap_result.from_amount), source_token_amount);
+
+        // protocol fee is always less than trade fee
+        assert!(swap_result.trade_fee >= swap_result.protocol_fee);
+
+        let previous_value = swap_source_amount
+            .checked_mul(swap_destination_amount)
+            .unwrap();
+
+        // the trade fee leaves the curve reserves towards the fee buckets
+        let destination_amount_swapped = u128::from(swap_result.to_amount)
+            .checked_add(u128::from(swap_result.trade_fee))
+            .unwrap();
+        let new_swap_source_amount = swap_source_amount
+            .checked_add(u128::from(swap_result.from_amount))
+            .unwrap();
+        let new_swap_destination_amount = swap_destination_amount
+            .checked_sub(destination_amount_swapped)
+            .unwrap();
+
+        let new_value = new_swap_source_amount
+            .checked_mul(new_swap_destination_amount)
+            .unwrap();
+
+        assert!(new_value >= previous_value);
+    }
+
+    /// Test function checking that an exact-out swap delivers the requested
+    /// amount when replayed as an exact-in swap, without reducing the value of
+    /// the pool.
+    pub fn check_swap_base_output(
+        destination_amount: u128,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        trade_fee_rate: Ppm,
+        protocol_fee_rate: Ppm,
+        fee_side: FeeSide,
+    ) {
+        let Some(swap_result) = swap_base_output(
+            destination_amount,
+            swap_source_amount,
+            swap_destination_amount,
+            trade_fee_rate,
+            protocol_fee_rate,
+            fee_side,
+        ) else {
+            // the pool can't deliver that much
+            return;
+        };
+        assert_eq!(u128::from(swap_result.to_amount), destination_amount);
+        assert!(swap_result.trade_fee >= swap_result.protocol_fee);
+
+        let source_amount = match fee_side {
+            FeeSide::Input => u128::from(swap_result.from_amount) + u128::from(swap_result.trade_fee),
+            FeeSide::Output => u128::from(swap_result.from_amount),
+        };
+        let forward = swap_with_fee_side(
+            source_amount,
+            swap_source_amount,
+            swap_destination_amount,
+            trade_fee_rate,
+            protocol_fee_rate,
+            fee_side,
+        )
+        .unwrap();
+        assert!(u128::from(forward.to_amount) >= destination_amount);
+        // a rounded up source amount can swap for slightly more, and so pay a
+        // slightly larger fee on output
+        if fee_side == FeeSide::Input {
+            assert_eq!(forward.trade_fee, swap_result.trade_fee);
+        }
+
+        let destination_amount_swapped = match fee_side {
+            FeeSide::Input => destination_amount,
+            FeeSide::Output => destination_amount + u128::from(swap_result.trade_fee),
+        };
+        let previous_value = swap_source_amount
+            .checked_mul(swap_destination_amount)
+            .unwrap();
+        let new_value = (swap_source_amount + u128::from(swap_result.from_amount))
+            .checked_mul(swap_destination_amount - destination_amount_swapped)
+            .unwrap();
+        assert!(new_value >= previous_value);
+    }
+
     // /// Test function checking that a deposit never reduces the value of pool
     // /// tokens.
     // ///
@@ -425,6 +826,33 @@ pub mod tests {
         assert!(new_invariant >= invariant);
     }
 
+    #[test]
+    fn mul_div_floor_handles_wide_products() {
+        assert_eq!(mul_div_floor(u128::MAX, u128::MAX, u128::MAX), Some(u128::MAX));
+        assert_eq!(mul_div_floor(u128::MAX, 3, 4), Some(u128::MAX / 4 * 3 + 2));
+        assert_eq!(mul_div_floor(1 << 100, 1 << 100, 1 << 90), Some(1 << 110));
+        assert_eq!(mul_div_floor(u128::MAX, 2, 1), None);
+        assert_eq!(mul_div_floor(1, 1, 0), None);
+    }
+
+    proptest! {
+        #[test]
+        fn mul_div_floor_matches_u128(a in any::<u64>(), b in any::<u64>(), c in 1..u128::MAX) {
+            prop_assert_eq!(
+                mul_div_floor(a as u128, b as u128, c),
+                Some(a as u128 * b as u128 / c)
+            );
+        }
+    }
+
+    #[test]
+    fn sqrt_rounds_in_the_requested_direction() {
+        assert_eq!((sqrt(0), sqrt(1), sqrt(15), sqrt(16), sqrt(17)), (0, 1, 3, 4, 4));
+        assert_eq!((sqrt_ceil(15), sqrt_ceil(16), sqrt_ceil(17)), (4, 4, 5));
+        assert_eq!(sqrt(u128::MAX), u64::MAX as u128);
+        assert_eq!(sqrt_ceil(u64::MAX as u128 * u64::MAX as u128), u64::MAX as u128);
+    }
+
     #[test]
     fn constant_product_swap_rounding() {
         let tests: &[(u128, u128, u128, u128, u128)] = &[
@@ -500,13 +928,108 @@ pub mod tests {
                 source_token_amount as u128,
                 swap_source_amount as u128,
                 swap_destination_amount as u128,
-                trade_fee_rate,
-                protocol_fee_rate,
+                Ppm::new(trade_fee_rate).unwrap(),
+                Ppm::new(protocol_fee_rate).unwrap(),
                 is_x_to_y
             );
         }
     }
 
+    proptest! {
+        #[test]
+        fn curve_value_does_not_decrease_from_swap_fee_on_output(
+            source_token_amount in 1..u64::MAX,
+            swap_source_amount in 1..u64::MAX,
+            swap_destination_amount in 1..u64::MAX,
+            trade_fee_rate in 1..(MAX_PERCENTAGE - 1),
+            protocol_fee_rate in 1..MAX_PERCENTAGE,
+        ) {
+            check_curve_value_from_swap_fee_on_output(
+                source_token_amount as u128,
+                swap_source_amount as u128,
+                swap_destination_amount as u128,
+                Ppm::new(trade_fee_rate).unwrap(),
+                Ppm::new(protocol_fee_rate).unwrap(),
+            );
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn swap_base_output_round_trips(
+            destination_amount in 1..u32::MAX as u64,
+            swap_source_amount in 1..u32::MAX as u64,
+            swap_destination_amount in 1..u64::MAX,
+            trade_fee_rate in 0..(MAX_PERCENTAGE - 1),
+            protocol_fee_rate in 0..MAX_PERCENTAGE,
+            fee_on_output in any::<bool>(),
+        ) {
+            check_swap_base_output(
+                destination_amount as u128,
+                swap_source_amount as u128,
+                swap_destination_amount as u128,
+                Ppm::new(trade_fee_rate).unwrap(),
+                Ppm::new(protocol_fee_rate).unwrap(),
+                if fee_on_output { FeeSide::Output } else { FeeSide::Input },
+            );
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn host_fee_is_carved_out_of_its_source(
+            source_token_amount in 1..u32::MAX as u64,
+            trade_fee_rate in 0..MAX_PERCENTAGE,
+            protocol_fee_rate in 0..=MAX_PERCENTAGE,
+            host_fee_rate in 0..=MAX_PERCENTAGE,
+            from_protocol_fee in any::<bool>(),
+        ) {
+            let (host_fee_rate, host_fee_source) = if from_protocol_fee {
+                (host_fee_rate, HostFeeSource::ProtocolFee)
+            } else {
+                // validated configs keep both slices within the trade fee
+                (host_fee_rate.min(MAX_PERCENTAGE - protocol_fee_rate), HostFeeSource::TradeFee)
+            };
+
+            let trade_fee = get_trade_fee(source_token_amount as u128, trade_fee_rate).unwrap();
+            let unsplit_protocol_fee = get_protocol_fee(trade_fee, protocol_fee_rate).unwrap();
+            let (protocol_fee, host_fee) = split_host_fee(
+                trade_fee,
+                unsplit_protocol_fee,
+                Ppm::new(host_fee_rate).unwrap(),
+                host_fee_source,
+            )
+            .unwrap();
+
+            prop_assert!(protocol_fee + host_fee <= trade_fee);
+            match host_fee_source {
+                HostFeeSource::TradeFee => {
+                    prop_assert_eq!(host_fee, trade_fee * host_fee_rate as u128 / MAX_PERCENTAGE as u128);
+                    prop_assert_eq!(protocol_fee, unsplit_protocol_fee);
+                }
+                HostFeeSource::ProtocolFee => {
+                    prop_assert_eq!(host_fee, unsplit_protocol_fee * host_fee_rate as u128 / MAX_PERCENTAGE as u128);
+                    prop_assert_eq!(protocol_fee + host_fee, unsplit_protocol_fee);
+                }
+            }
+
+            // quote reports the same slices
+            let amm_config = crate::AmmConfig::builder()
+                .trade_fee_rate(Ppm::new(trade_fee_rate).unwrap())
+                .protocol_fee_rate(Ppm::new(protocol_fee_rate).unwrap())
+                .ratio_change_tolerance_rate(Ppm::MAX)
+                .host_fee_rate(Ppm::new(host_fee_rate).unwrap(), host_fee_source)
+                .build()
+                .unwrap();
+            let result = crate::quote(source_token_amount, true, &amm_config, 0, 0, 0, 0, 0, 0, u64::MAX, u64::MAX, 0, 0)
+                .unwrap();
+            prop_assert_eq!(
+                (result.trade_fee as u128, result.protocol_fee as u128, result.host_fee as u128),
+                (trade_fee, protocol_fee, host_fee)
+            );
+        }
+    }
+
     // proptest! {
     //     #[test]
     //     fn curve_value_does_not_decrease_from_deposit(
@@ -742,6 +1265,13 @@ pub mod tests {
     //     }
     // }
 
+    #[test]
+    fn rebalance_pool_ratio_without_original_ratio() {
+        // 0:0 has no ratio to restore, and must not search every lock amount
+        let result = rebalance_pool_ratio(524_288, 280_375_465_082_924, 13_510_798_882_111_488, 0, 0, Ppm::ZERO);
+        assert_eq!(result, Some(RebalanceResult { from_to_lock: 0, is_rate_tolerance_exceeded: true }));
+    }
+
     #[test]
     fn test_from_to_lock_transition_manually() {
         // Test cases with different ratios and amounts - both small and large values
@@ -808,7 +1338,7 @@ pub mod tests {
                 current_destination_amount,
                 original_source_amount,
                 original_destination_amount,
-                tolerance_rate,
+                Ppm::new(tolerance_rate).unwrap(),
             )
             .unwrap();
 
@@ -0,0 +1,381 @@
+//! WebAssembly bindings
+//!
+//! Thin wrappers over the library for `wasm-bindgen`:
+//!
+//! - `u64` arguments take a `bigint`, a decimal string or a safe integer
+//!   `number`, and integers in results come back as `bigint`
+//! - configs and results are plain objects with camelCase keys, snake_case
+//!   keys are accepted too
+//! - math errors throw an `Error` named after the `MathError`, with the code
+//!   number in `code`; malformed arguments throw a `TypeError`
+
+use js_sys::{Array, BigInt, Object, Reflect};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Map, Number, Value};
+use wasm_bindgen::{prelude::wasm_bindgen, JsCast, JsValue};
+
+use crate::{
+    quote_pool_balances, quote_pool_balances_at, AmmConfig, FeeSchedule, HostFeeSource, MathError, PoolBalances,
+    Ppm,
+};
+
+/// Largest integer a JS `number` holds exactly
+const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_991.0;
+
+#[wasm_bindgen]
+pub fn quote(
+    exchange_in: JsValue,
+    is_swap_x_to_y: bool,
+    amm_config: JsValue,
+    pool_balances: JsValue,
+) -> Result<JsValue, JsValue> {
+    let exchange_in = u64_arg("exchangeIn", &exchange_in)?;
+    let (amm_config, balances) = pool_args(&amm_config, &pool_balances)?;
+    let output = quote_pool_balances(exchange_in, is_swap_x_to_y, &amm_config, &balances);
+    to_js_result(output.map_err(math_error)?)
+}
+
+/// [`quote`] applying the launch fee schedule at slot / timestamp `now`
+#[wasm_bindgen(js_name = quoteAt)]
+pub fn quote_at(
+    now: JsValue,
+    exchange_in: JsValue,
+    is_swap_x_to_y: bool,
+    amm_config: JsValue,
+    pool_balances: JsValue,
+) -> Result<JsValue, JsValue> {
+    let now = u64_arg("now", &now)?;
+    let exchange_in = u64_arg("exchangeIn", &exchange_in)?;
+    let (amm_config, balances) = pool_args(&amm_config, &pool_balances)?;
+    let output = quote_pool_balances_at(now, exchange_in, is_swap_x_to_y, &amm_config, &balances);
+    to_js_result(output.map_err(math_error)?)
+}
+
+/// LP tokens minted for a deposit, see [`crate::try_deposit_lp`]
+#[wasm_bindgen(js_name = depositLp)]
+pub fn deposit_lp(
+    amount_x: JsValue,
+    amount_y: JsValue,
+    total_lp_supply: JsValue,
+    reserve_x: JsValue,
+    reserve_y: JsValue,
+) -> Result<BigInt, JsValue> {
+    let amount_x = u64_arg("amountX", &amount_x)?;
+    let amount_y = u64_arg("amountY", &amount_y)?;
+    let total_lp_supply = u64_arg("totalLpSupply", &total_lp_supply)?;
+    let reserve_x = u64_arg("reserveX", &reserve_x)?;
+    let reserve_y = u64_arg("reserveY", &reserve_y)?;
+    let lp_tokens = crate::try_deposit_lp(amount_x, amount_y, total_lp_supply, reserve_x, reserve_y);
+    Ok(lp_tokens.map_err(math_error)?.into())
+}
+
+/// Tokens returned for burning LP tokens, `{ amountX, amountY }`, see
+/// [`crate::try_withdraw_lp`]
+#[wasm_bindgen(js_name = withdrawLp)]
+pub fn withdraw_lp(
+    lp_tokens: JsValue,
+    total_lp_supply: JsValue,
+    reserve_x: JsValue,
+    reserve_y: JsValue,
+) -> Result<JsValue, JsValue> {
+    let lp_tokens = u64_arg("lpTokens", &lp_tokens)?;
+    let total_lp_supply = u64_arg("totalLpSupply", &total_lp_supply)?;
+    let reserve_x = u64_arg("reserveX", &reserve_x)?;
+    let reserve_y = u64_arg("reserveY", &reserve_y)?;
+    let (amount_x, amount_y) =
+        crate::try_withdraw_lp(lp_tokens, total_lp_supply, reserve_x, reserve_y).map_err(math_error)?;
+    Ok(to_js(&json!({ "amount_x": amount_x, "amount_y": amount_y })))
+}
+
+/// LP tokens minted for a deposit after minting the protocol fee,
+/// `{ lpMinted, unusedX, unusedY, protocolFeeLp, kLast }`, see
+/// [`crate::deposit_lp_with_protocol_fee`]
+#[wasm_bindgen(js_name = depositLpWithProtocolFee)]
+pub fn deposit_lp_with_protocol_fee(
+    amount_x: JsValue,
+    amount_y: JsValue,
+    total_lp_supply: JsValue,
+    pool_balances: JsValue,
+    k_last: JsValue,
+    protocol_share: JsValue,
+) -> Result<JsValue, JsValue> {
+    let deposit = crate::deposit_lp_with_protocol_fee(
+        u64_arg("amountX", &amount_x)?,
+        u64_arg("amountY", &amount_y)?,
+        u64_arg("totalLpSupply", &total_lp_supply)?,
+        &from_js("poolBalances", &pool_balances)?,
+        u128_arg("kLast", &k_last)?,
+        rate_arg("protocolShare", &protocol_share)?,
+    )
+    .map_err(math_error)?;
+    with_k_last(to_js_result(&deposit)?, deposit.k_last)
+}
+
+/// Tokens returned for burning LP tokens after minting the protocol fee,
+/// `{ amountX, amountY, lpFeeX, lpFeeY, protocolFeeLp, kLast }`, see
+/// [`crate::withdraw_lp_with_protocol_fee`]
+#[wasm_bindgen(js_name = withdrawLpWithProtocolFee)]
+pub fn withdraw_lp_with_protocol_fee(
+    lp_tokens: JsValue,
+    total_lp_supply: JsValue,
+    pool_balances: JsValue,
+    k_last: JsValue,
+    protocol_share: JsValue,
+) -> Result<JsValue, JsValue> {
+    let withdrawal = crate::withdraw_lp_with_protocol_fee(
+        u64_arg("lpTokens", &lp_tokens)?,
+        u64_arg("totalLpSupply", &total_lp_supply)?,
+        &from_js("poolBalances", &pool_balances)?,
+        u128_arg("kLast", &k_last)?,
+        rate_arg("protocolShare", &protocol_share)?,
+    )
+    .map_err(math_error)?;
+    with_k_last(to_js_result(&withdrawal)?, withdrawal.k_last)
+}
+
+/// Amount to lock to restore the pool ratio after a swap,
+/// `{ fromToLock, isRateToleranceExceeded }`
+#[wasm_bindgen(js_name = rebalancePoolRatio)]
+pub fn rebalance_pool_ratio(
+    to_amount_swapped: JsValue,
+    current_source_amount: JsValue,
+    current_destination_amount: JsValue,
+    original_source_amount: JsValue,
+    original_destination_amount: JsValue,
+    ratio_change_tolerance_rate: JsValue,
+) -> Result<JsValue, JsValue> {
+    let result = crate::rebalance_pool_ratio(
+        u64_arg("toAmountSwapped", &to_amount_swapped)?,
+        u64_arg("currentSourceAmount", &current_source_amount)?,
+        u64_arg("currentDestinationAmount", &current_destination_amount)?,
+        u64_arg("originalSourceAmount", &original_source_amount)?,
+        u64_arg("originalDestinationAmount", &original_destination_amount)?,
+        rate_arg("ratioChangeToleranceRate", &ratio_change_tolerance_rate)?,
+    )
+    .ok_or_else(|| math_error(MathError::MathOverflow))?;
+    to_js_result(result)
+}
+
+/// Trade fee on `amount`, rounded up
+#[wasm_bindgen(js_name = getTradeFee)]
+pub fn get_trade_fee(amount: JsValue, trade_fee_rate: JsValue) -> Result<BigInt, JsValue> {
+    let amount = u64_arg("amount", &amount)?;
+    let rate = rate_arg("tradeFeeRate", &trade_fee_rate)?;
+    fee_to_js(crate::get_trade_fee(amount as u128, rate.get()))
+}
+
+/// Protocol fee on a trade fee, rounded down
+#[wasm_bindgen(js_name = getProtocolFee)]
+pub fn get_protocol_fee(amount: JsValue, protocol_fee_rate: JsValue) -> Result<BigInt, JsValue> {
+    let amount = u64_arg("amount", &amount)?;
+    let rate = rate_arg("protocolFeeRate", &protocol_fee_rate)?;
+    fee_to_js(crate::get_protocol_fee(amount as u128, rate.get()))
+}
+
+/// Host fee carved out of a trade, `{ protocolFee, hostFee }`,
+/// `hostFeeSource` being `"TradeFee"` or `"ProtocolFee"`
+#[wasm_bindgen(js_name = splitHostFee)]
+pub fn split_host_fee(
+    trade_fee: JsValue,
+    protocol_fee: JsValue,
+    host_fee_rate: JsValue,
+    host_fee_source: JsValue,
+) -> Result<JsValue, JsValue> {
+    let trade_fee = u64_arg("tradeFee", &trade_fee)?;
+    let protocol_fee = u64_arg("protocolFee", &protocol_fee)?;
+    let host_fee_rate = rate_arg("hostFeeRate", &host_fee_rate)?;
+    let host_fee_source: HostFeeSource = from_js("hostFeeSource", &host_fee_source)?;
+
+    let (protocol_fee, host_fee) =
+        crate::split_host_fee(trade_fee as u128, protocol_fee as u128, host_fee_rate, host_fee_source)
+            .ok_or_else(|| math_error(MathError::InvalidHostFeeRate))?;
+    Ok(to_js(&json!({ "protocol_fee": protocol_fee as u64, "host_fee": host_fee as u64 })))
+}
+
+/// Trade fee rate in effect at `now` under a launch fee schedule
+#[wasm_bindgen(js_name = effectiveFeeRate)]
+pub fn effective_fee_rate(fee_schedule: JsValue, base_fee_rate: JsValue, now: JsValue) -> Result<BigInt, JsValue> {
+    let fee_schedule: FeeSchedule = from_js("feeSchedule", &fee_schedule)?;
+    let base_fee_rate = rate_arg("baseFeeRate", &base_fee_rate)?;
+    let now = u64_arg("now", &now)?;
+    Ok(crate::effective_fee_rate(&fee_schedule, base_fee_rate, now).get().into())
+}
+
+fn pool_args(amm_config: &JsValue, pool_balances: &JsValue) -> Result<(AmmConfig, PoolBalances), JsValue> {
+    let amm_config: AmmConfig = from_js("ammConfig", amm_config)?;
+    amm_config.validate().map_err(math_error)?;
+    Ok((amm_config, from_js("poolBalances", pool_balances)?))
+}
+
+fn u64_arg(name: &str, value: &JsValue) -> Result<u64, JsValue> {
+    match js_to_value(value)? {
+        Value::Number(number) => number.as_u64(),
+        _ => None,
+    }
+    .ok_or_else(|| type_error(&format!("`{name}` must be a u64 bigint, string or safe integer")))
+}
+
+/// [`u64_arg`] for `u128`s, from `bigint`s, digit strings and safe integers
+fn u128_arg(name: &str, value: &JsValue) -> Result<u128, JsValue> {
+    let digits = match value.dyn_ref::<BigInt>() {
+        Some(bigint) => Some(String::from(bigint.to_string(10).map_err(JsValue::from)?)),
+        None => value.as_string(),
+    };
+    match digits {
+        Some(digits) if !digits.is_empty() && digits.bytes().all(|byte| byte.is_ascii_digit()) => digits.parse().ok(),
+        Some(_) => None,
+        None => return u64_arg(name, value).map(u128::from),
+    }
+    .ok_or_else(|| type_error(&format!("`{name}` must be a u128 bigint, string or safe integer")))
+}
+
+/// `kLast` as a `bigint`, the serialized result carries it as a string
+fn with_k_last(output: JsValue, k_last: u128) -> Result<JsValue, JsValue> {
+    Reflect::set(&output, &"kLast".into(), &BigInt::from(k_last).into())?;
+    Ok(output)
+}
+
+fn rate_arg(name: &str, value: &JsValue) -> Result<Ppm, JsValue> {
+    Ppm::new(u64_arg(name, value)?).map_err(math_error)
+}
+
+fn from_js<T: DeserializeOwned>(name: &str, value: &JsValue) -> Result<T, JsValue> {
+    serde_json::from_value(js_to_value(value)?).map_err(|e| type_error(&format!("`{name}`: {e}")))
+}
+
+fn to_js_result<T: serde::Serialize>(result: T) -> Result<JsValue, JsValue> {
+    let value = serde_json::to_value(result).map_err(|e| type_error(&e.to_string()))?;
+    Ok(to_js(&value))
+}
+
+fn fee_to_js(fee: Option<u128>) -> Result<BigInt, JsValue> {
+    // fees never exceed the u64 amount they are taken from
+    let fee = fee.and_then(|fee| u64::try_from(fee).ok());
+    fee.map(BigInt::from)
+        .ok_or_else(|| math_error(MathError::MathOverflow))
+}
+
+fn math_error(error: MathError) -> JsValue {
+    let js_error = js_sys::Error::new(error.message());
+    js_error.set_name(error.name());
+    // setting a property on a fresh Error cannot fail
+    let _ = Reflect::set(&js_error, &"code".into(), &error.code().into());
+    js_error.into()
+}
+
+fn type_error(message: &str) -> JsValue {
+    js_sys::TypeError::new(message).into()
+}
+
+/// JS value to JSON, integers from `bigint`s, digit strings and whole numbers,
+/// camelCase keys to snake_case
+fn js_to_value(value: &JsValue) -> Result<Value, JsValue> {
+    if value.is_null() || value.is_undefined() {
+        return Ok(Value::Null);
+    }
+    if let Some(flag) = value.as_bool() {
+        return Ok(Value::Bool(flag));
+    }
+    if let Some(bigint) = value.dyn_ref::<BigInt>() {
+        let digits = String::from(bigint.to_string(10).map_err(JsValue::from)?);
+        return digits
+            .parse::<u64>()
+            .map(Value::from)
+            .map_err(|_| type_error(&format!("{digits} does not fit in a u64")));
+    }
+    if let Some(number) = value.as_f64() {
+        if number.fract() == 0.0 && number.abs() <= MAX_SAFE_INTEGER {
+            return Ok(Value::from(number as i64));
+        }
+        return Number::from_f64(number)
+            .map(Value::Number)
+            .ok_or_else(|| type_error(&format!("{number} is not a finite number")));
+    }
+    if let Some(string) = value.as_string() {
+        if !string.is_empty() && string.bytes().all(|byte| byte.is_ascii_digit()) {
+            return string
+                .parse::<u64>()
+                .map(Value::from)
+                .map_err(|_| type_error(&format!("{string} does not fit in a u64")));
+        }
+        return Ok(Value::String(string));
+    }
+    if let Some(array) = value.dyn_ref::<Array>() {
+        return array.iter().map(|item| js_to_value(&item)).collect();
+    }
+    if value.is_object() {
+        let mut map = Map::new();
+        for entry in Object::entries(value.unchecked_ref::<Object>()).iter() {
+            let entry: Array = entry.unchecked_into();
+            let key = entry.get(0).as_string().unwrap_or_default();
+            map.insert(snake_case(&key), js_to_value(&entry.get(1))?);
+        }
+        return Ok(Value::Object(map));
+    }
+    Err(type_error("unsupported argument type"))
+}
+
+/// JSON to a JS value, integers as `bigint`s, snake_case keys to camelCase
+fn to_js(value: &Value) -> JsValue {
+    match value {
+        Value::Null => JsValue::NULL,
+        Value::Bool(flag) => (*flag).into(),
+        Value::Number(number) => match (number.as_u64(), number.as_i64()) {
+            (Some(number), _) => BigInt::from(number).into(),
+            (None, Some(number)) => BigInt::from(number).into(),
+            _ => number.as_f64().unwrap_or(f64::NAN).into(),
+        },
+        Value::String(string) => string.into(),
+        Value::Array(values) => values.iter().map(to_js).collect::<Array>().into(),
+        Value::Object(map) => {
+            let object = Object::new();
+            for (key, value) in map {
+                let _ = Reflect::set(&object, &camel_case(key).into(), &to_js(value));
+            }
+            object.into()
+        }
+    }
+}
+
+/// `reserveXBalance` to `reserve_x_balance`, leaving capitalised enum variant
+/// names alone
+fn snake_case(key: &str) -> String {
+    let mut snake = String::with_capacity(key.len() + 4);
+    for (index, char) in key.chars().enumerate() {
+        if index > 0 && char.is_ascii_uppercase() {
+            snake.push('_');
+            snake.push(char.to_ascii_lowercase());
+        } else {
+            snake.push(char);
+        }
+    }
+    snake
+}
+
+fn camel_case(key: &str) -> String {
+    let mut parts = key.split('_');
+    let mut camel = parts.next().unwrap_or_default().to_string();
+    for part in parts {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            camel.push(first.to_ascii_uppercase());
+            camel.extend(chars);
+        }
+    }
+    camel
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_cases_round_trip() {
+        for key in ["reserve_x_balance", "lp_fee_y", "x_to_y", "from_to_lock", "is_rate_tolerance_exceeded"] {
+            assert_eq!(snake_case(&camel_case(key)), key);
+        }
+        assert_eq!(camel_case("reserve_x_balance"), "reserveXBalance");
+        assert_eq!(snake_case("Exponential"), "Exponential");
+        assert_eq!(snake_case("half_life"), "half_life");
+    }
+}
@@ -0,0 +1,67 @@
+//! C ABI: the committed header matches `src/ffi.rs`, and `tests/ffi/test.c`
+//! builds against it and passes when linked to the cdylib, built here with
+//! `cargo rustc --crate-type cdylib` as the package only declares an rlib
+
+use std::{env, fs, path::Path, process::Command};
+
+const UPDATE_HEADER: &str = "DEX_MATH_UPDATE_HEADER";
+
+fn manifest_dir() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+}
+
+#[test]
+fn header_is_up_to_date() {
+    let config = cbindgen::Config::from_file(manifest_dir().join("cbindgen.toml")).unwrap();
+    let mut generated = Vec::new();
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(manifest_dir().join("src/ffi.rs"))
+        .generate()
+        .unwrap()
+        .write(&mut generated);
+    let generated = String::from_utf8(generated).unwrap();
+
+    let header = manifest_dir().join("include/dex_math.h");
+    if env::var_os(UPDATE_HEADER).is_some() {
+        fs::write(&header, &generated).unwrap();
+    }
+    assert!(
+        fs::read_to_string(&header).unwrap_or_default() == generated,
+        "include/dex_math.h is stale, rerun with {UPDATE_HEADER}=1"
+    );
+}
+
+#[test]
+fn c_program_passes() {
+    // a target dir of its own, the one running this test is locked
+    let target_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("ffi");
+    let status = Command::new(env!("CARGO"))
+        .current_dir(manifest_dir())
+        .args(["rustc", "--quiet", "--lib", "--crate-type", "cdylib", "--features", "ffi", "--target-dir"])
+        .arg(&target_dir)
+        .status()
+        .unwrap();
+    assert!(status.success(), "building the cdylib failed");
+    let deps = target_dir.join("debug");
+    let program = deps.join("dex_math_ffi_test");
+
+    let compiler = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let status = Command::new(&compiler)
+        .args(["-std=c99", "-Wall", "-Wextra", "-Werror", "-I"])
+        .arg(manifest_dir().join("include"))
+        .arg(manifest_dir().join("tests/ffi/test.c"))
+        .arg("-L")
+        .arg(&deps)
+        .arg(format!("-Wl,-rpath,{}", deps.display()))
+        .args(["-ldex_math", "-o"])
+        .arg(&program)
+        .status()
+        .unwrap_or_else(|e| panic!("running {compiler}: {e}"));
+    assert!(status.success(), "compiling tests/ffi/test.c failed");
+
+    let output = Command::new(&program).env("LD_LIBRARY_PATH", &deps).output().unwrap();
+    print!("{}", String::from_utf8_lossy(&output.stdout));
+    eprint!("{}", String::from_utf8_lossy(&output.stderr));
+    assert!(output.status.success(), "tests/ffi/test.c failed");
+}
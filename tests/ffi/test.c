@@ -0,0 +1,147 @@
+/* Exercises the C ABI, run by `cargo test --features ffi --test ffi`. */
+
+#include <stdio.h>
+#include <stdlib.h>
+
+#include "dex_math.h"
+
+static int failures = 0;
+
+#define CHECK_EQ(actual, expected)                                                      \
+  do {                                                                                  \
+    unsigned long long actual_ = (unsigned long long)(actual);                          \
+    unsigned long long expected_ = (unsigned long long)(expected);                      \
+    if (actual_ != expected_) {                                                         \
+      fprintf(stderr, "%s:%d: %s == %llu, expected %llu\n", __FILE__, __LINE__, #actual, \
+              actual_, expected_);                                                      \
+      failures++;                                                                       \
+    }                                                                                   \
+  } while (0)
+
+static DexMathAmmConfig amm_config(void) {
+  DexMathAmmConfig config = {0};
+  config.trade_fee_rate = 3000;
+  config.protocol_fee_rate = 200000;
+  config.ratio_change_tolerance_rate = 1000000;
+  config.host_fee_source = DEX_MATH_HOST_FEE_SOURCE_TRADE_FEE;
+  config.fee_side = DEX_MATH_FEE_SIDE_INPUT;
+  return config;
+}
+
+static void test_quote(void) {
+  DexMathAmmConfig config = amm_config();
+  DexMathPoolBalances pool = {0};
+  pool.reserve_x_balance = 1000000;
+  pool.reserve_y_balance = 2000000;
+  DexMathQuoteOutput out = {0};
+
+  CHECK_EQ(dex_math_quote(10000, 1, &config, &pool, &out), DEX_MATH_STATUS_OK);
+  CHECK_EQ(out.from_amount, 9970);
+  CHECK_EQ(out.to_amount, 19743);
+  CHECK_EQ(out.trade_fee_rate, 3000);
+  CHECK_EQ(out.trade_fee, 30);
+  CHECK_EQ(out.protocol_fee, 6);
+  CHECK_EQ(out.from_to_lock, 9871);
+
+  config.fee_side = DEX_MATH_FEE_SIDE_OUTPUT;
+  CHECK_EQ(dex_math_quote(10000, 0, &config, &pool, &out), DEX_MATH_STATUS_OK);
+  CHECK_EQ(out.to_amount, 4960);
+  CHECK_EQ(out.trade_fee, 15);
+
+  config = amm_config();
+  CHECK_EQ(dex_math_quote(0, 1, &config, &pool, &out), DEX_MATH_STATUS_INPUT_AMOUNT_TOO_SMALL);
+  config.trade_fee_rate = 1000000;
+  CHECK_EQ(dex_math_quote(13, 1, &config, &pool, &out), DEX_MATH_STATUS_INVALID_TRADE_FEE_RATE);
+  CHECK_EQ(dex_math_quote(13, 1, &config, NULL, &out), DEX_MATH_STATUS_NULL_POINTER);
+
+  /* enums and flags are range checked */
+  config = amm_config();
+  config.fee_side = 2;
+  CHECK_EQ(dex_math_quote(13, 1, &config, &pool, &out), DEX_MATH_STATUS_INVALID_ARGUMENT);
+  config = amm_config();
+  config.has_directional_fee_rates = 2;
+  CHECK_EQ(dex_math_quote(13, 1, &config, &pool, &out), DEX_MATH_STATUS_INVALID_ARGUMENT);
+  config = amm_config();
+  CHECK_EQ(dex_math_quote(13, 2, &config, &pool, &out), DEX_MATH_STATUS_INVALID_ARGUMENT);
+}
+
+static void test_swap(void) {
+  DexMathSwapResult out = {0};
+  CHECK_EQ(dex_math_swap(10000, 1000000, 2000000, 3000, 200000, DEX_MATH_FEE_SIDE_INPUT, &out),
+           DEX_MATH_STATUS_OK);
+  CHECK_EQ(out.from_amount, 9970);
+  CHECK_EQ(out.to_amount, 19743);
+  CHECK_EQ(out.trade_fee, 30);
+  CHECK_EQ(out.protocol_fee, 6);
+
+  CHECK_EQ(dex_math_swap(10000, 1000000, 2000000, 1000001, 0, DEX_MATH_FEE_SIDE_INPUT, &out),
+           DEX_MATH_STATUS_INVALID_RATE);
+  CHECK_EQ(dex_math_swap(10000, 1000000, 2000000, 3000, 200000, 2, &out), DEX_MATH_STATUS_INVALID_ARGUMENT);
+}
+
+static void test_liquidity(void) {
+  uint64_t lp_tokens = 0;
+  CHECK_EQ(dex_math_deposit_lp(1000, 4000, 0, 0, 0, &lp_tokens), DEX_MATH_STATUS_OK);
+  CHECK_EQ(lp_tokens, 2000);
+  CHECK_EQ(dex_math_deposit_lp(1000, 3000, 1000000, 1000000, 2000000, &lp_tokens), DEX_MATH_STATUS_OK);
+  CHECK_EQ(lp_tokens, 1000);
+  CHECK_EQ(dex_math_deposit_lp(1000, 3000, 1000000, 0, 2000000, &lp_tokens), DEX_MATH_STATUS_MATH_OVERFLOW);
+
+  uint64_t amount_x = 0, amount_y = 0;
+  CHECK_EQ(dex_math_withdraw_lp(1000, 1000000, 1000000, 2000000, &amount_x, &amount_y), DEX_MATH_STATUS_OK);
+  CHECK_EQ(amount_x, 1000);
+  CHECK_EQ(amount_y, 2000);
+  CHECK_EQ(dex_math_withdraw_lp(1000, 1000000, 1000000, 2000000, &amount_x, NULL),
+           DEX_MATH_STATUS_NULL_POINTER);
+
+  /* sqrt(k) grew from 2000000 to 2020000, half of it is the protocol's */
+  DexMathPoolBalances pool = {0};
+  pool.reserve_x_balance = 1010000;
+  pool.reserve_y_balance = 4040000;
+  DexMathProtocolFeeDeposit deposit = {0};
+  CHECK_EQ(dex_math_deposit_lp_with_protocol_fee(10100, 40400, 2000000, &pool, 0, 4000000000000, 500000, &deposit),
+           DEX_MATH_STATUS_OK);
+  CHECK_EQ(deposit.protocol_fee_lp, 9950);
+  CHECK_EQ(deposit.lp_minted, 20099);
+  CHECK_EQ(deposit.unused_x, 0);
+  CHECK_EQ(deposit.unused_y, 1);
+  CHECK_EQ(deposit.k_last_high, 0);
+  CHECK_EQ(deposit.k_last_low, 4162415019900);
+
+  /* k_last past 64 bits, too small a deposit to mint anything */
+  DexMathPoolBalances large = {0};
+  large.reserve_x_balance = 1ULL << 63;
+  large.reserve_y_balance = 1ULL << 63;
+  CHECK_EQ(dex_math_deposit_lp_with_protocol_fee(1, 1, 1000, &large, 1ULL << 62, 0, 500000, &deposit),
+           DEX_MATH_STATUS_OK);
+  CHECK_EQ(deposit.lp_minted, 0);
+  CHECK_EQ(deposit.k_last_high, 1ULL << 62);
+
+  /* the same growth, a tenth of it still sitting in the LP fee buckets */
+  pool.lp_fee_x = 101000;
+  pool.lp_fee_y = 404000;
+  DexMathProtocolFeeWithdrawal withdrawal = {0};
+  CHECK_EQ(dex_math_withdraw_lp_with_protocol_fee(20000, 2000000, &pool, 0, 4000000000000, 500000, &withdrawal),
+           DEX_MATH_STATUS_OK);
+  CHECK_EQ(withdrawal.protocol_fee_lp, 9950);
+  CHECK_EQ(withdrawal.amount_x, 10050);
+  CHECK_EQ(withdrawal.lp_fee_x, 1005);
+  CHECK_EQ(dex_math_withdraw_lp_with_protocol_fee(1, 1000, &pool, 0, 1, 1000001, &withdrawal),
+           DEX_MATH_STATUS_INVALID_RATE);
+  CHECK_EQ(dex_math_withdraw_lp_with_protocol_fee(1, 1000, NULL, 0, 1, 500000, &withdrawal),
+           DEX_MATH_STATUS_NULL_POINTER);
+}
+
+int main(void) {
+  test_quote();
+  test_swap();
+  test_liquidity();
+
+  printf("%s\n", dex_math_status_name(DEX_MATH_STATUS_TRADE_TOO_BIG));
+  if (failures) {
+    fprintf(stderr, "%d checks failed\n", failures);
+    return EXIT_FAILURE;
+  }
+  printf("all checks passed\n");
+  return EXIT_SUCCESS;
+}
@@ -0,0 +1,511 @@
+//! Golden test vectors for the TypeScript and Python ports
+//!
+//! `tests/golden/vectors.json` holds inputs and exact outputs of the quote,
+//! swap, rebalance, liquidity and fee functions. Integers are decimal strings
+//! so `u64` / `u128` survive JSON parsers that read numbers as doubles,
+//! `Option::None` is `null` and errors are `{ "error": <MathError name> }`.
+//!
+//! `VERSION` goes up whenever the format changes or an existing output does.
+//! Regenerate with `DEX_MATH_UPDATE_GOLDEN=1 cargo test --test golden`.
+
+use std::{env, fs, path::Path};
+
+use dex_math::{
+    deposit_lp, get_protocol_fee, get_trade_fee, quote, rebalance_pool_ratio, split_host_fee, swap, withdraw_lp,
+    AmmConfig, DirectionalFeeRates, FeeRates, FeeSide, HostFeeSource, MathError, Ppm, MAX_PERCENTAGE,
+};
+use serde_json::{json, Value};
+
+const VERSION: u64 = 1;
+const UPDATE_GOLDEN: &str = "DEX_MATH_UPDATE_GOLDEN";
+
+fn corpus_path() -> &'static Path {
+    Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/vectors.json"))
+}
+
+#[test]
+fn committed_vectors_match_the_code() {
+    let generated = generate();
+    if env::var_os(UPDATE_GOLDEN).is_some() {
+        let mut text = serde_json::to_string_pretty(&generated).unwrap();
+        text.push('\n');
+        fs::write(corpus_path(), text).unwrap();
+    }
+
+    let committed: Value = serde_json::from_str(&fs::read_to_string(corpus_path()).unwrap()).unwrap();
+    assert_eq!(committed["version"], VERSION, "bump VERSION when the corpus changes");
+    for case in committed["cases"].as_array().unwrap() {
+        let function = case["fn"].as_str().unwrap();
+        assert_eq!(evaluate(function, &case["input"]), case["output"], "{function} {}", case["input"]);
+    }
+    assert!(committed == generated, "tests/golden/vectors.json is stale, rerun with {UPDATE_GOLDEN}=1");
+}
+
+fn generate() -> Value {
+    let cases: Vec<Value> = inputs()
+        .into_iter()
+        .map(|(function, input)| {
+            let output = evaluate(function, &input);
+            json!({ "fn": function, "input": input, "output": output })
+        })
+        .collect();
+    json!({ "version": VERSION, "cases": cases })
+}
+
+/// Run `function` on a corpus input
+fn evaluate(function: &str, input: &Value) -> Value {
+    match function {
+        "quote" => {
+            let amm_config = amm_config(&input["amm_config"]);
+            let pool = &input["pool"];
+            let result = quote(
+                int(&input["exchange_in"]),
+                input["is_swap_x_to_y"].as_bool().unwrap(),
+                &amm_config,
+                int(&pool["protocol_fee_x"]),
+                int(&pool["protocol_fee_y"]),
+                int(&pool["user_locked_x"]),
+                int(&pool["user_locked_y"]),
+                int(&pool["locked_x"]),
+                int(&pool["locked_y"]),
+                int(&pool["reserve_x_balance"]),
+                int(&pool["reserve_y_balance"]),
+                int(&pool["lp_fee_x"]),
+                int(&pool["lp_fee_y"]),
+            );
+            match result {
+                Ok(output) => json!({
+                    "from_amount": output.from_amount.to_string(),
+                    "to_amount": output.to_amount.to_string(),
+                    "trade_fee_rate": output.trade_fee_rate.get().to_string(),
+                    "trade_fee": output.trade_fee.to_string(),
+                    "protocol_fee": output.protocol_fee.to_string(),
+                    "host_fee": output.host_fee.to_string(),
+                    "from_to_lock": output.from_to_lock.to_string(),
+                }),
+                Err(error) => error_value(error),
+            }
+        }
+        "swap" => {
+            let result = swap(
+                int(&input["source_amount"]),
+                int(&input["pool_source_amount"]),
+                int(&input["pool_destination_amount"]),
+                rate(&input["trade_fee_rate"]),
+                rate(&input["protocol_fee_rate"]),
+            );
+            match result {
+                Some(output) => json!({
+                    "from_amount": output.from_amount.to_string(),
+                    "to_amount": output.to_amount.to_string(),
+                    "trade_fee": output.trade_fee.to_string(),
+                    "protocol_fee": output.protocol_fee.to_string(),
+                    "host_fee": output.host_fee.to_string(),
+                }),
+                None => Value::Null,
+            }
+        }
+        "rebalance_pool_ratio" => {
+            let result = rebalance_pool_ratio(
+                int(&input["to_amount_swapped"]),
+                int(&input["current_source_amount"]),
+                int(&input["current_destination_amount"]),
+                int(&input["original_source_amount"]),
+                int(&input["original_destination_amount"]),
+                rate(&input["ratio_change_tolerance_rate"]),
+            );
+            match result {
+                Some(output) => json!({
+                    "from_to_lock": output.from_to_lock.to_string(),
+                    "is_rate_tolerance_exceeded": output.is_rate_tolerance_exceeded,
+                }),
+                None => Value::Null,
+            }
+        }
+        "deposit_lp" => {
+            let lp_tokens = deposit_lp(
+                int(&input["token_a_amount"]),
+                int(&input["token_b_amount"]),
+                int(&input["total_lp_supply"]),
+                int(&input["token_a_reserve"]),
+                int(&input["token_b_reserve"]),
+            );
+            json!(lp_tokens.to_string())
+        }
+        "withdraw_lp" => {
+            let (token_a_amount, token_b_amount) = withdraw_lp(
+                int(&input["lp_tokens"]),
+                int(&input["total_lp_supply"]),
+                int(&input["token_a_reserve"]),
+                int(&input["token_b_reserve"]),
+            );
+            json!({ "token_a_amount": token_a_amount.to_string(), "token_b_amount": token_b_amount.to_string() })
+        }
+        "get_trade_fee" => {
+            let fee = get_trade_fee(int(&input["amount"]), int(&input["trade_fee_rate"]));
+            fee.map_or(Value::Null, |fee| json!(fee.to_string()))
+        }
+        "get_protocol_fee" => {
+            let fee = get_protocol_fee(int(&input["amount"]), int(&input["protocol_fee_rate"]));
+            fee.map_or(Value::Null, |fee| json!(fee.to_string()))
+        }
+        "split_host_fee" => {
+            let result = split_host_fee(
+                int(&input["trade_fee"]),
+                int(&input["protocol_fee"]),
+                rate(&input["host_fee_rate"]),
+                host_fee_source(&input["host_fee_source"]),
+            );
+            match result {
+                Some((protocol_fee, host_fee)) => {
+                    json!({ "protocol_fee": protocol_fee.to_string(), "host_fee": host_fee.to_string() })
+                }
+                None => Value::Null,
+            }
+        }
+        function => panic!("unknown function {function}"),
+    }
+}
+
+fn int<T: std::str::FromStr>(value: &Value) -> T {
+    value.as_str().and_then(|digits| digits.parse().ok()).unwrap_or_else(|| panic!("integer string, got {value}"))
+}
+
+fn rate(value: &Value) -> Ppm {
+    Ppm::new(int(value)).unwrap()
+}
+
+fn host_fee_source(value: &Value) -> HostFeeSource {
+    match value.as_str().unwrap() {
+        "TradeFee" => HostFeeSource::TradeFee,
+        "ProtocolFee" => HostFeeSource::ProtocolFee,
+        source => panic!("unknown host fee source {source}"),
+    }
+}
+
+fn amm_config(value: &Value) -> AmmConfig {
+    let fee_rates = |value: &Value| FeeRates {
+        trade_fee_rate: rate(&value["trade_fee_rate"]),
+        protocol_fee_rate: rate(&value["protocol_fee_rate"]),
+    };
+    AmmConfig {
+        trade_fee_rate: rate(&value["trade_fee_rate"]),
+        protocol_fee_rate: rate(&value["protocol_fee_rate"]),
+        ratio_change_tolerance_rate: rate(&value["ratio_change_tolerance_rate"]),
+        host_fee_rate: rate(&value["host_fee_rate"]),
+        host_fee_source: host_fee_source(&value["host_fee_source"]),
+        directional_fee_rates: match &value["directional_fee_rates"] {
+            Value::Null => None,
+            rates => Some(DirectionalFeeRates {
+                x_to_y: fee_rates(&rates["x_to_y"]),
+                y_to_x: fee_rates(&rates["y_to_x"]),
+            }),
+        },
+        launch_fee_schedule: None,
+        fee_side: match value["fee_side"].as_str().unwrap() {
+            "Input" => FeeSide::Input,
+            "Output" => FeeSide::Output,
+            side => panic!("unknown fee side {side}"),
+        },
+    }
+}
+
+fn error_value(error: MathError) -> Value {
+    json!({ "error": error.name() })
+}
+
+// Inputs
+
+const U64_MAX: u64 = u64::MAX;
+
+/// Config with 0.3% trade fee, 20% of it to the protocol, and a tolerance
+/// that never trips
+fn config() -> Value {
+    json!({
+        "trade_fee_rate": "3000",
+        "protocol_fee_rate": "200000",
+        "ratio_change_tolerance_rate": MAX_PERCENTAGE.to_string(),
+        "host_fee_rate": "0",
+        "host_fee_source": "TradeFee",
+        "directional_fee_rates": null,
+        "fee_side": "Input",
+    })
+}
+
+fn with(mut value: Value, overrides: Value) -> Value {
+    for (key, item) in overrides.as_object().unwrap() {
+        value[key] = item.clone();
+    }
+    value
+}
+
+fn pool(reserve_x: u64, reserve_y: u64) -> Value {
+    let zero = "0";
+    json!({
+        "protocol_fee_x": zero,
+        "protocol_fee_y": zero,
+        "user_locked_x": zero,
+        "user_locked_y": zero,
+        "locked_x": zero,
+        "locked_y": zero,
+        "reserve_x_balance": reserve_x.to_string(),
+        "reserve_y_balance": reserve_y.to_string(),
+        "lp_fee_x": zero,
+        "lp_fee_y": zero,
+    })
+}
+
+fn quote_input(exchange_in: u64, is_swap_x_to_y: bool, amm_config: Value, pool: Value) -> (&'static str, Value) {
+    ("quote", json!({
+        "exchange_in": exchange_in.to_string(),
+        "is_swap_x_to_y": is_swap_x_to_y,
+        "amm_config": amm_config,
+        "pool": pool,
+    }))
+}
+
+fn swap_input(
+    source_amount: u128,
+    pool_source_amount: u128,
+    pool_destination_amount: u128,
+    trade_fee_rate: u64,
+    protocol_fee_rate: u64,
+) -> (&'static str, Value) {
+    ("swap", json!({
+        "source_amount": source_amount.to_string(),
+        "pool_source_amount": pool_source_amount.to_string(),
+        "pool_destination_amount": pool_destination_amount.to_string(),
+        "trade_fee_rate": trade_fee_rate.to_string(),
+        "protocol_fee_rate": protocol_fee_rate.to_string(),
+    }))
+}
+
+fn rebalance_input(
+    (to_amount_swapped, current_source_amount, current_destination_amount): (u64, u64, u64),
+    (original_source_amount, original_destination_amount): (u64, u64),
+    ratio_change_tolerance_rate: u64,
+) -> (&'static str, Value) {
+    ("rebalance_pool_ratio", json!({
+        "to_amount_swapped": to_amount_swapped.to_string(),
+        "current_source_amount": current_source_amount.to_string(),
+        "current_destination_amount": current_destination_amount.to_string(),
+        "original_source_amount": original_source_amount.to_string(),
+        "original_destination_amount": original_destination_amount.to_string(),
+        "ratio_change_tolerance_rate": ratio_change_tolerance_rate.to_string(),
+    }))
+}
+
+/// Deterministic LCG for the sweeps, the corpus must not change between runs
+struct Lcg(u64);
+
+impl Lcg {
+    fn below(&mut self, bound: u64) -> u64 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (self.0 >> 16) % bound
+    }
+}
+
+fn inputs() -> Vec<(&'static str, Value)> {
+    let mut inputs = Vec::new();
+
+    // quote: tiny reserves, extreme ratios, fee sides and rates
+    for (exchange_in, reserve_x, reserve_y) in [
+        (1, 1, 1),
+        (1, 2, 3),
+        (2, 2, 3),
+        (10, 3, 7),
+        (1_000, 1_000_000, 2_000_000),
+        (10_000, 1_000_000, 2_000_000),
+        (999_999, 1_000_000, 2_000_000),
+        (1, 1, 1_000_000_000_000_000_000),
+        (1_000_000, 1, 1_000_000_000_000_000_000),
+        (1_000, 1_000_000_000_000_000_000, 1),
+        (1_000_000_000_000, 9_000_000_000_000_000_000, 4_000_000_000_000_000_000),
+        (U64_MAX / 2, U64_MAX / 2, U64_MAX / 2),
+        (U64_MAX, U64_MAX, U64_MAX),
+        (0, 1_000_000, 2_000_000),
+    ] {
+        for is_swap_x_to_y in [true, false] {
+            for fee_side in ["Input", "Output"] {
+                let amm_config = with(config(), json!({ "fee_side": fee_side }));
+                inputs.push(quote_input(exchange_in, is_swap_x_to_y, amm_config, pool(reserve_x, reserve_y)));
+            }
+        }
+    }
+    for (trade_fee_rate, protocol_fee_rate) in [(0, 0), (1, 1_000_000), (999_999, 500_000), (100_000, 0)] {
+        let amm_config = with(config(), json!({
+            "trade_fee_rate": trade_fee_rate.to_string(),
+            "protocol_fee_rate": protocol_fee_rate.to_string(),
+        }));
+        inputs.push(quote_input(123_456, true, amm_config, pool(10_000_000, 30_000_000)));
+    }
+    for (host_fee_rate, host_fee_source) in [(100_000, "TradeFee"), (800_000, "TradeFee"), (500_000, "ProtocolFee")] {
+        let amm_config = with(config(), json!({
+            "host_fee_rate": host_fee_rate.to_string(),
+            "host_fee_source": host_fee_source,
+        }));
+        inputs.push(quote_input(250_000, false, amm_config, pool(5_000_000, 7_000_000)));
+    }
+    let directional = with(config(), json!({
+        "directional_fee_rates": {
+            "x_to_y": { "trade_fee_rate": "1000", "protocol_fee_rate": "0" },
+            "y_to_x": { "trade_fee_rate": "9000", "protocol_fee_rate": "500000" },
+        },
+    }));
+    for is_swap_x_to_y in [true, false] {
+        inputs.push(quote_input(50_000, is_swap_x_to_y, directional.clone(), pool(8_000_000, 2_000_000)));
+    }
+    // fee buckets and locks are not part of the curve
+    let busy_pool = with(pool(5_000_000, 7_000_000), json!({
+        "protocol_fee_x": "1000",
+        "protocol_fee_y": "2000",
+        "user_locked_x": "300",
+        "user_locked_y": "400",
+        "locked_x": "20000",
+        "locked_y": "30000",
+        "lp_fee_x": "50",
+        "lp_fee_y": "60",
+    }));
+    for is_swap_x_to_y in [true, false] {
+        inputs.push(quote_input(250_000, is_swap_x_to_y, config(), busy_pool.clone()));
+    }
+    inputs.push(quote_input(1, true, config(), with(pool(1_000, 1_000), json!({ "locked_y": "1000" }))));
+    // tolerance boundaries: the integer from_to_lock cannot keep 1000:2003 within 1 ppm
+    for tolerance in [1, 10, 100, 1_000, 10_000] {
+        let amm_config = with(config(), json!({
+            "trade_fee_rate": "0",
+            "ratio_change_tolerance_rate": tolerance.to_string(),
+        }));
+        for exchange_in in [13, 100, 500] {
+            inputs.push(quote_input(exchange_in, true, amm_config.clone(), pool(1_000, 2_003)));
+        }
+    }
+    let mut lcg = Lcg(42);
+    for _ in 0..100 {
+        let reserve_x = lcg.below(1 << 40) + 1_000;
+        let reserve_y = lcg.below(1 << 40) + 1_000;
+        let amm_config = with(config(), json!({
+            "trade_fee_rate": lcg.below(20_000).to_string(),
+            "protocol_fee_rate": lcg.below(MAX_PERCENTAGE + 1).to_string(),
+            "ratio_change_tolerance_rate": (lcg.below(MAX_PERCENTAGE) + 1).to_string(),
+            "fee_side": if lcg.below(2) == 0 { "Input" } else { "Output" },
+        }));
+        let exchange_in = lcg.below(reserve_x) + 1;
+        inputs.push(quote_input(exchange_in, lcg.below(2) == 0, amm_config, pool(reserve_x, reserve_y)));
+    }
+
+    // swap
+    for (source_amount, pool_source_amount, pool_destination_amount) in [
+        (1, 1, 1),
+        (1, 1, 2),
+        (1_000, 1, 1),
+        (1_000, 1_000_000, 2_000_000),
+        (1, 1_000_000_000_000_000_000, 1),
+        (1_000_000_000_000_000_000, 1, 1_000_000_000_000_000_000),
+        (u128::from(U64_MAX), u128::from(U64_MAX), u128::from(U64_MAX)),
+    ] {
+        for (trade_fee_rate, protocol_fee_rate) in [(0, 0), (3_000, 200_000), (999_999, 1_000_000)] {
+            inputs.push(swap_input(
+                source_amount,
+                pool_source_amount,
+                pool_destination_amount,
+                trade_fee_rate,
+                protocol_fee_rate,
+            ));
+        }
+    }
+    for _ in 0..50 {
+        let pool_source_amount = u128::from(lcg.below(1 << 50) + 1);
+        let pool_destination_amount = u128::from(lcg.below(1 << 50) + 1);
+        inputs.push(swap_input(
+            u128::from(lcg.below(1 << 50) + 1),
+            pool_source_amount,
+            pool_destination_amount,
+            lcg.below(MAX_PERCENTAGE),
+            lcg.below(MAX_PERCENTAGE + 1),
+        ));
+    }
+
+    // rebalance_pool_ratio, the cases of `test_from_to_lock_transition_manually` first
+    for ((to_amount_swapped, current_source, current_destination), original, tolerance) in [
+        ((500, 100, 1_000_000), (100, 1_000_000), 100),
+        ((500, 100, 1_000_000), (100, 1_000_000), 499),
+        ((500, 100, 1_000_000), (100, 1_000_000), 500),
+        ((500, 100, 1_000_000), (100, 1_000_000), 501),
+        ((1_980_148_883, 1_000_000, 2_000_000_000), (1_000_000, 2_000_000_000), 100),
+        ((1_980_148_883, 1_000_000, 2_000_000_000), (1_000_000, 2_000_000_000), 40),
+        ((10_000_000, 9_926, 19_851_117), (1_000_000, 2_000_000_000), 40),
+        ((49, 1, 100), (1, 100), 990_000),
+        ((100, 1, 100), (1, 100), 990_000),
+        ((100, 0, 100), (1, 100), 1_000_000),
+        ((100, 100, 0), (1, 100), 1_000_000),
+        ((0, 1_000, 2_003), (1_000, 2_003), 1),
+        ((1, 1_000, 2_003), (1_000, 2_003), 1),
+        ((26, 1_013, 2_003), (1_000, 2_003), 1),
+        ((26, 1_013, 2_003), (1_000, 2_003), 1_000_000),
+        ((1, U64_MAX, U64_MAX), (U64_MAX, U64_MAX), 1),
+        ((U64_MAX - 1, U64_MAX, U64_MAX), (U64_MAX, U64_MAX), 1_000_000),
+    ] {
+        inputs.push(rebalance_input((to_amount_swapped, current_source, current_destination), original, tolerance));
+    }
+
+    // deposit_lp and withdraw_lp, within the products that fit a u64
+    for (token_a_amount, token_b_amount, total_lp_supply, token_a_reserve, token_b_reserve) in [
+        (1, 1, 0, 0, 0),
+        (1_000, 4_000, 0, 0, 0),
+        (2, 3, 0, 0, 0),
+        (U64_MAX, U64_MAX, 0, 0, 0),
+        // an f64 sqrt of the product mints 2^53, one less
+        ((1 << 53) + 1, (1 << 53) + 1, 0, 0, 0),
+        (1_000, 3_000, 1_000_000, 1_000_000, 2_000_000),
+        (1, 1, 3, 7, 11),
+        (1_000_000_000, 1, 1_000_000_000, 1_000_000_000, 1),
+    ] {
+        inputs.push(("deposit_lp", json!({
+            "token_a_amount": token_a_amount.to_string(),
+            "token_b_amount": token_b_amount.to_string(),
+            "total_lp_supply": total_lp_supply.to_string(),
+            "token_a_reserve": token_a_reserve.to_string(),
+            "token_b_reserve": token_b_reserve.to_string(),
+        })));
+    }
+    for (lp_tokens, total_lp_supply, token_a_reserve, token_b_reserve) in [
+        (1_000, 0, 1_000_000, 2_000_000),
+        (1_000, 1_000_000, 1_000_000, 2_000_000),
+        (1, 3, 7, 11),
+        (1_000_000, 1_000_000, 1_000_000, 2_000_000),
+        (1, U64_MAX, U64_MAX, 1),
+    ] {
+        inputs.push(("withdraw_lp", json!({
+            "lp_tokens": lp_tokens.to_string(),
+            "total_lp_supply": total_lp_supply.to_string(),
+            "token_a_reserve": token_a_reserve.to_string(),
+            "token_b_reserve": token_b_reserve.to_string(),
+        })));
+    }
+
+    // fee helpers, including rates over 100%
+    for amount in [0, 1, 333, 1_000_001, u128::from(U64_MAX), u128::MAX / 1_000_000] {
+        for fee_rate in [0, 1, 3_000, 999_999, 1_000_000, 1_000_001] {
+            inputs.push(("get_trade_fee", json!({ "amount": amount.to_string(), "trade_fee_rate": fee_rate.to_string() })));
+            inputs.push((
+                "get_protocol_fee",
+                json!({ "amount": amount.to_string(), "protocol_fee_rate": fee_rate.to_string() }),
+            ));
+        }
+    }
+    for (trade_fee, protocol_fee) in [(0, 0), (3_000, 600), (3_000, 3_000), (1, 0), (u128::from(U64_MAX), 1)] {
+        for host_fee_rate in [0, 100_000, 1_000_000] {
+            for host_fee_source in ["TradeFee", "ProtocolFee"] {
+                inputs.push(("split_host_fee", json!({
+                    "trade_fee": trade_fee.to_string(),
+                    "protocol_fee": protocol_fee.to_string(),
+                    "host_fee_rate": host_fee_rate.to_string(),
+                    "host_fee_source": host_fee_source,
+                })));
+            }
+        }
+    }
+
+    inputs
+}
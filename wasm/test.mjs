@@ -0,0 +1,187 @@
+// Compares the wasm bindings against the native library.
+//
+// Builds the `wasm` feature for wasm32-unknown-unknown, generates Node glue
+// with `wasm-bindgen` (the CLI version must match the crate's), and runs the
+// same cases through `examples/native_cases.rs`.
+//
+//   rustup target add wasm32-unknown-unknown
+//   cargo install wasm-bindgen-cli --version <wasm-bindgen version in Cargo.lock>
+//   cd wasm && npm test
+
+import assert from "node:assert/strict";
+import { execFileSync } from "node:child_process";
+import { createRequire } from "node:module";
+import path from "node:path";
+import { test } from "node:test";
+import { fileURLToPath } from "node:url";
+
+const root = path.resolve(path.dirname(fileURLToPath(import.meta.url)), "..");
+const run = (command, args, input) =>
+  execFileSync(command, args, { cwd: root, input, encoding: "utf8", stdio: ["pipe", "pipe", "inherit"] });
+
+run("cargo", [
+  "rustc", "--release", "--lib", "--crate-type", "cdylib",
+  "--target", "wasm32-unknown-unknown", "--features", "wasm",
+]);
+run("wasm-bindgen", [
+  "--target", "nodejs",
+  "--out-dir", "wasm/pkg",
+  "target/wasm32-unknown-unknown/release/dex_math.wasm",
+]);
+const wasm = createRequire(import.meta.url)("./pkg/dex_math.js");
+
+const U64_MAX = "18446744073709551615";
+
+const ammConfig = (overrides = {}) => ({
+  trade_fee_rate: 3000,
+  protocol_fee_rate: 200000,
+  ratio_change_tolerance_rate: 1000000,
+  host_fee_rate: 0,
+  host_fee_source: "TradeFee",
+  fee_side: "Input",
+  ...overrides,
+});
+
+const pool = (reserveX, reserveY, overrides = {}) => ({
+  reserve_x_balance: reserveX,
+  reserve_y_balance: reserveY,
+  ...overrides,
+});
+
+const schedule = (decay) => ({
+  start_fee_rate: 500000,
+  start: 100,
+  duration: 1000,
+  decay,
+});
+
+const cases = [
+  { fn: "quote", args: ["10000", true, ammConfig(), pool(1000000, 2000000)] },
+  { fn: "quote", args: ["10000", false, ammConfig({ fee_side: "Output" }), pool(1000000, 2000000)] },
+  {
+    fn: "quote",
+    args: [
+      "250000",
+      true,
+      ammConfig({ host_fee_rate: 100000, host_fee_source: "ProtocolFee" }),
+      pool(5000000, 7000000, { protocol_fee_x: 1000, locked_y: 20000, user_locked_x: 300, lp_fee_y: 50 }),
+    ],
+  },
+  {
+    fn: "quote",
+    args: [
+      "1000000000000",
+      false,
+      ammConfig({ directional_fee_rates: { x_to_y: { trade_fee_rate: 1000, protocol_fee_rate: 0 }, y_to_x: { trade_fee_rate: 9000, protocol_fee_rate: 500000 } } }),
+      pool("9000000000000000000", "4000000000000000000"),
+    ],
+  },
+  { fn: "quote", args: ["1", true, ammConfig({ trade_fee_rate: 1000000 }), pool(1000, 2000)] },
+  { fn: "quoteAt", args: ["400", "10000", true, ammConfig({ launch_fee_schedule: schedule("Linear") }), pool(1000000, 2000000)] },
+  { fn: "depositLp", args: ["1000", "4000", "0", "0", "0"] },
+  { fn: "depositLp", args: ["1000", "3000", "1000000", "1000000", "2000000"] },
+  { fn: "withdrawLp", args: ["1000", "1000000", "1000000", "2000000"] },
+  { fn: "depositLp", args: ["1", "1", "1000", "0", "1000"] },
+  { fn: "withdrawLp", args: ["1000", "0", "1000000", "2000000"] },
+  { fn: "withdrawLp", args: [U64_MAX, "1000", "2", "2"] },
+  // sqrt(k) grew by 10% since kLast, past u64 in the second pool
+  { fn: "depositLpWithProtocolFee", args: ["1000", "2000", "1000000", pool(1000000, 2000000), "1652892561983", "500000"] },
+  { fn: "depositLpWithProtocolFee", args: ["9000000000000000", "9000000000000000", "1000000", pool("9000000000000000000", "9000000000000000000"), "66942148760330578512396694214876033057", "500000"] },
+  { fn: "withdrawLpWithProtocolFee", args: ["1000", "1000000", pool(1000000, 2000000), "1652892561983", "500000"] },
+  // the LP fee buckets are paid out with the withdrawal and count towards sqrt(k)
+  { fn: "withdrawLpWithProtocolFee", args: ["1000", "1000000", pool(1010000, 2020000, { lp_fee_x: 10000, lp_fee_y: 20000 }), "1652892561983", "500000"] },
+  { fn: "withdrawLpWithProtocolFee", args: ["1000001", "1000000", pool(1000000, 2000000), "1652892561983", "500000"] },
+  { fn: "rebalancePoolRatio", args: ["100000", "1100000", "1900000", "1000000", "2000000", "10000"] },
+  { fn: "rebalancePoolRatio", args: ["1999999", "1000000", "2000000", "1000000", "2000000", "10000"] },
+  { fn: "getTradeFee", args: ["1000001", "3000"] },
+  { fn: "getTradeFee", args: [U64_MAX, "1000000"] },
+  { fn: "getProtocolFee", args: ["2999", "200000"] },
+  { fn: "splitHostFee", args: ["3000", "600", "100000", "TradeFee"] },
+  { fn: "splitHostFee", args: ["3000", "600", "100000", "ProtocolFee"] },
+  { fn: "effectiveFeeRate", args: [schedule("Linear"), "3000", "600"] },
+  { fn: "effectiveFeeRate", args: [schedule({ Exponential: { half_life: 50 } }), "3000", "250"] },
+  { fn: "effectiveFeeRate", args: [schedule("Linear"), "3000", "2000"] },
+];
+
+// deterministic sweep over sizes, directions and fee sides
+let seed = 42n;
+const next = (bound) => {
+  seed = (seed * 6364136223846793005n + 1442695040888963407n) & ((1n << 64n) - 1n);
+  return (seed >> 16n) % bound;
+};
+for (let index = 0; index < 200; index++) {
+  const reserveX = next(1n << 40n) + 1000n;
+  const reserveY = next(1n << 40n) + 1000n;
+  const config = ammConfig({
+    trade_fee_rate: Number(next(20000n)),
+    fee_side: next(2n) === 0n ? "Input" : "Output",
+  });
+  cases.push({
+    fn: "quote",
+    args: [String(next(reserveX) + 1n), next(2n) === 0n, config, pool(String(reserveX), String(reserveY))],
+  });
+}
+
+const snakeCase = (key) => key.replace(/(?<=.)[A-Z]/g, (char) => `_${char.toLowerCase()}`);
+
+// wasm result in the native example's format
+const normalize = (value) => {
+  if (typeof value === "bigint") return value.toString();
+  if (Array.isArray(value)) return value.map(normalize);
+  if (value !== null && typeof value === "object") {
+    return Object.fromEntries(Object.entries(value).map(([key, item]) => [snakeCase(key), normalize(item)]));
+  }
+  return value;
+};
+
+const callWasm = ({ fn, args }) => {
+  try {
+    return normalize(wasm[fn](...args));
+  } catch (error) {
+    return { error: error.name };
+  }
+};
+
+test("wasm matches native", () => {
+  const native = JSON.parse(run("cargo", ["run", "--quiet", "--example", "native_cases", "--features", "cli"], JSON.stringify(cases)));
+  assert.equal(native.length, cases.length);
+  cases.forEach((testCase, index) => {
+    assert.deepEqual(callWasm(testCase), native[index], `${testCase.fn} ${JSON.stringify(testCase.args)}`);
+  });
+});
+
+test("results are camelCase objects of bigints", () => {
+  const output = wasm.quote(10000n, true, ammConfig(), pool(1000000, 2000000));
+  assert.deepEqual(Object.keys(output).sort(), [
+    "fromAmount", "fromToLock", "hostFee", "protocolFee", "toAmount", "tradeFee", "tradeFeeRate",
+  ]);
+  assert.equal(typeof output.toAmount, "bigint");
+  assert.equal(wasm.depositLp(1000n, 4000n, 0n, 0n, 0n), 2000n);
+  const deposit = wasm.depositLpWithProtocolFee(9000000000000000n, 9000000000000000n, 1000000n, pool("9000000000000000000", "9000000000000000000"), "66942148760330578512396694214876033057", 500000);
+  assert.equal(typeof deposit.kLast, "bigint");
+  assert.ok(deposit.kLast > 2n ** 64n);
+});
+
+test("u64 arguments take bigints, strings and safe integers", () => {
+  const camelConfig = { tradeFeeRate: 3000n, protocolFeeRate: "200000", ratioChangeToleranceRate: 1000000, hostFeeRate: 0, hostFeeSource: "TradeFee", feeSide: "Input" };
+  const camelPool = { reserveXBalance: "1000000", reserveYBalance: 2000000n };
+  const expected = wasm.quote("10000", true, ammConfig(), pool(1000000, 2000000));
+  assert.deepEqual(wasm.quote(10000n, true, camelConfig, camelPool), expected);
+  assert.deepEqual(wasm.quote(10000, true, camelConfig, camelPool), expected);
+
+  assert.equal(wasm.getTradeFee(BigInt(U64_MAX), 1000000n), BigInt(U64_MAX));
+  for (const bad of [-1, 1.5, "abc", "", 2n ** 64n, null, Number.MAX_SAFE_INTEGER + 2]) {
+    assert.throws(() => wasm.getTradeFee(bad, 3000), TypeError, String(bad));
+  }
+});
+
+test("math errors carry the ErrorCode", () => {
+  assert.throws(() => wasm.getTradeFee(1000, 1000001), { name: "MathLibInvalidRate", code: 6005 });
+  assert.throws(
+    () => wasm.withdrawLpWithProtocolFee(1000001, 1000000, pool(1000000, 2000000), 0, 500000),
+    { name: "MathLibInsufficientLpSupply", code: 6013 },
+  );
+  // the unchecked deposit_lp / withdraw_lp would divide by zero or overflow
+  assert.throws(() => wasm.depositLp(1, 1, 1000, 0, 1000), { name: "MathLibMathOverflow", code: 6000 });
+  assert.throws(() => wasm.withdrawLp(BigInt(U64_MAX), 1000, 2, 2), { name: "MathLibMathOverflow" });
+});